@@ -11,8 +11,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .compile(
             &[
                 "proto/flightmngr/planes.proto",
+                "proto/flightmngr/aircraft_types.proto",
+                "proto/flightmngr/airlines.proto",
+                "proto/flightmngr/cabin.proto",
                 "proto/flightmngr/airports.proto",
                 "proto/flightmngr/flights.proto",
+                "proto/flightmngr/routes.proto",
+                "proto/flightmngr/schedules.proto",
+                "proto/flightmngr/ticketing.proto",
             ],
             &["proto"],
         )?;
@@ -0,0 +1,12 @@
+alter table flight_cancellations add column id uuid primary key default gen_random_uuid();
+alter table flight_delays add column id uuid primary key default gen_random_uuid();
+alter table flight_departure_gates add column id uuid primary key default gen_random_uuid();
+alter table flight_arrival_gates add column id uuid primary key default gen_random_uuid();
+
+create table flight_retractions (
+    id uuid primary key default gen_random_uuid(),
+    flight_id uuid not null references flights(id),
+    timestamp timestamp with time zone not null default now(),
+    event_id uuid not null unique,
+    reason varchar
+);
@@ -0,0 +1,53 @@
+create table flight_current_state (
+    flight_id uuid primary key references flights(id),
+    expected_departure_time timestamp with time zone,
+    expected_arrival_time timestamp with time zone,
+    departure_gate varchar,
+    arrival_gate varchar,
+    is_cancelled boolean not null,
+    status varchar not null,
+    version bigint not null
+);
+
+create index flight_current_state_departure_idx on flight_current_state (expected_departure_time);
+
+-- current state of each flight folded from its status events, not retracted ones,
+-- see flights::queries::refresh_flight_state
+create view flight_state_fold as
+select
+    f.id as flight_id,
+    d.departure_time as expected_departure_time,
+    d.arrival_time as expected_arrival_time,
+    gd.gate as departure_gate,
+    ga.gate as arrival_gate,
+    c.id is not null as is_cancelled,
+    case when c.id is not null then 'cancelled' when d.id is not null then 'delayed' else 'scheduled' end as status,
+    (select count(*) from flight_cancellations where flight_id = f.id)
+        + (select count(*) from flight_delays where flight_id = f.id)
+        + (select count(*) from flight_departure_gates where flight_id = f.id)
+        + (select count(*) from flight_arrival_gates where flight_id = f.id)
+        + (select count(*) from flight_retractions where flight_id = f.id) as version
+from flights f
+left join lateral (
+    select id from flight_cancellations e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    limit 1
+) c on true
+left join lateral (
+    select id, departure_time, arrival_time from flight_delays e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) d on true
+left join lateral (
+    select gate from flight_departure_gates e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) gd on true
+left join lateral (
+    select gate from flight_arrival_gates e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) ga on true;
+
+-- populate the projection for existing flights
+insert into flight_current_state select * from flight_state_fold;
@@ -0,0 +1,11 @@
+create table idempotency_keys (
+    method varchar not null,
+    key varchar not null,
+    response bytea not null,
+    -- fingerprint of the request the response was recorded for
+    request_hash bytea not null,
+    created_at timestamp with time zone not null default now(),
+    primary key (method, key)
+);
+
+create index idempotency_keys_created_at_idx on idempotency_keys (created_at);
@@ -0,0 +1,6 @@
+-- events recorded in the same transaction must still be ordered
+alter table flight_cancellations alter column timestamp set default clock_timestamp();
+alter table flight_delays alter column timestamp set default clock_timestamp();
+alter table flight_departure_gates alter column timestamp set default clock_timestamp();
+alter table flight_arrival_gates alter column timestamp set default clock_timestamp();
+alter table flight_retractions alter column timestamp set default clock_timestamp();
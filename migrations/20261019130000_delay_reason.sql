@@ -0,0 +1 @@
+alter table flight_delays add column reason varchar;
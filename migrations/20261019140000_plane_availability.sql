@@ -0,0 +1,68 @@
+create table plane_unavailabilities (
+    id uuid primary key,
+    plane_id uuid not null references planes(id),
+    kind varchar not null,
+    start_time timestamp with time zone not null,
+    end_time timestamp with time zone,
+    reason varchar not null,
+    timestamp timestamp with time zone not null default now()
+);
+
+create index plane_unavailabilities_plane_idx on plane_unavailabilities (plane_id, start_time);
+
+create table flight_plane_changes (
+    id uuid primary key default gen_random_uuid(),
+    flight_id uuid not null references flights(id),
+    timestamp timestamp with time zone not null default clock_timestamp(),
+    plane_id uuid not null references planes(id)
+);
+
+-- plane changes count in the version, and the plane currently assigned to each flight
+create or replace view flight_state_fold as
+select
+    f.id as flight_id,
+    d.departure_time as expected_departure_time,
+    d.arrival_time as expected_arrival_time,
+    gd.gate as departure_gate,
+    ga.gate as arrival_gate,
+    c.id is not null as is_cancelled,
+    case when c.id is not null then 'cancelled' when d.id is not null then 'delayed' else 'scheduled' end as status,
+    (select count(*) from flight_cancellations where flight_id = f.id)
+        + (select count(*) from flight_delays where flight_id = f.id)
+        + (select count(*) from flight_departure_gates where flight_id = f.id)
+        + (select count(*) from flight_arrival_gates where flight_id = f.id)
+        + (select count(*) from flight_plane_changes where flight_id = f.id)
+        + (select count(*) from flight_retractions where flight_id = f.id) as version,
+    coalesce(p.plane_id, f.plane_id) as plane_id
+from flights f
+left join lateral (
+    select id from flight_cancellations e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    limit 1
+) c on true
+left join lateral (
+    select id, departure_time, arrival_time from flight_delays e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) d on true
+left join lateral (
+    select gate from flight_departure_gates e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) gd on true
+left join lateral (
+    select gate from flight_arrival_gates e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) ga on true
+left join lateral (
+    select plane_id from flight_plane_changes e
+    where e.flight_id = f.id and e.id not in (select event_id from flight_retractions)
+    order by e.timestamp desc limit 1
+) p on true;
+
+alter table flight_current_state add column plane_id uuid references planes(id);
+update flight_current_state s set plane_id = v.plane_id from flight_state_fold v where v.flight_id = s.flight_id;
+alter table flight_current_state alter column plane_id set not null;
+
+create index flight_current_state_plane_idx on flight_current_state (plane_id);
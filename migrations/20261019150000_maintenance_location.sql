@@ -0,0 +1 @@
+alter table plane_unavailabilities add column location_id uuid references airports(id);
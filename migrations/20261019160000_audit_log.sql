@@ -0,0 +1,10 @@
+create table audit_log (
+    id uuid primary key default gen_random_uuid(),
+    entity varchar not null,
+    entity_id uuid not null,
+    action varchar not null,
+    actor varchar,
+    timestamp timestamp with time zone not null default clock_timestamp()
+);
+
+create index audit_log_entity_idx on audit_log (entity, entity_id, timestamp);
@@ -0,0 +1,8 @@
+alter table planes add column registration varchar;
+alter table planes add column icao_type varchar(4);
+alter table planes add column manufacturer varchar;
+alter table planes add column in_service_date date;
+alter table planes add column retirement_date date;
+
+-- a registration can be reused once the plane carrying it is retired
+create unique index planes_active_registration_idx on planes (registration) where not deleted;
@@ -0,0 +1,26 @@
+create table aircraft_types (
+    id uuid primary key,
+    icao_designator varchar(4) not null,
+    name varchar not null,
+    typical_seats int not null,
+    cargo_capacity_kg int not null,
+    max_range_km int not null,
+    cruise_speed_kmh int not null,
+    min_turnaround_minutes int not null,
+    deleted boolean not null default false
+);
+
+-- capacities of a plane with a type are overrides of the type defaults
+alter table planes add column type_id uuid references aircraft_types(id);
+alter table planes alter column cabin_capacity drop not null;
+alter table planes alter column cargo_capacity_kg drop not null;
+alter table planes add constraint planes_capacity_check
+    check (type_id is not null or (cabin_capacity is not null and cargo_capacity_kg is not null));
+
+create view plane_capacities as
+select
+    planes.id as plane_id,
+    coalesce(planes.cabin_capacity, aircraft_types.typical_seats) as cabin_capacity,
+    coalesce(planes.cargo_capacity_kg, aircraft_types.cargo_capacity_kg) as cargo_capacity_kg
+from planes
+left join aircraft_types on aircraft_types.id = planes.type_id;
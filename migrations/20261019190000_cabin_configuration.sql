@@ -0,0 +1,16 @@
+create table plane_cabin_classes (
+    plane_id uuid not null references planes(id),
+    class varchar not null,
+    capacity int not null,
+    primary key (plane_id, class)
+);
+
+create table plane_seats (
+    plane_id uuid not null references planes(id),
+    row_number int not null,
+    letter varchar(1) not null,
+    class varchar not null,
+    exit_row boolean not null default false,
+    blocked boolean not null default false,
+    primary key (plane_id, row_number, letter)
+);
@@ -0,0 +1,7 @@
+create table flight_seat_inventory (
+    flight_id uuid not null references flights(id),
+    class varchar not null,
+    sold int not null default 0 check (sold >= 0),
+    overbooking_allowance int not null default 0 check (overbooking_allowance >= 0),
+    primary key (flight_id, class)
+);
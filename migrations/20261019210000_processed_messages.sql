@@ -0,0 +1,7 @@
+-- ids of the ticketing messages already applied, to ignore redeliveries
+create table processed_messages (
+    message_id varchar primary key,
+    processed_at timestamptz not null default now()
+);
+
+create index processed_messages_processed_at_idx on processed_messages (processed_at);
@@ -0,0 +1,8 @@
+create table cargo_shipments (
+    flight_id uuid not null references flights(id),
+    shipment_id varchar not null,
+    weight_kg int not null check (weight_kg > 0),
+    origin_id uuid not null references airports(id),
+    destination_id uuid not null references airports(id),
+    primary key (flight_id, shipment_id)
+);
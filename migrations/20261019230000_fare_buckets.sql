@@ -0,0 +1,10 @@
+create table fare_buckets (
+    flight_id uuid not null references flights(id),
+    booking_class varchar(2) not null,
+    cabin varchar not null,
+    currency char(3) not null,
+    -- in minor units of the currency
+    base_price bigint not null check (base_price >= 0),
+    seats_allotted int not null check (seats_allotted >= 0),
+    primary key (flight_id, booking_class)
+);
@@ -0,0 +1,25 @@
+-- IANA timezone of the airport, used to compute local dates
+alter table airports add column timezone varchar not null default 'UTC';
+
+create table airlines (
+    id uuid primary key,
+    iata varchar(2) not null,
+    icao varchar(3) not null,
+    name varchar not null,
+    callsign varchar not null,
+    deleted boolean not null default false
+);
+
+create unique index airlines_iata_key on airlines (iata) where not deleted;
+create unique index airlines_icao_key on airlines (icao) where not deleted;
+
+alter table flights add column carrier_id uuid references airlines(id);
+alter table flights add column flight_number varchar(5);
+-- scheduled departure date in the timezone of the origin airport
+alter table flights add column departure_local_date date;
+alter table flights add constraint flights_number_check
+    check ((carrier_id is null) = (flight_number is null));
+
+-- cancelled flights keep holding their number for the date
+create unique index flights_number_key
+    on flights (carrier_id, flight_number, departure_local_date);
@@ -0,0 +1,18 @@
+-- flights created before local dates were tracked
+update flights set departure_local_date = (departure_time at time zone airports.timezone)::date
+from airports where airports.id = flights.origin_id and departure_local_date is null;
+
+alter table flights alter column departure_local_date set not null;
+
+-- marketing flight numbers of other airlines on an operating flight
+create table flight_codeshares (
+    flight_id uuid not null references flights(id),
+    carrier_id uuid not null references airlines(id),
+    flight_number varchar(5) not null,
+    -- copied from the operating flight to enforce unique numbers per date
+    departure_local_date date not null,
+    primary key (flight_id, carrier_id)
+);
+
+create unique index flight_codeshares_number_key
+    on flight_codeshares (carrier_id, flight_number, departure_local_date);
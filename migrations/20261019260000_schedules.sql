@@ -0,0 +1,39 @@
+-- recurring flights, materialised as individual flights by the schedule generator
+create table schedules (
+    id uuid primary key,
+    carrier_id uuid references airlines(id),
+    flight_number varchar(5),
+    origin_id uuid not null references airports(id),
+    destination_id uuid not null references airports(id),
+    -- either a plane, or an aircraft type whose planes are assigned on generation
+    plane_id uuid references planes(id),
+    aircraft_type_id uuid references aircraft_types(id),
+    -- in the timezone of the origin airport
+    departure_local_time time not null,
+    -- in the timezone of the destination airport, days after the departure date
+    arrival_local_time time not null,
+    arrival_day_offset int not null check (arrival_day_offset between 0 and 2),
+    -- ISO days of the week, 1 is Monday
+    days_of_week int[] not null,
+    valid_from date not null,
+    valid_to date not null,
+    -- dates without a flight
+    exceptions date[] not null,
+    deleted boolean not null default false,
+    check ((carrier_id is null) = (flight_number is null)),
+    check ((plane_id is null) <> (aircraft_type_id is null)),
+    check (valid_from <= valid_to)
+);
+
+alter table flights add column schedule_id uuid references schedules(id);
+
+-- one flight per schedule and local departure date
+create unique index flights_schedule_key on flights (schedule_id, departure_local_date);
+
+-- status events recorded by the schedule generator, which do not make a flight modified
+create table flight_schedule_events (
+    event_id uuid primary key,
+    flight_id uuid not null references flights(id)
+);
+
+create index flight_schedule_events_flight_idx on flight_schedule_events (flight_id);
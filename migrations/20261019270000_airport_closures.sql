@@ -0,0 +1,10 @@
+create table airport_closures (
+    id uuid primary key,
+    airport_id uuid not null references airports(id),
+    start_time timestamp with time zone not null,
+    end_time timestamp with time zone not null,
+    reason varchar not null,
+    check (start_time < end_time)
+);
+
+create index airport_closures_airport_idx on airport_closures (airport_id, start_time);
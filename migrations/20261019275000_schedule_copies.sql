@@ -0,0 +1,7 @@
+-- schedules created by copying a season, so that a season is not copied twice
+create table schedule_copies (
+    schedule_id uuid primary key references schedules(id),
+    source_id uuid not null references schedules(id)
+);
+
+create index schedule_copies_source_idx on schedule_copies (source_id);
@@ -0,0 +1,5 @@
+-- null while the flight is a draft
+alter table flights add column published_at timestamptz;
+
+-- flights created before drafts existed were visible right away
+update flights set published_at = now();
@@ -0,0 +1,23 @@
+-- scheduled data of flights as of each change, to rebuild flights as they were published
+create table flight_schedule_history (
+    flight_id uuid not null references flights(id),
+    valid_from timestamp with time zone not null default now(),
+    plane_id uuid not null references planes(id),
+    origin_id uuid not null references airports(id),
+    destination_id uuid not null references airports(id),
+    departure_time timestamp with time zone not null,
+    arrival_time timestamp with time zone not null,
+    carrier_id uuid references airlines(id),
+    flight_number varchar(5),
+    primary key (flight_id, valid_from)
+);
+
+-- the earlier changes of existing flights are unknown
+insert into flight_schedule_history (
+    flight_id, valid_from, plane_id, origin_id, destination_id, departure_time, arrival_time,
+    carrier_id, flight_number
+)
+select
+    id, '-infinity', plane_id, origin_id, destination_id, departure_time, arrival_time,
+    carrier_id, flight_number
+from flights;
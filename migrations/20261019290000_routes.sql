@@ -0,0 +1,29 @@
+-- in decimal degrees, required to compute route distances
+alter table airports add column latitude double precision;
+alter table airports add column longitude double precision;
+alter table airports add constraint airports_coordinates_check check (
+    (latitude is null) = (longitude is null)
+    and latitude between -90 and 90
+    and longitude between -180 and 180
+);
+
+-- origin and destination pair flown by flights
+create table routes (
+    id uuid primary key,
+    origin_id uuid not null references airports(id),
+    destination_id uuid not null references airports(id),
+    -- great-circle distance between the airports
+    distance_km int not null,
+    deleted boolean not null default false,
+    check (origin_id <> destination_id)
+);
+
+create unique index routes_airports_key on routes (origin_id, destination_id) where not deleted;
+
+-- standard time from gate departure to gate arrival of an aircraft type on a route
+create table route_block_times (
+    route_id uuid not null references routes(id),
+    aircraft_type_id uuid not null references aircraft_types(id),
+    block_minutes int not null check (block_minutes > 0),
+    primary key (route_id, aircraft_type_id)
+);
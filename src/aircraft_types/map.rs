@@ -0,0 +1,53 @@
+use tonic::Status;
+
+use super::queries;
+use crate::proto;
+
+impl From<queries::AircraftType> for proto::flightmngr::AircraftType {
+    fn from(aircraft_type: queries::AircraftType) -> Self {
+        Self {
+            id: aircraft_type.id.to_string(),
+            icao_designator: aircraft_type.icao_designator,
+            name: aircraft_type.name,
+            typical_seats: aircraft_type.typical_seats as u32,
+            cargo_capacity_kg: aircraft_type.cargo_capacity_kg as u32,
+            max_range_km: aircraft_type.max_range_km as u32,
+            cruise_speed_kmh: aircraft_type.cruise_speed_kmh as u32,
+            min_turnaround_minutes: aircraft_type.min_turnaround_minutes as u32,
+            deleted: aircraft_type.deleted,
+        }
+    }
+}
+
+impl TryFrom<proto::flightmngr::AircraftType> for queries::AircraftTypeSpec {
+    type Error = Status;
+
+    fn try_from(aircraft_type: proto::flightmngr::AircraftType) -> Result<Self, Self::Error> {
+        let icao_designator = aircraft_type.icao_designator.trim().to_uppercase();
+        if !(2..=4).contains(&icao_designator.len())
+            || !icao_designator.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(Status::invalid_argument("'icao_designator'"));
+        }
+        if aircraft_type.cruise_speed_kmh == 0 {
+            return Err(Status::invalid_argument("'cruise_speed_kmh'"));
+        }
+
+        let int = |value: u32, field: &str| {
+            i32::try_from(value).map_err(|_| Status::invalid_argument(format!("'{field}'")))
+        };
+
+        Ok(Self {
+            icao_designator,
+            name: aircraft_type.name,
+            typical_seats: int(aircraft_type.typical_seats, "typical_seats")?,
+            cargo_capacity_kg: int(aircraft_type.cargo_capacity_kg, "cargo_capacity_kg")?,
+            max_range_km: int(aircraft_type.max_range_km, "max_range_km")?,
+            cruise_speed_kmh: int(aircraft_type.cruise_speed_kmh, "cruise_speed_kmh")?,
+            min_turnaround_minutes: int(
+                aircraft_type.min_turnaround_minutes,
+                "min_turnaround_minutes",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,144 @@
+use sqlx::{types::Uuid, PgConnection};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    datautils::parse_id,
+    db::{Database, DatabaseError},
+    idempotency,
+    proto::flightmngr::{
+        self, aircraft_types_server::AircraftTypes, CreateAircraftTypeRequest,
+        DeleteAircraftTypeRequest, GetAircraftTypeRequest, ListAircraftTypesRequest,
+        ListAircraftTypesResponse, UpdateAircraftTypeRequest,
+    },
+};
+
+mod map;
+mod queries;
+
+pub(crate) use queries::AircraftType;
+
+pub struct AircraftTypesApp {
+    db: Database,
+}
+
+#[tonic::async_trait]
+impl AircraftTypes for AircraftTypesApp {
+    async fn list_aircraft_types(
+        &self,
+        request: Request<ListAircraftTypesRequest>,
+    ) -> Result<Response<ListAircraftTypesResponse>, Status> {
+        let ListAircraftTypesRequest { show_deleted } = request.into_inner();
+        let mut t = self.db.begin().await?;
+
+        let aircraft_types = if show_deleted {
+            queries::list_aircraft_types_with_deleted(t.get_conn()).await?
+        } else {
+            queries::list_aircraft_types(t.get_conn()).await?
+        };
+
+        let aircraft_types = aircraft_types.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListAircraftTypesResponse { aircraft_types }))
+    }
+
+    async fn get_aircraft_type(
+        &self,
+        request: Request<GetAircraftTypeRequest>,
+    ) -> Result<Response<flightmngr::AircraftType>, Status> {
+        let GetAircraftTypeRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let aircraft_type = queries::get_aircraft_type(t.get_conn(), &id).await?.into();
+
+        Ok(Response::new(aircraft_type))
+    }
+
+    async fn create_aircraft_type(
+        &self,
+        request: Request<CreateAircraftTypeRequest>,
+    ) -> std::result::Result<Response<flightmngr::AircraftType>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let spec = request
+            .into_inner()
+            .aircraft_type
+            .unwrap_or_default()
+            .try_into()?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(a) = idempotency::replay(t.get_conn(), "CreateAircraftType", key).await? {
+            return Ok(Response::new(a));
+        }
+
+        let aircraft_type = queries::create_aircraft_type(t.get_conn(), spec)
+            .await?
+            .into();
+
+        idempotency::record(t.get_conn(), "CreateAircraftType", key, &aircraft_type).await?;
+
+        t.commit().await?;
+        Ok(Response::new(aircraft_type))
+    }
+
+    async fn update_aircraft_type(
+        &self,
+        request: Request<UpdateAircraftTypeRequest>,
+    ) -> std::result::Result<Response<flightmngr::AircraftType>, Status> {
+        let aircraft_type = request.into_inner().aircraft_type.unwrap_or_default();
+        let id = parse_id(&aircraft_type.id)?;
+        let spec = aircraft_type.try_into()?;
+        let mut t = self.db.begin().await?;
+
+        let aircraft_type = queries::update_aircraft_type(t.get_conn(), &id, spec)
+            .await?
+            .into();
+
+        t.commit().await?;
+        Ok(Response::new(aircraft_type))
+    }
+
+    async fn delete_aircraft_type(
+        &self,
+        request: Request<DeleteAircraftTypeRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        let DeleteAircraftTypeRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        if queries::count_planes(t.get_conn(), &id).await? > 0 {
+            return Err(Status::failed_precondition(
+                "aircraft type is used by active planes",
+            ));
+        }
+        queries::delete_aircraft_type(t.get_conn(), &id).await?;
+
+        t.commit().await?;
+        Ok(Response::new(()))
+    }
+}
+
+impl AircraftTypesApp {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+/// Ensure that an aircraft type exists and has not been deleted.
+pub(crate) async fn ensure_aircraft_type_active(
+    ex: &mut PgConnection,
+    id: &Uuid,
+) -> Result<AircraftType, Status> {
+    let aircraft_type = queries::get_aircraft_type(ex, id).await?;
+    if aircraft_type.deleted {
+        return Err(Status::failed_precondition("aircraft type is deleted"));
+    }
+
+    Ok(aircraft_type)
+}
+
+pub(crate) async fn get_aircraft_types(
+    ex: &mut PgConnection,
+    ids: &[Uuid],
+) -> Result<Vec<AircraftType>, DatabaseError> {
+    queries::get_aircraft_types(ex, ids).await
+}
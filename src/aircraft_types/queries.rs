@@ -0,0 +1,138 @@
+use sqlx::{types::Uuid, PgConnection};
+
+use crate::db::DatabaseError;
+
+type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+
+#[derive(Clone)]
+pub struct AircraftType {
+    pub id: Uuid,
+    pub icao_designator: String,
+    pub name: String,
+    pub typical_seats: i32,
+    pub cargo_capacity_kg: i32,
+    pub max_range_km: i32,
+    pub cruise_speed_kmh: i32,
+    pub min_turnaround_minutes: i32,
+    pub deleted: bool,
+}
+
+pub struct AircraftTypeSpec {
+    pub icao_designator: String,
+    pub name: String,
+    pub typical_seats: i32,
+    pub cargo_capacity_kg: i32,
+    pub max_range_km: i32,
+    pub cruise_speed_kmh: i32,
+    pub min_turnaround_minutes: i32,
+}
+
+pub async fn list_aircraft_types(ex: &mut PgConnection) -> Result<Vec<AircraftType>> {
+    let aircraft_types = sqlx::query_as!(
+        AircraftType,
+        "select * from aircraft_types where not deleted order by icao_designator"
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(aircraft_types)
+}
+
+pub async fn list_aircraft_types_with_deleted(ex: &mut PgConnection) -> Result<Vec<AircraftType>> {
+    let aircraft_types = sqlx::query_as!(
+        AircraftType,
+        "select * from aircraft_types order by icao_designator"
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(aircraft_types)
+}
+
+pub async fn get_aircraft_type(ex: &mut PgConnection, id: &Uuid) -> Result<AircraftType> {
+    let aircraft_type = sqlx::query_as!(
+        AircraftType,
+        "select * from aircraft_types where id = $1",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(aircraft_type)
+}
+
+pub async fn get_aircraft_types(ex: &mut PgConnection, ids: &[Uuid]) -> Result<Vec<AircraftType>> {
+    let aircraft_types = sqlx::query_as!(
+        AircraftType,
+        "select * from aircraft_types where id = any($1)",
+        ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(aircraft_types)
+}
+
+pub async fn create_aircraft_type(
+    ex: &mut PgConnection,
+    spec: AircraftTypeSpec,
+) -> Result<AircraftType> {
+    let aircraft_type = sqlx::query_as!(
+        AircraftType,
+        "insert into aircraft_types (id, icao_designator, name, typical_seats, cargo_capacity_kg, max_range_km, cruise_speed_kmh, min_turnaround_minutes) values (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7) returning *",
+        spec.icao_designator,
+        spec.name,
+        spec.typical_seats,
+        spec.cargo_capacity_kg,
+        spec.max_range_km,
+        spec.cruise_speed_kmh,
+        spec.min_turnaround_minutes
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(aircraft_type)
+}
+
+pub async fn update_aircraft_type(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    spec: AircraftTypeSpec,
+) -> Result<AircraftType> {
+    let aircraft_type = sqlx::query_as!(
+        AircraftType,
+        "update aircraft_types set icao_designator = $2, name = $3, typical_seats = $4, cargo_capacity_kg = $5, max_range_km = $6, cruise_speed_kmh = $7, min_turnaround_minutes = $8 where id = $1 returning *",
+        id,
+        spec.icao_designator,
+        spec.name,
+        spec.typical_seats,
+        spec.cargo_capacity_kg,
+        spec.max_range_km,
+        spec.cruise_speed_kmh,
+        spec.min_turnaround_minutes
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(aircraft_type)
+}
+
+pub async fn delete_aircraft_type(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
+    let res = sqlx::query!("update aircraft_types set deleted = true where id = $1", id)
+        .execute(ex)
+        .await?;
+
+    DatabaseError::ensure_single_affected(res)
+}
+
+/// Count the active planes of a type.
+pub async fn count_planes(ex: &mut PgConnection, id: &Uuid) -> Result<i64> {
+    let count = sqlx::query_scalar!(
+        r#"select count(*) as "count!" from planes where type_id = $1 and not deleted"#,
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,42 @@
+use tonic::Status;
+
+use super::queries;
+use crate::proto;
+
+impl From<queries::Airline> for proto::flightmngr::Airline {
+    fn from(airline: queries::Airline) -> Self {
+        Self {
+            id: airline.id.to_string(),
+            iata: airline.iata,
+            icao: airline.icao,
+            name: airline.name,
+            callsign: airline.callsign,
+            deleted: airline.deleted,
+        }
+    }
+}
+
+impl TryFrom<proto::flightmngr::Airline> for queries::AirlineSpec {
+    type Error = Status;
+
+    fn try_from(airline: proto::flightmngr::Airline) -> Result<Self, Self::Error> {
+        let iata = airline.iata.trim().to_uppercase();
+        if iata.len() != 2 || !iata.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(Status::invalid_argument("'iata'"));
+        }
+        let icao = airline.icao.trim().to_uppercase();
+        if icao.len() != 3 || !icao.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(Status::invalid_argument("'icao'"));
+        }
+        if airline.name.is_empty() {
+            return Err(Status::invalid_argument("'name'"));
+        }
+
+        Ok(Self {
+            iata,
+            icao,
+            name: airline.name,
+            callsign: airline.callsign.trim().to_uppercase(),
+        })
+    }
+}
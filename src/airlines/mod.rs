@@ -0,0 +1,166 @@
+use sqlx::{types::Uuid, PgConnection};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    datautils::parse_id,
+    db::{Database, DatabaseError},
+    idempotency,
+    proto::flightmngr::{
+        self, airlines_server::Airlines, CreateAirlineRequest, DeleteAirlineRequest,
+        GetAirlineRequest, ListAirlinesRequest, ListAirlinesResponse, UpdateAirlineRequest,
+    },
+};
+
+mod map;
+mod queries;
+
+pub(crate) use queries::Airline;
+
+pub struct AirlinesApp {
+    db: Database,
+}
+
+#[tonic::async_trait]
+impl Airlines for AirlinesApp {
+    async fn list_airlines(
+        &self,
+        request: Request<ListAirlinesRequest>,
+    ) -> Result<Response<ListAirlinesResponse>, Status> {
+        let ListAirlinesRequest { show_deleted } = request.into_inner();
+        let mut t = self.db.begin().await?;
+
+        let airlines = if show_deleted {
+            queries::list_airlines_with_deleted(t.get_conn()).await?
+        } else {
+            queries::list_airlines(t.get_conn()).await?
+        };
+
+        let airlines = airlines.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListAirlinesResponse { airlines }))
+    }
+
+    async fn get_airline(
+        &self,
+        request: Request<GetAirlineRequest>,
+    ) -> Result<Response<flightmngr::Airline>, Status> {
+        let GetAirlineRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let airline = queries::get_airline(t.get_conn(), &id).await?.into();
+
+        Ok(Response::new(airline))
+    }
+
+    async fn create_airline(
+        &self,
+        request: Request<CreateAirlineRequest>,
+    ) -> std::result::Result<Response<flightmngr::Airline>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let spec = request
+            .into_inner()
+            .airline
+            .unwrap_or_default()
+            .try_into()?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(a) = idempotency::replay(t.get_conn(), "CreateAirline", key).await? {
+            return Ok(Response::new(a));
+        }
+
+        ensure_codes_available(t.get_conn(), None, &spec).await?;
+        let airline = queries::create_airline(t.get_conn(), spec).await?.into();
+
+        idempotency::record(t.get_conn(), "CreateAirline", key, &airline).await?;
+
+        t.commit().await?;
+        Ok(Response::new(airline))
+    }
+
+    async fn update_airline(
+        &self,
+        request: Request<UpdateAirlineRequest>,
+    ) -> std::result::Result<Response<flightmngr::Airline>, Status> {
+        let airline = request.into_inner().airline.unwrap_or_default();
+        let id = parse_id(&airline.id)?;
+        let spec = airline.try_into()?;
+        let mut t = self.db.begin().await?;
+
+        ensure_codes_available(t.get_conn(), Some(&id), &spec).await?;
+        let airline = queries::update_airline(t.get_conn(), &id, spec)
+            .await?
+            .into();
+
+        t.commit().await?;
+        Ok(Response::new(airline))
+    }
+
+    async fn delete_airline(
+        &self,
+        request: Request<DeleteAirlineRequest>,
+    ) -> std::result::Result<Response<()>, Status> {
+        let DeleteAirlineRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        if queries::count_future_flights(t.get_conn(), &id).await? > 0 {
+            return Err(Status::failed_precondition(
+                "airline operates future flights",
+            ));
+        }
+        queries::delete_airline(t.get_conn(), &id).await?;
+
+        t.commit().await?;
+        Ok(Response::new(()))
+    }
+}
+
+impl AirlinesApp {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+async fn ensure_codes_available(
+    ex: &mut PgConnection,
+    id: Option<&Uuid>,
+    spec: &queries::AirlineSpec,
+) -> Result<(), Status> {
+    if let Some(other) = queries::get_conflicting_airlines(ex, id, spec)
+        .await?
+        .first()
+    {
+        return Err(Status::already_exists(format!(
+            "codes already used by airline {}",
+            other.id
+        )));
+    }
+
+    Ok(())
+}
+
+/// Ensure that an airline exists and has not been deleted.
+pub(crate) async fn ensure_airline_active(ex: &mut PgConnection, id: &Uuid) -> Result<(), Status> {
+    let airline = queries::get_airline(ex, id).await?;
+    if airline.deleted {
+        return Err(Status::failed_precondition("airline is deleted"));
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn get_airlines(
+    ex: &mut PgConnection,
+    ids: &[Uuid],
+) -> Result<Vec<Airline>, DatabaseError> {
+    queries::get_airlines(ex, ids).await
+}
+
+/// Get the active airline with an IATA or ICAO code.
+pub(crate) async fn get_airline_by_code(
+    ex: &mut PgConnection,
+    code: &str,
+) -> Result<Airline, DatabaseError> {
+    queries::get_airline_by_code(ex, &code.trim().to_uppercase()).await
+}
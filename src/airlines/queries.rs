@@ -0,0 +1,149 @@
+use sqlx::{types::Uuid, PgConnection};
+
+use crate::db::DatabaseError;
+
+type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+
+#[derive(Clone)]
+pub struct Airline {
+    pub id: Uuid,
+    pub iata: String,
+    pub icao: String,
+    pub name: String,
+    pub callsign: String,
+    pub deleted: bool,
+}
+
+pub struct AirlineSpec {
+    pub iata: String,
+    pub icao: String,
+    pub name: String,
+    pub callsign: String,
+}
+
+pub async fn list_airlines(ex: &mut PgConnection) -> Result<Vec<Airline>> {
+    let airlines = sqlx::query_as!(
+        Airline,
+        "select * from airlines where not deleted order by iata"
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(airlines)
+}
+
+pub async fn list_airlines_with_deleted(ex: &mut PgConnection) -> Result<Vec<Airline>> {
+    let airlines = sqlx::query_as!(Airline, "select * from airlines order by iata")
+        .fetch_all(ex)
+        .await?;
+
+    Ok(airlines)
+}
+
+pub async fn get_airline(ex: &mut PgConnection, id: &Uuid) -> Result<Airline> {
+    let airline = sqlx::query_as!(Airline, "select * from airlines where id = $1", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(airline)
+}
+
+pub async fn get_airlines(ex: &mut PgConnection, ids: &[Uuid]) -> Result<Vec<Airline>> {
+    let airlines = sqlx::query_as!(Airline, "select * from airlines where id = any($1)", ids)
+        .fetch_all(ex)
+        .await?;
+
+    Ok(airlines)
+}
+
+/// Get the active airline with an IATA or ICAO code.
+pub async fn get_airline_by_code(ex: &mut PgConnection, code: &str) -> Result<Airline> {
+    let airline = sqlx::query_as!(
+        Airline,
+        "select * from airlines where not deleted and (iata = $1 or icao = $1)",
+        code
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(airline)
+}
+
+/// Get the active airlines other than `id` using the IATA or ICAO code of a spec.
+pub async fn get_conflicting_airlines(
+    ex: &mut PgConnection,
+    id: Option<&Uuid>,
+    spec: &AirlineSpec,
+) -> Result<Vec<Airline>> {
+    let airlines = sqlx::query_as!(
+        Airline,
+        "select * from airlines \
+        where not deleted and id is distinct from $1 and (iata = $2 or icao = $3)",
+        id,
+        spec.iata,
+        spec.icao
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(airlines)
+}
+
+pub async fn create_airline(ex: &mut PgConnection, spec: AirlineSpec) -> Result<Airline> {
+    let airline = sqlx::query_as!(
+        Airline,
+        "insert into airlines (id, iata, icao, name, callsign) values (gen_random_uuid(), $1, $2, $3, $4) returning *",
+        spec.iata,
+        spec.icao,
+        spec.name,
+        spec.callsign
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(airline)
+}
+
+pub async fn update_airline(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    spec: AirlineSpec,
+) -> Result<Airline> {
+    let airline = sqlx::query_as!(
+        Airline,
+        "update airlines set iata = $2, icao = $3, name = $4, callsign = $5 where id = $1 returning *",
+        id,
+        spec.iata,
+        spec.icao,
+        spec.name,
+        spec.callsign
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(airline)
+}
+
+pub async fn delete_airline(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
+    let res = sqlx::query!("update airlines set deleted = true where id = $1", id)
+        .execute(ex)
+        .await?;
+
+    DatabaseError::ensure_single_affected(res)
+}
+
+/// Count the future flights operated by an airline that are not cancelled.
+pub async fn count_future_flights(ex: &mut PgConnection, id: &Uuid) -> Result<i64> {
+    let count = sqlx::query_scalar!(
+        r#"select count(*) as "count!" from flights
+        join flight_current_state on flight_id = id
+        where (carrier_id = $1 or exists (select from flight_codeshares c
+        where c.flight_id = flights.id and c.carrier_id = $1)) and not is_cancelled
+        and coalesce(expected_departure_time, departure_time) > now()"#,
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(count)
+}
@@ -1,5 +1,7 @@
+use tonic::Status;
+
 use super::queries;
-use crate::proto;
+use crate::{datautils::convert_odt_to_timestamp, proto};
 
 impl From<queries::Airport> for proto::flightmngr::Airport {
     fn from(airport: queries::Airport) -> Self {
@@ -11,6 +13,55 @@ impl From<queries::Airport> for proto::flightmngr::Airport {
             country: airport.country,
             city: airport.city,
             deleted: airport.deleted,
+            timezone: airport.timezone,
+            latitude: airport.latitude,
+            longitude: airport.longitude,
+        }
+    }
+}
+
+impl TryFrom<proto::flightmngr::Airport> for queries::AirportSpec {
+    type Error = Status;
+
+    fn try_from(airport: proto::flightmngr::Airport) -> Result<Self, Self::Error> {
+        let timezone = if airport.timezone.is_empty() {
+            String::from("UTC")
+        } else {
+            airport.timezone
+        };
+        match (airport.latitude, airport.longitude) {
+            (Some(latitude), _) if !(-90.0..=90.0).contains(&latitude) => {
+                return Err(Status::invalid_argument("'latitude'"))
+            }
+            (_, Some(longitude)) if !(-180.0..=180.0).contains(&longitude) => {
+                return Err(Status::invalid_argument("'longitude'"))
+            }
+            (Some(_), None) => return Err(Status::invalid_argument("'longitude'")),
+            (None, Some(_)) => return Err(Status::invalid_argument("'latitude'")),
+            _ => {}
+        }
+
+        Ok(Self {
+            icao: airport.icao,
+            iata: airport.iata,
+            name: airport.name,
+            country: airport.country,
+            city: airport.city,
+            timezone,
+            latitude: airport.latitude,
+            longitude: airport.longitude,
+        })
+    }
+}
+
+impl From<queries::Closure> for proto::flightmngr::AirportClosure {
+    fn from(closure: queries::Closure) -> Self {
+        Self {
+            id: closure.id.to_string(),
+            airport_id: closure.airport_id.to_string(),
+            start_time: Some(convert_odt_to_timestamp(closure.start_time)),
+            end_time: Some(convert_odt_to_timestamp(closure.end_time)),
+            reason: closure.reason,
         }
     }
 }
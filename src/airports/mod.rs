@@ -1,19 +1,33 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+use time::OffsetDateTime;
 use tonic::{Request, Response, Status};
 
 use crate::{
-    datautils::parse_id,
-    db::Database,
+    audit,
+    datautils::{parse_id, parse_timestamp},
+    db::{Database, DatabaseError},
+    flights::{self, FlightResource},
+    idempotency,
     proto::flightmngr::{
-        airports_server::Airports, Airport, CreateAirportRequest, DeleteAirportRequest,
-        GetAirportRequest, ListAirportsRequest, ListAirportsResponse,
+        airports_server::Airports, Airport, AirportClosure, CreateAirportRequest,
+        DeleteAirportRequest, GetAirportRequest, ListAirportClosuresRequest,
+        ListAirportClosuresResponse, ListAirportsRequest, ListAirportsResponse,
+        RestoreAirportRequest, ScheduleAirportClosureRequest,
     },
+    rabbitmq::Rabbit,
 };
 
 mod map;
 mod queries;
 
+pub(crate) use queries::Closure;
+
 pub struct AirportsApp {
     db: Database,
+    rabbitmq: Arc<Rabbit>,
 }
 
 #[tonic::async_trait]
@@ -52,19 +66,26 @@ impl Airports for AirportsApp {
         &self,
         request: Request<CreateAirportRequest>,
     ) -> std::result::Result<Response<Airport>, Status> {
-        let Airport {
-            icao,
-            iata,
-            name,
-            country,
-            city,
-            ..
-        } = request.into_inner().airport.unwrap_or_default();
+        let idempotency_key = idempotency::get_key(&request)?;
+        let spec: queries::AirportSpec = request
+            .into_inner()
+            .airport
+            .unwrap_or_default()
+            .try_into()?;
         let mut t = self.db.begin().await?;
 
-        let airport = queries::create_airport(t.get_conn(), icao, iata, name, country, city)
-            .await?
-            .into();
+        let key = idempotency_key.as_ref();
+        if let Some(airport) = idempotency::replay(t.get_conn(), "CreateAirport", key).await? {
+            return Ok(Response::new(airport));
+        }
+
+        if !queries::timezone_exists(t.get_conn(), &spec.timezone).await? {
+            return Err(Status::invalid_argument("'timezone'"));
+        }
+
+        let airport = queries::create_airport(t.get_conn(), spec).await?.into();
+
+        idempotency::record(t.get_conn(), "CreateAirport", key, &airport).await?;
 
         t.commit().await?;
         Ok(Response::new(airport))
@@ -74,19 +95,149 @@ impl Airports for AirportsApp {
         &self,
         request: Request<DeleteAirportRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        let DeleteAirportRequest { id } = request.into_inner();
+        let actor = audit::get_actor(&request)?;
+        let DeleteAirportRequest {
+            id,
+            cancel_future_flights,
+            cancellation_reason,
+        } = request.into_inner();
         let id = parse_id(&id)?;
+        let reason = cancel_future_flights.then_some(cancellation_reason);
+        if reason.as_ref().is_some_and(String::is_empty) {
+            return Err(Status::invalid_argument("'cancellation_reason'"));
+        }
         let mut t = self.db.begin().await?;
 
         queries::delete_airport(t.get_conn(), &id).await?;
+        let cancelled =
+            flights::cancel_future_flights(t.get_conn(), FlightResource::Airport(id), reason)
+                .await?;
+        audit::record(t.get_conn(), "airport", &id, "delete", actor.as_deref()).await?;
 
         t.commit().await?;
+
+        for flight in &cancelled {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
         Ok(Response::new(()))
     }
+
+    async fn restore_airport(
+        &self,
+        request: Request<RestoreAirportRequest>,
+    ) -> std::result::Result<Response<Airport>, Status> {
+        let actor = audit::get_actor(&request)?;
+        let RestoreAirportRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let airport = queries::get_airport(t.get_conn(), &id).await?;
+        if !airport.deleted {
+            return Ok(Response::new(airport.into()));
+        }
+
+        let airport = queries::restore_airport(t.get_conn(), &id).await?;
+        let conflicting = queries::get_conflicting_airports(t.get_conn(), &id).await?;
+        if !conflicting.is_empty() {
+            let ids = conflicting.iter().map(|a| a.id.to_string()).join(", ");
+            return Err(Status::failed_precondition(format!(
+                "ICAO or IATA code is used by active airports: {ids}"
+            )));
+        }
+        audit::record(t.get_conn(), "airport", &id, "restore", actor.as_deref()).await?;
+
+        t.commit().await?;
+        Ok(Response::new(airport.into()))
+    }
+
+    async fn list_airport_closures(
+        &self,
+        request: Request<ListAirportClosuresRequest>,
+    ) -> Result<Response<ListAirportClosuresResponse>, Status> {
+        let ListAirportClosuresRequest { airport_id } = request.into_inner();
+        let airport_id = parse_id(&airport_id)?;
+        let mut t = self.db.begin().await?;
+
+        let closures = queries::list_closures(t.get_conn(), &airport_id).await?;
+
+        let closures = closures.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListAirportClosuresResponse { closures }))
+    }
+
+    async fn schedule_airport_closure(
+        &self,
+        request: Request<ScheduleAirportClosureRequest>,
+    ) -> Result<Response<AirportClosure>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let AirportClosure {
+            airport_id,
+            start_time,
+            end_time,
+            reason,
+            ..
+        } = request.into_inner().closure.unwrap_or_default();
+        let airport_id = parse_id(&airport_id)?;
+        let start_time = parse_timestamp(start_time)?;
+        let end_time = parse_timestamp(end_time)?;
+        if end_time <= start_time {
+            return Err(Status::invalid_argument(
+                "'end_time' must be after 'start_time'",
+            ));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(c) = idempotency::replay(t.get_conn(), "ScheduleAirportClosure", key).await? {
+            return Ok(Response::new(c));
+        }
+
+        ensure_airport_active(t.get_conn(), &airport_id).await?;
+        let closure =
+            queries::create_closure(t.get_conn(), &airport_id, &start_time, &end_time, reason)
+                .await?
+                .into();
+
+        idempotency::record(t.get_conn(), "ScheduleAirportClosure", key, &closure).await?;
+
+        t.commit().await?;
+        Ok(Response::new(closure))
+    }
 }
 
 impl AirportsApp {
-    pub fn new(db: Database) -> Self {
-        Self { db }
+    pub fn new(db: Database, rabbitmq: Arc<Rabbit>) -> Self {
+        Self { db, rabbitmq }
     }
 }
+
+/// Ensure that an airport exists and has not been deleted.
+pub(crate) async fn ensure_airport_active(ex: &mut PgConnection, id: &Uuid) -> Result<(), Status> {
+    let airport = queries::get_airport(ex, id).await?;
+    if airport.deleted {
+        return Err(Status::failed_precondition("airport is deleted"));
+    }
+
+    Ok(())
+}
+
+/// Get the latitude and longitude of an airport, if known.
+pub(crate) async fn get_coordinates(
+    ex: &mut PgConnection,
+    id: &Uuid,
+) -> Result<Option<(f64, f64)>, DatabaseError> {
+    let airport = queries::get_airport(ex, id).await?;
+
+    Ok(airport.latitude.zip(airport.longitude))
+}
+
+/// Get the closures of an airport overlapping a time window.
+pub(crate) async fn get_closures_during(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<Closure>, DatabaseError> {
+    queries::get_overlapping_closures(ex, airport_id, from, to).await
+}
@@ -1,4 +1,5 @@
 use sqlx::{types::Uuid, PgConnection};
+use time::OffsetDateTime;
 
 use crate::db::DatabaseError;
 
@@ -12,6 +13,28 @@ pub struct Airport {
     pub country: String,
     pub city: String,
     pub deleted: bool,
+    pub timezone: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+pub struct AirportSpec {
+    pub icao: String,
+    pub iata: String,
+    pub name: String,
+    pub country: String,
+    pub city: String,
+    pub timezone: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+pub struct Closure {
+    pub id: Uuid,
+    pub airport_id: Uuid,
+    pub start_time: OffsetDateTime,
+    pub end_time: OffsetDateTime,
+    pub reason: String,
 }
 
 pub async fn list_airports(ex: &mut PgConnection) -> Result<Vec<Airport>> {
@@ -38,22 +61,18 @@ pub async fn get_airport(ex: &mut PgConnection, id: &Uuid) -> Result<Airport> {
     Ok(airport)
 }
 
-pub async fn create_airport(
-    ex: &mut PgConnection,
-    icao: String,
-    iata: String,
-    name: String,
-    country: String,
-    city: String,
-) -> Result<Airport> {
+pub async fn create_airport(ex: &mut PgConnection, spec: AirportSpec) -> Result<Airport> {
     let airport = sqlx::query_as!(
         Airport,
-        "insert into airports (id, icao, iata, name, country, city) values (gen_random_uuid(), $1, $2, $3, $4, $5) returning *",
-        icao,
-        iata,
-        name,
-        country,
-        city
+        "insert into airports (id, icao, iata, name, country, city, timezone, latitude, longitude) values (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7, $8) returning *",
+        spec.icao,
+        spec.iata,
+        spec.name,
+        spec.country,
+        spec.city,
+        spec.timezone,
+        spec.latitude,
+        spec.longitude
     )
     .fetch_one(ex)
     .await?;
@@ -68,3 +87,94 @@ pub async fn delete_airport(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
 
     DatabaseError::ensure_single_affected(res)
 }
+
+pub async fn restore_airport(ex: &mut PgConnection, id: &Uuid) -> Result<Airport> {
+    let airport = sqlx::query_as!(
+        Airport,
+        "update airports set deleted = false where id = $1 returning *",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(airport)
+}
+
+/// Get the other active airports sharing the ICAO or IATA code of an airport.
+pub async fn get_conflicting_airports(ex: &mut PgConnection, id: &Uuid) -> Result<Vec<Airport>> {
+    let airports = sqlx::query_as!(
+        Airport,
+        "select a.* from airports a join airports b on b.id = $1 \
+        where a.id <> b.id and not a.deleted and (a.icao = b.icao or a.iata = b.iata)",
+        id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(airports)
+}
+
+pub async fn timezone_exists(ex: &mut PgConnection, timezone: &str) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(select 1 from pg_timezone_names where name = $1) as "exists!""#,
+        timezone
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(exists)
+}
+
+pub async fn list_closures(ex: &mut PgConnection, airport_id: &Uuid) -> Result<Vec<Closure>> {
+    let closures = sqlx::query_as!(
+        Closure,
+        "select * from airport_closures where airport_id = $1 order by start_time",
+        airport_id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(closures)
+}
+
+pub async fn get_overlapping_closures(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<Closure>> {
+    let closures = sqlx::query_as!(
+        Closure,
+        "select * from airport_closures \
+        where airport_id = $1 and start_time < $3 and end_time > $2 \
+        order by start_time",
+        airport_id,
+        from,
+        to
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(closures)
+}
+
+pub async fn create_closure(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    start_time: &OffsetDateTime,
+    end_time: &OffsetDateTime,
+    reason: String,
+) -> Result<Closure> {
+    let closure = sqlx::query_as!(
+        Closure,
+        "insert into airport_closures (id, airport_id, start_time, end_time, reason) values (gen_random_uuid(), $1, $2, $3, $4) returning *",
+        airport_id,
+        start_time,
+        end_time,
+        reason
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(closure)
+}
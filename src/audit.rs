@@ -0,0 +1,37 @@
+use sqlx::{types::Uuid, PgConnection};
+use tonic::{Request, Status};
+
+use crate::db::DatabaseError;
+
+/// Metadata key identifying the user or system performing a request.
+pub const ACTOR: &str = "x-actor";
+
+pub fn get_actor<T>(request: &Request<T>) -> Result<Option<String>, Status> {
+    request
+        .metadata()
+        .get(ACTOR)
+        .map(|v| v.to_str().map(str::to_owned))
+        .transpose()
+        .map_err(|_| Status::invalid_argument("invalid actor"))
+}
+
+/// Record in the audit trail that `actor` performed `action` on an entity.
+pub async fn record(
+    ex: &mut PgConnection,
+    entity: &str,
+    entity_id: &Uuid,
+    action: &str,
+    actor: Option<&str>,
+) -> Result<(), DatabaseError> {
+    sqlx::query!(
+        "insert into audit_log (entity, entity_id, action, actor) values ($1, $2, $3, $4)",
+        entity,
+        entity_id,
+        action,
+        actor
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
@@ -14,6 +14,14 @@ fn default_rabbitmq_port() -> u16 {
     5672
 }
 
+fn default_ticketing_exchange() -> String {
+    String::from("ticketing")
+}
+
+fn default_ticketing_queue() -> String {
+    String::from("flightmngr.tickets")
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Options {
     pub database_url: String,
@@ -26,4 +34,8 @@ pub struct Options {
     pub rabbitmq_port: u16,
     pub rabbitmq_username: String,
     pub rabbitmq_password: String,
+    #[serde(default = "default_ticketing_exchange")]
+    pub ticketing_exchange: String,
+    #[serde(default = "default_ticketing_queue")]
+    pub ticketing_queue: String,
 }
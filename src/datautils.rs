@@ -1,6 +1,6 @@
 use prost_types::Timestamp;
 use sqlx::types::Uuid;
-use time::{Duration, OffsetDateTime};
+use time::{Date, Duration, OffsetDateTime, Time};
 use tonic::Status;
 
 pub fn convert_odt_to_timestamp(d: OffsetDateTime) -> Timestamp {
@@ -10,6 +10,10 @@ pub fn convert_odt_to_timestamp(d: OffsetDateTime) -> Timestamp {
     }
 }
 
+pub fn convert_date_to_timestamp(d: Date) -> Timestamp {
+    convert_odt_to_timestamp(d.with_time(Time::MIDNIGHT).assume_utc())
+}
+
 pub fn parse_id(id: &str) -> Result<Uuid, Status> {
     id.parse().map_err(|_| Status::invalid_argument("'id'"))
 }
@@ -23,3 +27,8 @@ pub fn parse_timestamp(timestamp: Option<Timestamp>) -> Result<OffsetDateTime, S
         .map(|t| t + Duration::nanoseconds(nanos as i64))
         .map_err(|_| Status::invalid_argument("'timestamp'"))
 }
+
+/// Parse the UTC day of a timestamp.
+pub fn parse_date(timestamp: Option<Timestamp>) -> Result<Date, Status> {
+    parse_timestamp(timestamp).map(OffsetDateTime::date)
+}
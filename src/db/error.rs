@@ -35,6 +35,22 @@ impl From<DatabaseError> for tonic::Status {
 }
 
 impl DatabaseError {
+    /// Whether the error is a violated unique constraint.
+    pub fn is_unique_violation(&self) -> bool {
+        match self {
+            DatabaseError::Other(sqlx::Error::Database(e)) => e.is_unique_violation(),
+            _ => false,
+        }
+    }
+
+    /// Name of the constraint violated by the error, if any.
+    pub fn constraint(&self) -> Option<&str> {
+        match self {
+            DatabaseError::Other(sqlx::Error::Database(e)) => e.constraint(),
+            _ => None,
+        }
+    }
+
     pub fn ensure_single_affected(res: PgQueryResult) -> Result<(), DatabaseError> {
         match res.rows_affected() {
             0 => Err(DatabaseError::NotFound),
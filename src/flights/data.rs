@@ -2,19 +2,56 @@ use std::collections::HashMap;
 
 use itertools::Itertools;
 use sqlx::{types::Uuid, PgConnection};
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 
 use super::queries;
 
-type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+use crate::airlines;
+use crate::db::DatabaseError;
+use crate::planes;
+use crate::proto::flightmngr::{CabinClassCapacity, Codeshare};
 
-pub struct FlightData(
-    pub queries::Flight,
-    pub Vec<queries::EventCancelled>,
-    pub Vec<queries::EventDelayed>,
-    pub Vec<queries::EventGateDepartureSet>,
-    pub Vec<queries::EventGateArrivalSet>,
-);
+type Result<T> = std::result::Result<T, DatabaseError>;
+
+impl FlightData {
+    /// Departure time taking into account the last delay.
+    pub fn expected_departure_time(&self) -> OffsetDateTime {
+        self.state
+            .expected_departure_time
+            .unwrap_or(self.flight.departure_time)
+    }
+
+    /// Arrival time taking into account the last delay.
+    pub fn expected_arrival_time(&self) -> OffsetDateTime {
+        self.state
+            .expected_arrival_time
+            .unwrap_or(self.flight.arrival_time)
+    }
+
+    /// Total weight of the cargo shipments on the flight.
+    pub fn cargo_booked_kg(&self) -> i32 {
+        self.shipments.iter().map(|s| s.weight_kg).sum()
+    }
+}
+
+pub struct FlightData {
+    pub flight: queries::Flight,
+    pub state: queries::FlightState,
+    pub cancelled: Vec<queries::EventCancelled>,
+    pub delayed: Vec<queries::EventDelayed>,
+    pub gate_dep: Vec<queries::EventGateDepartureSet>,
+    pub gate_arr: Vec<queries::EventGateArrivalSet>,
+    pub plane_changed: Vec<queries::EventPlaneChanged>,
+    pub retracted: Vec<queries::EventRetracted>,
+    pub cabin_classes: Vec<CabinClassCapacity>,
+    pub inventory: Vec<queries::SeatInventory>,
+    pub booked_seats: i32,
+    pub shipments: Vec<queries::CargoShipment>,
+    pub cargo_capacity_kg: i32,
+    pub fares: Vec<queries::FareBucket>,
+    pub designator: Option<String>,
+    pub codeshares: Vec<Codeshare>,
+}
 
 fn group_by_id<T>(list: Vec<T>, id: &'static impl Fn(&T) -> Uuid) -> HashMap<Uuid, Vec<T>> {
     list.into_iter()
@@ -31,6 +68,9 @@ pub async fn load_flights_data(
 ) -> Result<impl Iterator<Item = FlightData>> {
     let ids = flights.iter().map(|f| f.id).collect::<Vec<_>>();
 
+    let states = queries::get_flight_state(ex, &ids).await?;
+    let mut states: HashMap<_, _> = states.into_iter().map(|s| (s.flight_id, s)).collect();
+
     let cancelled = queries::get_event_cancelled(ex, &ids).await?;
     let mut cancelled = group_by_id(cancelled, &|e| e.flight_id);
 
@@ -43,26 +83,97 @@ pub async fn load_flights_data(
     let gate_arr = queries::get_event_gate_arr(ex, &ids).await?;
     let mut gate_arr = group_by_id(gate_arr, &|e| e.flight_id);
 
+    let plane_changed = queries::get_event_plane_changed(ex, &ids).await?;
+    let mut plane_changed = group_by_id(plane_changed, &|e| e.flight_id);
+
+    let retracted = queries::get_event_retracted(ex, &ids).await?;
+    let mut retracted = group_by_id(retracted, &|e| e.flight_id);
+
+    let plane_ids = states.values().map(|s| s.plane_id).unique().collect_vec();
+    let cabin_classes = planes::get_cabin_classes(ex, &plane_ids).await?;
+
+    let inventory = queries::get_seat_inventory(ex, &ids).await?;
+    let mut inventory = group_by_id(inventory, &|i| i.flight_id);
+
+    let shipments = queries::get_cargo_shipments(ex, &ids).await?;
+    let mut shipments = group_by_id(shipments, &|s| s.flight_id);
+    let cargo_capacities = planes::get_cargo_capacities(ex, &plane_ids).await?;
+
+    let fares = queries::get_fare_buckets(ex, &ids).await?;
+    let mut fares = group_by_id(fares, &|f| f.flight_id);
+
+    let codeshares = queries::get_codeshares(ex, &ids).await?;
+    let carrier_ids = flights
+        .iter()
+        .filter_map(|f| f.carrier_id)
+        .chain(codeshares.iter().map(|c| c.carrier_id))
+        .unique()
+        .collect_vec();
+    let carriers = get_carrier_codes(ex, &carrier_ids).await?;
+    let mut codeshares = group_by_id(codeshares, &|c| c.flight_id);
+
     let flights = flights.into_iter().map(move |f| {
         let id = f.id;
+        let state = states.remove(&id).ok_or(DatabaseError::Unexpected(
+            "missing current state for flight",
+        ))?;
         let cancelled = cancelled.remove(&id).unwrap_or_default();
         let delayed = delayed.remove(&id).unwrap_or_default();
         let gate_dep = gate_dep.remove(&id).unwrap_or_default();
         let gate_arr = gate_arr.remove(&id).unwrap_or_default();
-        FlightData(f, cancelled, delayed, gate_dep, gate_arr)
+        let plane_changed = plane_changed.remove(&id).unwrap_or_default();
+        let retracted = retracted.remove(&id).unwrap_or_default();
+        let cabin_classes = cabin_classes
+            .get(&state.plane_id)
+            .cloned()
+            .unwrap_or_default();
+        let inventory = inventory.remove(&id).unwrap_or_default();
+        let booked_seats = inventory.iter().map(|i| i.sold).sum();
+        let shipments = shipments.remove(&id).unwrap_or_default();
+        let cargo_capacity_kg = cargo_capacities
+            .get(&state.plane_id)
+            .copied()
+            .unwrap_or_default();
+        let fares = fares.remove(&id).unwrap_or_default();
+        let designator = designator(&carriers, f.carrier_id, f.flight_number.as_deref());
+        let codeshares = codeshares
+            .remove(&id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| codeshare(&carriers, c))
+            .collect();
+        Ok(FlightData {
+            flight: f,
+            state,
+            cancelled,
+            delayed,
+            gate_dep,
+            gate_arr,
+            plane_changed,
+            retracted,
+            cabin_classes,
+            inventory,
+            booked_seats,
+            shipments,
+            cargo_capacity_kg,
+            fares,
+            designator,
+            codeshares,
+        })
     });
 
-    Ok(flights)
+    Ok(flights.collect::<Result<Vec<_>>>()?.into_iter())
 }
 
 pub async fn list_flights(
     ex: &mut PgConnection,
     include_cancelled: bool,
+    include_drafts: bool,
 ) -> Result<impl Iterator<Item = FlightData>> {
     let flights = if include_cancelled {
-        queries::list_flights_with_cancelled(ex).await?
+        queries::list_flights_with_cancelled(ex, include_drafts).await?
     } else {
-        queries::list_flights(ex).await?
+        queries::list_flights(ex, include_drafts).await?
     };
 
     load_flights_data(ex, flights).await
@@ -73,9 +184,80 @@ pub async fn search_flights(
     origin_airport_id: Uuid,
     destination_airport_id: Uuid,
     date: OffsetDateTime,
+    include_drafts: bool,
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::search_flights(
+        ex,
+        origin_airport_id,
+        destination_airport_id,
+        date,
+        include_drafts,
+    )
+    .await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn lock_flights_at_airport(
+    ex: &mut PgConnection,
+    airport_id: Uuid,
+    window_start: OffsetDateTime,
+    window_end: OffsetDateTime,
 ) -> Result<impl Iterator<Item = FlightData>> {
     let flights =
-        queries::search_flights(ex, origin_airport_id, destination_airport_id, date).await?;
+        queries::lock_flights_at_airport(ex, &airport_id, &window_start, &window_end).await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn lock_plane_flights_after(
+    ex: &mut PgConnection,
+    plane_id: Uuid,
+    after: OffsetDateTime,
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::lock_plane_flights_after(ex, &plane_id, &after).await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn lock_airport_flights_after(
+    ex: &mut PgConnection,
+    airport_id: Uuid,
+    after: OffsetDateTime,
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::lock_airport_flights_after(ex, &airport_id, &after).await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn get_plane_flights_during(
+    ex: &mut PgConnection,
+    plane_id: Uuid,
+    from: OffsetDateTime,
+    to: Option<OffsetDateTime>,
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::get_plane_flights_during(ex, &plane_id, &from, to.as_ref()).await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn get_flights_between(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::get_flights_between(ex, origin_id, destination_id, from, to).await?;
+
+    load_flights_data(ex, flights).await
+}
+
+pub async fn get_flights(
+    ex: &mut PgConnection,
+    ids: &[Uuid],
+) -> Result<impl Iterator<Item = FlightData>> {
+    let flights = queries::get_flights(ex, ids).await?;
 
     load_flights_data(ex, flights).await
 }
@@ -83,31 +265,60 @@ pub async fn search_flights(
 pub async fn get_flight(ex: &mut PgConnection, id: Uuid) -> Result<FlightData> {
     let flight = queries::get_flight(ex, &id).await?;
 
-    let cancelled = queries::get_event_cancelled(ex, &[id]).await?;
-    let delayed = queries::get_event_delayed(ex, &[id]).await?;
-    let gate_dep = queries::get_event_gate_dep(ex, &[id]).await?;
-    let gate_arr = queries::get_event_gate_arr(ex, &[id]).await?;
+    load_flights_data(ex, vec![flight])
+        .await?
+        .next()
+        .ok_or(DatabaseError::Unexpected("flight data not loaded"))
+}
+
+/// IATA codes of airlines by id.
+async fn get_carrier_codes(ex: &mut PgConnection, ids: &[Uuid]) -> Result<HashMap<Uuid, String>> {
+    let airlines = airlines::get_airlines(ex, ids).await?;
+
+    Ok(airlines.into_iter().map(|a| (a.id, a.iata)).collect())
+}
 
-    Ok(FlightData(flight, cancelled, delayed, gate_dep, gate_arr))
+/// IATA code of the carrier followed by the flight number, e.g. LH123.
+fn designator(
+    carriers: &HashMap<Uuid, String>,
+    carrier_id: Option<Uuid>,
+    flight_number: Option<&str>,
+) -> Option<String> {
+    Some(format!("{}{}", carriers.get(&carrier_id?)?, flight_number?))
+}
+
+fn codeshare(carriers: &HashMap<Uuid, String>, codeshare: queries::Codeshare) -> Codeshare {
+    Codeshare {
+        carrier_id: codeshare.carrier_id.to_string(),
+        designator: designator(
+            carriers,
+            Some(codeshare.carrier_id),
+            Some(&codeshare.flight_number),
+        )
+        .unwrap_or_default(),
+        flight_number: codeshare.flight_number,
+    }
+}
+
+pub async fn get_flight_by_number(
+    ex: &mut PgConnection,
+    number: &queries::FlightNumber,
+    departure_local_date: &Date,
+) -> Result<FlightData> {
+    let flight = queries::get_flight_by_number(ex, number, departure_local_date).await?;
+
+    get_flight(ex, flight.id).await
 }
 
 pub async fn create_flight(
     ex: &mut PgConnection,
-    plane_id: Uuid,
-    origin_id: Uuid,
-    destination_id: Uuid,
-    departure_time: time::OffsetDateTime,
-    arrival_time: time::OffsetDateTime,
+    spec: &queries::FlightSpec,
+    schedule_id: Option<&Uuid>,
+    draft: bool,
 ) -> Result<FlightData> {
-    let flight = queries::create_flight(
-        ex,
-        plane_id,
-        origin_id,
-        destination_id,
-        departure_time,
-        arrival_time,
-    )
-    .await?;
+    let flight = queries::create_flight(ex, spec, schedule_id, draft).await?;
+
+    queries::refresh_flight_state(ex, Some(&flight.id)).await?;
 
-    Ok(FlightData(flight, vec![], vec![], vec![], vec![]))
+    get_flight(ex, flight.id).await
 }
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use sqlx::types::Uuid;
+use tonic::Status;
+
+use super::queries;
+use crate::{
+    planes,
+    proto::flightmngr::{FareBucket, SeatAvailability},
+};
+
+/// Fare buckets of a flight, cheapest first, with the seats that can still be sold in each.
+///
+/// A bucket cannot sell more seats than are available in its cabin.
+pub fn fare_buckets(
+    buckets: Vec<queries::FareBucket>,
+    availability: &[SeatAvailability],
+) -> Vec<FareBucket> {
+    buckets
+        .into_iter()
+        .map(|b| {
+            let cabin = i32::from(planes::cabin_class_from_str(&b.cabin));
+            let cabin_available = availability
+                .iter()
+                .find(|a| a.class == cabin)
+                .map(|a| a.available)
+                .unwrap_or_default();
+            FareBucket {
+                booking_class: b.booking_class,
+                cabin,
+                currency: b.currency,
+                base_price: b.base_price as u64,
+                seats_allotted: b.seats_allotted as u32,
+                seats_available: cabin_available.min(b.seats_allotted as u32),
+            }
+        })
+        .collect()
+}
+
+/// Cheapest fare bucket that still has seats available.
+pub fn lowest_fare(buckets: &[FareBucket]) -> Option<FareBucket> {
+    buckets
+        .iter()
+        .filter(|b| b.seats_available > 0)
+        .min_by_key(|b| b.base_price)
+        .cloned()
+}
+
+/// Validate the fare buckets of a flight.
+///
+/// Booking classes must be unique, and every bucket must be priced in the same currency
+/// so that fares can be compared.
+pub fn parse_fare_buckets(
+    flight_id: Uuid,
+    buckets: Vec<FareBucket>,
+) -> Result<Vec<queries::FareBucket>, Status> {
+    let mut booking_classes = HashSet::new();
+    let currency = buckets.first().map(|b| b.currency.clone());
+
+    buckets
+        .into_iter()
+        .map(|b| {
+            let valid_code = (1..=2).contains(&b.booking_class.len())
+                && b.booking_class.chars().all(|c| c.is_ascii_uppercase());
+            if !valid_code || !booking_classes.insert(b.booking_class.clone()) {
+                return Err(Status::invalid_argument("'booking_class'"));
+            }
+            let valid_currency =
+                b.currency.len() == 3 && b.currency.chars().all(|c| c.is_ascii_uppercase());
+            if !valid_currency || Some(&b.currency) != currency.as_ref() {
+                return Err(Status::invalid_argument("'currency'"));
+            }
+
+            Ok(queries::FareBucket {
+                flight_id,
+                cabin: planes::parse_cabin_class(b.cabin)?.to_string(),
+                booking_class: b.booking_class,
+                currency: b.currency,
+                base_price: i64::try_from(b.base_price)
+                    .map_err(|_| Status::invalid_argument("'base_price'"))?,
+                seats_allotted: i32::try_from(b.seats_allotted)
+                    .map_err(|_| Status::invalid_argument("'seats_allotted'"))?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use sqlx::types::Uuid;
+
+use super::queries::FlightSnapshot;
+
+#[derive(Default)]
+pub struct FlightsDiff {
+    pub added: Vec<FlightSnapshot>,
+    pub removed: Vec<FlightSnapshot>,
+    pub changed: Vec<(FlightSnapshot, FlightSnapshot)>,
+}
+
+/// Compare the published flights at two points in time.
+///
+/// Flights are never deleted, so a flight missing from `after` was cancelled in between.
+pub fn diff(before: Vec<FlightSnapshot>, after: Vec<FlightSnapshot>) -> FlightsDiff {
+    let mut before: HashMap<Uuid, FlightSnapshot> =
+        before.into_iter().map(|f| (f.flight_id, f)).collect();
+    let mut diff = FlightsDiff::default();
+
+    for flight in after {
+        match before.remove(&flight.flight_id) {
+            None => diff.added.push(flight),
+            Some(previous) if previous != flight => diff.changed.push((previous, flight)),
+            Some(_) => {}
+        }
+    }
+
+    diff.removed = before.into_values().collect();
+    diff.removed.sort_by_key(|f| f.departure_time);
+
+    diff
+}
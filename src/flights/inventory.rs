@@ -0,0 +1,68 @@
+use tonic::Status;
+
+use super::queries::SeatInventory;
+use crate::{
+    planes,
+    proto::flightmngr::{CabinClassCapacity, SeatAvailability},
+};
+
+/// Seats of each cabin class of a flight, combining the capacity of the plane operating it
+/// with the seats sold.
+///
+/// Classes the plane does not have are listed with no capacity as long as seats were sold
+/// or allowed in them.
+pub fn seat_availability(
+    classes: &[CabinClassCapacity],
+    inventory: &[SeatInventory],
+) -> Vec<SeatAvailability> {
+    let mut availability: Vec<_> = classes
+        .iter()
+        .map(|c| SeatAvailability {
+            class: c.class,
+            capacity: c.capacity,
+            ..Default::default()
+        })
+        .collect();
+
+    for row in inventory {
+        let class = i32::from(planes::cabin_class_from_str(&row.class));
+        let index = match availability.iter().position(|a| a.class == class) {
+            Some(index) => index,
+            None if row.sold == 0 && row.overbooking_allowance == 0 => continue,
+            None => {
+                availability.push(SeatAvailability {
+                    class,
+                    ..Default::default()
+                });
+                availability.len() - 1
+            }
+        };
+        availability[index].sold = row.sold as u32;
+        availability[index].overbooking_allowance = row.overbooking_allowance as u32;
+    }
+
+    for a in &mut availability {
+        a.available = (a.capacity + a.overbooking_allowance).saturating_sub(a.sold);
+    }
+
+    availability
+}
+
+/// Ensure that the seats sold on a flight fit in the cabin of a plane.
+pub fn ensure_seats_fit(
+    classes: &[CabinClassCapacity],
+    inventory: &[SeatInventory],
+) -> Result<(), Status> {
+    for a in seat_availability(classes, inventory) {
+        if a.sold > a.capacity + a.overbooking_allowance {
+            return Err(Status::failed_precondition(format!(
+                "{} seats sold in class {} but only {} fit",
+                a.sold,
+                a.class().as_str_name(),
+                a.capacity + a.overbooking_allowance
+            )));
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use super::{data::FlightData, queries};
+use super::{
+    data::FlightData, fares, history::FlightsDiff, inventory, queries, rotation::KnockOnDelay,
+};
 use crate::{
     datautils::convert_odt_to_timestamp,
     proto::{self, flightmngr::FlightStatusEvent},
@@ -6,42 +8,92 @@ use crate::{
 
 impl From<FlightData> for proto::flightmngr::Flight {
     fn from(flight_data: FlightData) -> Self {
-        let FlightData(flight, cancelled, delayed, gate_dep, gate_arr) = flight_data;
-
-        // extract last event statuses
-        let is_cancelled = !cancelled.is_empty();
-
-        let last_delay = delayed.iter().max_by_key(|e| e.timestamp);
-        let exp_dep_t = last_delay.map(|e| convert_odt_to_timestamp(e.departure_time));
-        let exp_arr_t = last_delay.map(|e| convert_odt_to_timestamp(e.arrival_time));
-
-        let last_gate_dep = gate_dep.iter().max_by_key(|e| e.timestamp);
-        let departure_gate = last_gate_dep.map(|e| e.gate.clone());
-        let last_gate_arr = gate_arr.iter().max_by_key(|e| e.timestamp);
-        let arrival_gate = last_gate_arr.map(|e| e.gate.clone());
+        let cargo_booked_kg = flight_data.cargo_booked_kg();
+        let FlightData {
+            flight,
+            state,
+            cancelled,
+            delayed,
+            gate_dep,
+            gate_arr,
+            plane_changed,
+            retracted,
+            cabin_classes,
+            inventory: seat_inventory,
+            booked_seats,
+            shipments: _,
+            cargo_capacity_kg,
+            fares: fare_buckets,
+            designator,
+            codeshares,
+        } = flight_data;
+        let seat_availability = inventory::seat_availability(&cabin_classes, &seat_inventory);
+        let fare_buckets = fares::fare_buckets(fare_buckets, &seat_availability);
+        let capacity: u32 = cabin_classes.iter().map(|c| c.capacity).sum();
+        let load_factor = if capacity > 0 {
+            f64::from(booked_seats) / f64::from(capacity)
+        } else {
+            0.0
+        };
 
         // build history of status events
         let status_events: Vec<FlightStatusEvent> = (cancelled.into_iter().map(Into::into))
             .chain(delayed.into_iter().map(Into::into))
             .chain(gate_dep.into_iter().map(Into::into))
             .chain(gate_arr.into_iter().map(Into::into))
+            .chain(plane_changed.into_iter().map(Into::into))
+            .chain(retracted.into_iter().map(Into::into))
             .collect();
 
         // assemble
         Self {
             id: flight.id.to_string(),
-            plane_id: flight.plane_id.to_string(),
+            plane_id: state.plane_id.to_string(),
             origin_id: flight.origin_id.to_string(),
             destination_id: flight.destination_id.to_string(),
             departure_time: Some(convert_odt_to_timestamp(flight.departure_time)),
             arrival_time: Some(convert_odt_to_timestamp(flight.arrival_time)),
             status_events,
             // computed fields
-            is_cancelled,
-            expected_departure_time: exp_dep_t,
-            expected_arrival_time: exp_arr_t,
-            departure_gate,
-            arrival_gate,
+            is_cancelled: state.is_cancelled,
+            expected_departure_time: state.expected_departure_time.map(convert_odt_to_timestamp),
+            expected_arrival_time: state.expected_arrival_time.map(convert_odt_to_timestamp),
+            departure_gate: state.departure_gate,
+            arrival_gate: state.arrival_gate,
+            version: state.version as u64,
+            cabin_classes,
+            seat_availability,
+            booked_seats: booked_seats as u32,
+            load_factor,
+            cargo_capacity_kg: cargo_capacity_kg as u32,
+            cargo_booked_kg: cargo_booked_kg as u32,
+            cargo_remaining_kg: cargo_capacity_kg.saturating_sub(cargo_booked_kg).max(0) as u32,
+            lowest_fare: fares::lowest_fare(&fare_buckets),
+            carrier_id: flight
+                .carrier_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            flight_number: flight.flight_number.unwrap_or_default(),
+            designator: designator.unwrap_or_default(),
+            codeshares,
+            schedule_id: flight
+                .schedule_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            draft: flight.published_at.is_none(),
+            published_at: flight.published_at.map(convert_odt_to_timestamp),
+        }
+    }
+}
+
+impl From<queries::CargoShipment> for proto::flightmngr::CargoShipment {
+    fn from(shipment: queries::CargoShipment) -> Self {
+        Self {
+            flight_id: shipment.flight_id.to_string(),
+            shipment_id: shipment.shipment_id,
+            weight_kg: shipment.weight_kg as u32,
+            origin_id: shipment.origin_id.to_string(),
+            destination_id: shipment.destination_id.to_string(),
         }
     }
 }
@@ -49,6 +101,7 @@ impl From<FlightData> for proto::flightmngr::Flight {
 impl From<queries::EventCancelled> for proto::flightmngr::FlightStatusEvent {
     fn from(event: queries::EventCancelled) -> Self {
         Self {
+            id: event.id.to_string(),
             timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
             event: Some(
                 proto::flightmngr::flight_status_event::Event::FlightCancelled(
@@ -64,12 +117,14 @@ impl From<queries::EventCancelled> for proto::flightmngr::FlightStatusEvent {
 impl From<queries::EventDelayed> for proto::flightmngr::FlightStatusEvent {
     fn from(event: queries::EventDelayed) -> Self {
         Self {
+            id: event.id.to_string(),
             timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
             event: Some(
                 proto::flightmngr::flight_status_event::Event::FlightDelayed(
                     proto::flightmngr::FlightDelayed {
                         arrival_time: Some(convert_odt_to_timestamp(event.arrival_time)),
                         departure_time: Some(convert_odt_to_timestamp(event.departure_time)),
+                        reason: event.reason.unwrap_or_default(),
                     },
                 ),
             ),
@@ -80,6 +135,7 @@ impl From<queries::EventDelayed> for proto::flightmngr::FlightStatusEvent {
 impl From<queries::EventGateDepartureSet> for proto::flightmngr::FlightStatusEvent {
     fn from(event: queries::EventGateDepartureSet) -> Self {
         Self {
+            id: event.id.to_string(),
             timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
             event: Some(
                 proto::flightmngr::flight_status_event::Event::FlightGateDeparture(
@@ -93,6 +149,7 @@ impl From<queries::EventGateDepartureSet> for proto::flightmngr::FlightStatusEve
 impl From<queries::EventGateArrivalSet> for proto::flightmngr::FlightStatusEvent {
     fn from(event: queries::EventGateArrivalSet) -> Self {
         Self {
+            id: event.id.to_string(),
             timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
             event: Some(
                 proto::flightmngr::flight_status_event::Event::FlightGateArrival(
@@ -102,3 +159,79 @@ impl From<queries::EventGateArrivalSet> for proto::flightmngr::FlightStatusEvent
         }
     }
 }
+
+impl From<queries::EventPlaneChanged> for proto::flightmngr::FlightStatusEvent {
+    fn from(event: queries::EventPlaneChanged) -> Self {
+        Self {
+            id: event.id.to_string(),
+            timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
+            event: Some(
+                proto::flightmngr::flight_status_event::Event::FlightPlaneChanged(
+                    proto::flightmngr::FlightPlaneChanged {
+                        plane_id: event.plane_id.to_string(),
+                    },
+                ),
+            ),
+        }
+    }
+}
+
+impl From<queries::EventRetracted> for proto::flightmngr::FlightStatusEvent {
+    fn from(event: queries::EventRetracted) -> Self {
+        Self {
+            id: event.id.to_string(),
+            timestamp: Some(convert_odt_to_timestamp(event.timestamp)),
+            event: Some(
+                proto::flightmngr::flight_status_event::Event::FlightEventRetracted(
+                    proto::flightmngr::FlightEventRetracted {
+                        event_id: event.event_id.to_string(),
+                        reason: event.reason.unwrap_or_default(),
+                    },
+                ),
+            ),
+        }
+    }
+}
+
+impl From<KnockOnDelay> for proto::flightmngr::ProposedDelay {
+    fn from(delay: KnockOnDelay) -> Self {
+        Self {
+            flight_id: delay.flight_id.to_string(),
+            departure_time: Some(convert_odt_to_timestamp(delay.departure_time)),
+            arrival_time: Some(convert_odt_to_timestamp(delay.arrival_time)),
+        }
+    }
+}
+
+impl From<queries::FlightSnapshot> for proto::flightmngr::FlightSnapshot {
+    fn from(flight: queries::FlightSnapshot) -> Self {
+        Self {
+            flight_id: flight.flight_id.to_string(),
+            designator: flight.designator.unwrap_or_default(),
+            plane_id: flight.plane_id.to_string(),
+            origin_id: flight.origin_id.to_string(),
+            destination_id: flight.destination_id.to_string(),
+            departure_time: Some(convert_odt_to_timestamp(flight.departure_time)),
+            arrival_time: Some(convert_odt_to_timestamp(flight.arrival_time)),
+            departure_gate: flight.departure_gate,
+            arrival_gate: flight.arrival_gate,
+        }
+    }
+}
+
+impl From<FlightsDiff> for proto::flightmngr::DiffFlightsResponse {
+    fn from(diff: FlightsDiff) -> Self {
+        Self {
+            added: diff.added.into_iter().map(Into::into).collect(),
+            removed: diff.removed.into_iter().map(Into::into).collect(),
+            changed: diff
+                .changed
+                .into_iter()
+                .map(|(before, after)| proto::flightmngr::FlightChange {
+                    before: Some(before.into()),
+                    after: Some(after.into()),
+                })
+                .collect(),
+        }
+    }
+}
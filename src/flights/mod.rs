@@ -1,24 +1,49 @@
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+use time::{Duration, OffsetDateTime};
 use tonic::{Request, Response, Status};
 
-use crate::datautils::{parse_id, parse_timestamp};
-use crate::db::Database;
+use crate::airlines;
+use crate::airports;
+use crate::datautils::{parse_date, parse_id, parse_timestamp};
+use crate::db::{Database, DatabaseError};
+use crate::idempotency::{self, IdempotencyKey};
+use crate::planes;
 use crate::proto::flightmngr::flight_status_event::Event;
 use crate::proto::flightmngr::{
-    flights_server::Flights, CreateFlightRequest, Flight, GetFlightRequest, ListFlightsRequest,
-    ListFlightsResponse, SearchFlightsRequest, UpdateFlightRequest,
+    flights_server::Flights, AddCargoShipmentRequest, AirportDisruption, BatchUpdateFlightsRequest,
+    BatchUpdateFlightsResponse, CabinConfiguration, CancelAirportFlightsRequest, CargoShipment,
+    ChangeSeatsRequest, CreateFlightRequest, DelayAirportFlightsRequest, DiffFlightsRequest,
+    DiffFlightsResponse, Flight, GetFlightByNumberRequest, GetFlightRequest,
+    GetFlightSeatMapRequest, ImpactedFlight, ListCargoShipmentsRequest, ListCargoShipmentsResponse,
+    ListFareBucketsRequest, ListFareBucketsResponse, ListFlightsRequest, ListFlightsResponse,
+    ListImpactedFlightsRequest, ListImpactedFlightsResponse, PropagateDelayRequest,
+    PropagateDelayResponse, PublishFlightsRequest, RemoveCargoShipmentRequest,
+    SearchFlightsRequest, SeatAvailability, SetCodesharesRequest, SetFareBucketsRequest,
+    UpdateFlightRequest,
 };
 use crate::proto::flightmngr::{
-    FlightCancelled, FlightDelayed, FlightGateArrival, FlightGateDeparture, FlightStatusEvent,
+    FlightCancelled, FlightDelayed, FlightEventRetracted, FlightGateArrival, FlightGateDeparture,
+    FlightPlaneChanged, FlightStatusEvent,
 };
+use crate::routes;
+
+use std::sync::Arc;
 
 use crate::rabbitmq::Rabbit;
 mod data;
+mod fares;
+mod history;
+mod inventory;
 mod map;
 mod queries;
+mod rotation;
+
+pub(crate) use queries::{FlightNumber, FlightSpec, ScheduleInstance};
 
 pub struct FlightsApp {
     db: Database,
-    rabbitmq: Rabbit,
+    rabbitmq: Arc<Rabbit>,
 }
 
 #[tonic::async_trait]
@@ -27,10 +52,13 @@ impl Flights for FlightsApp {
         &self,
         request: Request<ListFlightsRequest>,
     ) -> Result<Response<ListFlightsResponse>, Status> {
-        let ListFlightsRequest { include_cancelled } = request.into_inner();
+        let ListFlightsRequest {
+            include_cancelled,
+            include_drafts,
+        } = request.into_inner();
         let mut t = self.db.begin().await?;
 
-        let flights = data::list_flights(t.get_conn(), include_cancelled).await?;
+        let flights = data::list_flights(t.get_conn(), include_cancelled, include_drafts).await?;
 
         let flights = flights.map(Into::into).collect();
         Ok(Response::new(ListFlightsResponse { flights }))
@@ -44,6 +72,7 @@ impl Flights for FlightsApp {
             origin_id,
             destination_id,
             departure_day,
+            include_drafts,
         } = request.into_inner();
 
         let origin_id = parse_id(&origin_id)?;
@@ -52,8 +81,14 @@ impl Flights for FlightsApp {
 
         let mut t = self.db.begin().await?;
 
-        let flights =
-            data::search_flights(t.get_conn(), origin_id, destination_id, departure_day).await?;
+        let flights = data::search_flights(
+            t.get_conn(),
+            origin_id,
+            destination_id,
+            departure_day,
+            include_drafts,
+        )
+        .await?;
 
         let flights = flights.map(Into::into).collect();
         Ok(Response::new(ListFlightsResponse { flights }))
@@ -72,16 +107,42 @@ impl Flights for FlightsApp {
         Ok(Response::new(flight.into()))
     }
 
+    async fn get_flight_by_number(
+        &self,
+        request: Request<GetFlightByNumberRequest>,
+    ) -> Result<Response<Flight>, Status> {
+        let GetFlightByNumberRequest {
+            carrier,
+            flight_number,
+            date,
+        } = request.into_inner();
+        let date = parse_date(date)?;
+        let mut t = self.db.begin().await?;
+
+        let carrier = airlines::get_airline_by_code(t.get_conn(), &carrier).await?;
+        let number = queries::FlightNumber {
+            carrier_id: carrier.id,
+            flight_number: parse_flight_number(&flight_number)?,
+        };
+        let flight = data::get_flight_by_number(t.get_conn(), &number, &date).await?;
+
+        Ok(Response::new(flight.into()))
+    }
+
     async fn create_flight(
         &self,
         request: Request<CreateFlightRequest>,
     ) -> std::result::Result<Response<Flight>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
         let Flight {
             plane_id,
             origin_id,
             destination_id,
             departure_time,
             arrival_time,
+            carrier_id,
+            flight_number,
+            draft,
             ..
         } = request.into_inner().flight.unwrap_or_default();
 
@@ -89,20 +150,48 @@ impl Flights for FlightsApp {
         let origin_id = parse_id(&origin_id)?;
         let destination_id = parse_id(&destination_id)?;
         let departure_time = parse_timestamp(departure_time)?;
-        let arrival_time = parse_timestamp(arrival_time)?;
+        let arrival_time = arrival_time.map(|t| parse_timestamp(Some(t))).transpose()?;
+        let number = match (carrier_id.is_empty(), flight_number.is_empty()) {
+            (true, true) => None,
+            (false, false) => Some(queries::FlightNumber {
+                carrier_id: parse_id(&carrier_id)?,
+                flight_number: parse_flight_number(&flight_number)?,
+            }),
+            (true, false) => return Err(Status::invalid_argument("'carrier_id'")),
+            (false, true) => return Err(Status::invalid_argument("'flight_number'")),
+        };
 
         let mut t = self.db.begin().await?;
 
-        let flight = data::create_flight(
-            t.get_conn(),
+        let key = idempotency_key.as_ref();
+        if let Some(flight) = idempotency::replay(t.get_conn(), "CreateFlight", key).await? {
+            return Ok(Response::new(flight));
+        }
+
+        // without an arrival time, the flight takes the block time of the route
+        let arrival_time = match arrival_time {
+            Some(arrival_time) => arrival_time,
+            None => routes::get_block_time(t.get_conn(), &origin_id, &destination_id, &plane_id)
+                .await?
+                .map(|block_time| departure_time + block_time)
+                .ok_or(Status::invalid_argument("'arrival_time'"))?,
+        };
+        let spec = queries::FlightSpec {
             plane_id,
             origin_id,
             destination_id,
             departure_time,
             arrival_time,
-        )
-        .await?
-        .into();
+            number,
+        };
+        ensure_spec_valid(t.get_conn(), &spec, None).await?;
+
+        let flight = data::create_flight(t.get_conn(), &spec, None, draft)
+            .await
+            .map_err(number_taken)?
+            .into();
+
+        idempotency::record(t.get_conn(), "CreateFlight", key, &flight).await?;
 
         t.commit().await?;
         Ok(Response::new(flight))
@@ -112,46 +201,1226 @@ impl Flights for FlightsApp {
         &self,
         request: Request<UpdateFlightRequest>,
     ) -> std::result::Result<Response<Flight>, Status> {
-        let UpdateFlightRequest { id, status_event } = request.into_inner();
-        let id = parse_id(&id)?;
-        let FlightStatusEvent { event, .. } =
-            status_event.ok_or(Status::invalid_argument("'status_event' is required"))?;
-        let event = event.ok_or(Status::invalid_argument("'status_event.event' is required"))?;
+        let idempotency_key = idempotency::get_key(&request)?;
+        let update = FlightUpdate::try_from(request.into_inner())?;
 
         let mut t = self.db.begin().await?;
 
-        match event {
-            Event::FlightCancelled(FlightCancelled { reason }) => {
-                queries::add_event_cancelled(t.get_conn(), &id, reason).await?;
-            }
-            Event::FlightDelayed(FlightDelayed {
-                arrival_time,
-                departure_time,
-            }) => {
-                let arrival_time = parse_timestamp(arrival_time)?;
-                let departure_time = parse_timestamp(departure_time)?;
-                queries::add_event_delayed(t.get_conn(), &id, &departure_time, &arrival_time)
-                    .await?;
-            }
-            Event::FlightGateDeparture(FlightGateDeparture { gate }) => {
-                queries::add_event_gate_dep_set(t.get_conn(), &id, &gate).await?;
-            }
-            Event::FlightGateArrival(FlightGateArrival { gate }) => {
-                queries::add_event_gate_arr_set(t.get_conn(), &id, &gate).await?;
-            }
-        };
+        let key = idempotency_key.as_ref();
+        if let Some(flight) = idempotency::replay(t.get_conn(), "UpdateFlight", key).await? {
+            return Ok(Response::new(flight));
+        }
+
+        // serialize concurrent updates of the same flight
+        queries::lock_flight(t.get_conn(), &update.id).await?;
+        let id = update.id;
+        apply_update(t.get_conn(), update).await?;
 
         let flight = data::get_flight(t.get_conn(), id).await?.into();
+        idempotency::record(t.get_conn(), "UpdateFlight", key, &flight).await?;
         t.commit().await?;
 
         self.rabbitmq.notify_flight_update(&flight).await?;
 
         Ok(Response::new(flight))
     }
+
+    async fn batch_update_flights(
+        &self,
+        request: Request<BatchUpdateFlightsRequest>,
+    ) -> std::result::Result<Response<BatchUpdateFlightsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let BatchUpdateFlightsRequest { updates } = request.into_inner();
+        let updates = (updates.into_iter().enumerate())
+            .map(|(i, u)| FlightUpdate::try_from(u).map_err(|e| batch_item_error(i, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(response) = idempotency::replay(t.get_conn(), "BatchUpdateFlights", key).await?
+        {
+            return Ok(Response::new(response));
+        }
+
+        // affected flights in order of first appearance
+        let ids: Vec<_> = updates.iter().map(|u| u.id).unique().collect();
+
+        // lock in a consistent order to avoid deadlocks with concurrent batches
+        for id in ids.iter().sorted() {
+            queries::lock_flight(t.get_conn(), id).await?;
+        }
+        for (i, update) in updates.into_iter().enumerate() {
+            apply_update(t.get_conn(), update)
+                .await
+                .map_err(|e| batch_item_error(i, e))?;
+        }
+
+        let flights = data::get_flights(t.get_conn(), &ids).await?;
+        let response = BatchUpdateFlightsResponse {
+            flights: flights.map(Into::into).collect(),
+        };
+        idempotency::record(t.get_conn(), "BatchUpdateFlights", key, &response).await?;
+        t.commit().await?;
+
+        for flight in &response.flights {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
+        Ok(Response::new(response))
+    }
+
+    async fn cancel_airport_flights(
+        &self,
+        request: Request<CancelAirportFlightsRequest>,
+    ) -> std::result::Result<Response<ListFlightsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let CancelAirportFlightsRequest { disruption } = request.into_inner();
+        let disruption = Disruption::try_from(disruption)?;
+
+        let response = self
+            .disrupt_airport("CancelAirportFlights", idempotency_key, disruption, None)
+            .await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn delay_airport_flights(
+        &self,
+        request: Request<DelayAirportFlightsRequest>,
+    ) -> std::result::Result<Response<ListFlightsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let DelayAirportFlightsRequest {
+            disruption,
+            delay_minutes,
+        } = request.into_inner();
+        let disruption = Disruption::try_from(disruption)?;
+        if delay_minutes == 0 {
+            return Err(Status::invalid_argument("'delay_minutes' must be positive"));
+        }
+        let delay = Duration::minutes(delay_minutes.into());
+
+        let response = self
+            .disrupt_airport(
+                "DelayAirportFlights",
+                idempotency_key,
+                disruption,
+                Some(delay),
+            )
+            .await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn propagate_delay(
+        &self,
+        request: Request<PropagateDelayRequest>,
+    ) -> std::result::Result<Response<PropagateDelayResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let PropagateDelayRequest {
+            flight_id,
+            min_turnaround_minutes,
+            apply,
+        } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let min_turnaround = min_turnaround_minutes.map(|m| Duration::minutes(m.into()));
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref().filter(|_| apply);
+        if let Some(response) = idempotency::replay(t.get_conn(), "PropagateDelay", key).await? {
+            return Ok(Response::new(response));
+        }
+
+        queries::lock_flight(t.get_conn(), &flight_id).await?;
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        if flight.state.is_cancelled {
+            return Err(Status::failed_precondition("flight is cancelled"));
+        }
+
+        // default to the turnaround of the aircraft type operating the flight
+        let min_turnaround = match min_turnaround {
+            Some(min_turnaround) => min_turnaround,
+            None => planes::get_min_turnaround(t.get_conn(), &flight.state.plane_id)
+                .await?
+                .unwrap_or(rotation::DEFAULT_MIN_TURNAROUND),
+        };
+
+        let next_flights = data::lock_plane_flights_after(
+            t.get_conn(),
+            flight.state.plane_id,
+            flight.expected_departure_time(),
+        )
+        .await?
+        .collect::<Vec<_>>();
+        let delays = rotation::knock_on_delays(
+            flight.expected_arrival_time(),
+            min_turnaround,
+            &next_flights,
+        );
+
+        if !apply {
+            let delays = delays.into_iter().map(Into::into).collect();
+            return Ok(Response::new(PropagateDelayResponse { delays }));
+        }
+
+        let reason = format!("knock-on delay from flight {flight_id}");
+        for delay in &delays {
+            queries::add_event_delayed(
+                t.get_conn(),
+                &delay.flight_id,
+                &delay.departure_time,
+                &delay.arrival_time,
+                reason.clone(),
+            )
+            .await?;
+            queries::refresh_flight_state(t.get_conn(), Some(&delay.flight_id)).await?;
+        }
+
+        let ids = delays.iter().map(|d| d.flight_id).collect::<Vec<_>>();
+        let flights: Vec<Flight> = data::get_flights(t.get_conn(), &ids)
+            .await?
+            .map(Into::into)
+            .collect();
+
+        let delays = delays.into_iter().map(Into::into).collect();
+        let response = PropagateDelayResponse { delays };
+        idempotency::record(t.get_conn(), "PropagateDelay", key, &response).await?;
+        t.commit().await?;
+
+        for flight in &flights {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_impacted_flights(
+        &self,
+        request: Request<ListImpactedFlightsRequest>,
+    ) -> std::result::Result<Response<ListImpactedFlightsResponse>, Status> {
+        let ListImpactedFlightsRequest { unavailability_id } = request.into_inner();
+        let unavailability_id = parse_id(&unavailability_id)?;
+        let mut t = self.db.begin().await?;
+
+        let unavailability = planes::get_unavailability(t.get_conn(), &unavailability_id).await?;
+        let impacted = data::get_plane_flights_during(
+            t.get_conn(),
+            unavailability.plane_id,
+            unavailability.start_time,
+            unavailability.end_time,
+        )
+        .await?
+        .collect::<Vec<_>>();
+
+        let ids = impacted.iter().map(|f| f.flight.id).collect_vec();
+        let departure_times = impacted
+            .iter()
+            .map(|f| f.expected_departure_time())
+            .collect_vec();
+        let arrival_times = impacted
+            .iter()
+            .map(|f| f.expected_arrival_time())
+            .collect_vec();
+        let replacements = queries::find_replacement_planes(
+            t.get_conn(),
+            &unavailability.plane_id,
+            &ids,
+            &departure_times,
+            &arrival_times,
+        )
+        .await?;
+        let mut replacements = replacements.into_iter().into_group_map_by(|r| r.flight_id);
+
+        let flights = impacted
+            .into_iter()
+            .map(|flight| {
+                let replacement_plane_ids = replacements
+                    .remove(&flight.flight.id)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|r| r.plane_id.to_string())
+                    .collect();
+                ImpactedFlight {
+                    flight: Some(flight.into()),
+                    replacement_plane_ids,
+                }
+            })
+            .collect();
+
+        Ok(Response::new(ListImpactedFlightsResponse { flights }))
+    }
+
+    async fn get_flight_seat_map(
+        &self,
+        request: Request<GetFlightSeatMapRequest>,
+    ) -> std::result::Result<Response<CabinConfiguration>, Status> {
+        let GetFlightSeatMapRequest { flight_id } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let mut t = self.db.begin().await?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        let configuration =
+            planes::get_cabin_configuration(t.get_conn(), &flight.state.plane_id).await?;
+
+        Ok(Response::new(configuration))
+    }
+
+    async fn reserve_seats(
+        &self,
+        request: Request<ChangeSeatsRequest>,
+    ) -> std::result::Result<Response<SeatAvailability>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let availability = self
+            .change_seats(
+                "ReserveSeats",
+                idempotency_key,
+                SeatChange::Reserve,
+                request.into_inner(),
+            )
+            .await?;
+
+        Ok(Response::new(availability))
+    }
+
+    async fn release_seats(
+        &self,
+        request: Request<ChangeSeatsRequest>,
+    ) -> std::result::Result<Response<SeatAvailability>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let availability = self
+            .change_seats(
+                "ReleaseSeats",
+                idempotency_key,
+                SeatChange::Release,
+                request.into_inner(),
+            )
+            .await?;
+
+        Ok(Response::new(availability))
+    }
+
+    async fn set_overbooking_allowance(
+        &self,
+        request: Request<ChangeSeatsRequest>,
+    ) -> std::result::Result<Response<SeatAvailability>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let availability = self
+            .change_seats(
+                "SetOverbookingAllowance",
+                idempotency_key,
+                SeatChange::Allowance,
+                request.into_inner(),
+            )
+            .await?;
+
+        Ok(Response::new(availability))
+    }
+
+    async fn list_cargo_shipments(
+        &self,
+        request: Request<ListCargoShipmentsRequest>,
+    ) -> std::result::Result<Response<ListCargoShipmentsResponse>, Status> {
+        let ListCargoShipmentsRequest { flight_id } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let mut t = self.db.begin().await?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        let shipments = flight.shipments.into_iter().map(Into::into).collect();
+
+        Ok(Response::new(ListCargoShipmentsResponse { shipments }))
+    }
+
+    async fn add_cargo_shipment(
+        &self,
+        request: Request<AddCargoShipmentRequest>,
+    ) -> std::result::Result<Response<Flight>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let CargoShipment {
+            flight_id,
+            shipment_id,
+            weight_kg,
+            origin_id,
+            destination_id,
+        } = request.into_inner().shipment.unwrap_or_default();
+
+        let shipment = queries::CargoShipment {
+            flight_id: parse_id(&flight_id)?,
+            shipment_id,
+            weight_kg: i32::try_from(weight_kg)
+                .ok()
+                .filter(|w| *w > 0)
+                .ok_or_else(|| Status::invalid_argument("'weight_kg'"))?,
+            origin_id: parse_id(&origin_id)?,
+            destination_id: parse_id(&destination_id)?,
+        };
+        if shipment.shipment_id.is_empty() {
+            return Err(Status::invalid_argument("'shipment_id'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(flight) = idempotency::replay(t.get_conn(), "AddCargoShipment", key).await? {
+            return Ok(Response::new(flight));
+        }
+
+        airports::ensure_airport_active(t.get_conn(), &shipment.origin_id).await?;
+        airports::ensure_airport_active(t.get_conn(), &shipment.destination_id).await?;
+
+        // serialize concurrent bookings of the same flight
+        queries::lock_flight(t.get_conn(), &shipment.flight_id).await?;
+        let flight = data::get_flight(t.get_conn(), shipment.flight_id).await?;
+        if flight.state.is_cancelled {
+            return Err(Status::failed_precondition("flight is cancelled"));
+        }
+
+        let remaining = flight.cargo_capacity_kg - flight.cargo_booked_kg();
+        if shipment.weight_kg > remaining {
+            return Err(Status::failed_precondition(format!(
+                "only {} kg of cargo capacity remaining",
+                remaining.max(0)
+            )));
+        }
+
+        if !queries::add_cargo_shipment(t.get_conn(), &shipment).await? {
+            return Err(Status::already_exists("shipment is already on the flight"));
+        }
+
+        let flight = data::get_flight(t.get_conn(), shipment.flight_id)
+            .await?
+            .into();
+        idempotency::record(t.get_conn(), "AddCargoShipment", key, &flight).await?;
+        t.commit().await?;
+
+        self.rabbitmq.notify_flight_update(&flight).await?;
+
+        Ok(Response::new(flight))
+    }
+
+    async fn remove_cargo_shipment(
+        &self,
+        request: Request<RemoveCargoShipmentRequest>,
+    ) -> std::result::Result<Response<Flight>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let RemoveCargoShipmentRequest {
+            flight_id,
+            shipment_id,
+        } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(flight) = idempotency::replay(t.get_conn(), "RemoveCargoShipment", key).await? {
+            return Ok(Response::new(flight));
+        }
+
+        // serialize concurrent bookings of the same flight
+        queries::lock_flight(t.get_conn(), &flight_id).await?;
+        queries::remove_cargo_shipment(t.get_conn(), &flight_id, &shipment_id).await?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?.into();
+        idempotency::record(t.get_conn(), "RemoveCargoShipment", key, &flight).await?;
+        t.commit().await?;
+
+        self.rabbitmq.notify_flight_update(&flight).await?;
+
+        Ok(Response::new(flight))
+    }
+
+    async fn list_fare_buckets(
+        &self,
+        request: Request<ListFareBucketsRequest>,
+    ) -> std::result::Result<Response<ListFareBucketsResponse>, Status> {
+        let ListFareBucketsRequest { flight_id } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let mut t = self.db.begin().await?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        let availability = inventory::seat_availability(&flight.cabin_classes, &flight.inventory);
+        let buckets = fares::fare_buckets(flight.fares, &availability);
+
+        Ok(Response::new(ListFareBucketsResponse { buckets }))
+    }
+
+    async fn set_fare_buckets(
+        &self,
+        request: Request<SetFareBucketsRequest>,
+    ) -> std::result::Result<Response<ListFareBucketsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let SetFareBucketsRequest { flight_id, buckets } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let buckets = fares::parse_fare_buckets(flight_id, buckets)?;
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(response) = idempotency::replay(t.get_conn(), "SetFareBuckets", key).await? {
+            return Ok(Response::new(response));
+        }
+
+        queries::lock_flight(t.get_conn(), &flight_id).await?;
+        queries::replace_fare_buckets(t.get_conn(), &flight_id, buckets).await?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        let availability = inventory::seat_availability(&flight.cabin_classes, &flight.inventory);
+        let response = ListFareBucketsResponse {
+            buckets: fares::fare_buckets(flight.fares, &availability),
+        };
+
+        idempotency::record(t.get_conn(), "SetFareBuckets", key, &response).await?;
+        t.commit().await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn set_codeshares(
+        &self,
+        request: Request<SetCodesharesRequest>,
+    ) -> std::result::Result<Response<Flight>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let SetCodesharesRequest {
+            flight_id,
+            codeshares,
+        } = request.into_inner();
+        let flight_id = parse_id(&flight_id)?;
+        let mut numbers = codeshares
+            .into_iter()
+            .map(|c| {
+                Ok(queries::FlightNumber {
+                    carrier_id: parse_id(&c.carrier_id)?,
+                    flight_number: parse_flight_number(&c.flight_number)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        if !numbers.iter().map(|n| n.carrier_id).all_unique() {
+            return Err(Status::invalid_argument("'codeshares'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(flight) = idempotency::replay(t.get_conn(), "SetCodeshares", key).await? {
+            return Ok(Response::new(flight));
+        }
+
+        queries::lock_flight(t.get_conn(), &flight_id).await?;
+        let flight = queries::get_flight(t.get_conn(), &flight_id).await?;
+        let Some(carrier_id) = flight.carrier_id else {
+            return Err(Status::failed_precondition("flight has no flight number"));
+        };
+        // the operating airline cannot also market the flight
+        if numbers.iter().any(|n| n.carrier_id == carrier_id) {
+            return Err(Status::invalid_argument("'codeshares'"));
+        }
+        // lock the numbers in the same order as concurrent requests
+        numbers.sort_by_key(|n| n.carrier_id);
+        for number in &numbers {
+            airlines::ensure_airline_active(t.get_conn(), &number.carrier_id).await?;
+            ensure_flight_number_available(
+                t.get_conn(),
+                number,
+                &flight.departure_local_date,
+                Some(&flight_id),
+            )
+            .await?;
+        }
+
+        queries::replace_codeshares(t.get_conn(), &flight_id, numbers)
+            .await
+            .map_err(number_taken)?;
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?.into();
+        idempotency::record(t.get_conn(), "SetCodeshares", key, &flight).await?;
+        t.commit().await?;
+
+        self.rabbitmq.notify_flight_update(&flight).await?;
+        Ok(Response::new(flight))
+    }
+
+    async fn publish_flights(
+        &self,
+        request: Request<PublishFlightsRequest>,
+    ) -> std::result::Result<Response<ListFlightsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let PublishFlightsRequest { flight_ids } = request.into_inner();
+        let ids = flight_ids
+            .iter()
+            .map(|id| parse_id(id))
+            .collect::<Result<Vec<_>, Status>>()?;
+        let ids = ids.into_iter().unique().collect_vec();
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(response) = idempotency::replay(t.get_conn(), "PublishFlights", key).await? {
+            return Ok(Response::new(response));
+        }
+
+        // lock in a consistent order to avoid deadlocks with concurrent requests
+        for id in ids.iter().sorted() {
+            queries::lock_flight(t.get_conn(), id).await?;
+        }
+        for id in &ids {
+            if !queries::publish_flight(t.get_conn(), id).await? {
+                return Err(Status::failed_precondition(format!(
+                    "flight {id} is already published"
+                )));
+            }
+        }
+
+        let flights = data::get_flights(t.get_conn(), &ids).await?;
+        let response = ListFlightsResponse {
+            flights: flights.map(Into::into).collect(),
+        };
+        idempotency::record(t.get_conn(), "PublishFlights", key, &response).await?;
+        t.commit().await?;
+
+        for flight in &response.flights {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+        Ok(Response::new(response))
+    }
+
+    async fn diff_flights(
+        &self,
+        request: Request<DiffFlightsRequest>,
+    ) -> std::result::Result<Response<DiffFlightsResponse>, Status> {
+        let DiffFlightsRequest { since, until } = request.into_inner();
+        let since = parse_timestamp(since)?;
+        let until = match until {
+            Some(until) => parse_timestamp(Some(until))?,
+            None => OffsetDateTime::now_utc(),
+        };
+        if until < since {
+            return Err(Status::invalid_argument("'until'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let before = queries::get_flight_snapshots(t.get_conn(), &since).await?;
+        let after = queries::get_flight_snapshots(t.get_conn(), &until).await?;
+
+        Ok(Response::new(history::diff(before, after).into()))
+    }
 }
 
 impl FlightsApp {
-    pub fn new(db: Database, rabbitmq: Rabbit) -> Self {
+    pub fn new(db: Database, rabbitmq: Arc<Rabbit>) -> Self {
         Self { db, rabbitmq }
     }
+
+    /// Cancel, or delay by `delay`, every flight affected by a disruption at an airport.
+    ///
+    /// On a dry run the changes are rolled back and the response previews the affected flights.
+    async fn disrupt_airport(
+        &self,
+        method: &str,
+        idempotency_key: Option<IdempotencyKey>,
+        disruption: Disruption,
+        delay: Option<Duration>,
+    ) -> Result<ListFlightsResponse, Status> {
+        let Disruption {
+            airport_id,
+            window_start,
+            window_end,
+            reason,
+            dry_run,
+        } = disruption;
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref().filter(|_| !dry_run);
+        if let Some(response) = idempotency::replay(t.get_conn(), method, key).await? {
+            return Ok(response);
+        }
+
+        let flights =
+            data::lock_flights_at_airport(t.get_conn(), airport_id, window_start, window_end)
+                .await?
+                .collect::<Vec<_>>();
+
+        let mut ids = Vec::with_capacity(flights.len());
+        for flight in flights {
+            let id = flight.flight.id;
+            match delay {
+                Some(delay) => {
+                    let departure_time = flight.expected_departure_time() + delay;
+                    let arrival_time = flight.expected_arrival_time() + delay;
+                    queries::add_event_delayed(
+                        t.get_conn(),
+                        &id,
+                        &departure_time,
+                        &arrival_time,
+                        reason.clone(),
+                    )
+                    .await?;
+                }
+                None => {
+                    queries::add_event_cancelled(t.get_conn(), &id, reason.clone()).await?;
+                }
+            }
+            queries::refresh_flight_state(t.get_conn(), Some(&id)).await?;
+            ids.push(id);
+        }
+
+        let flights = data::get_flights(t.get_conn(), &ids).await?;
+        let response = ListFlightsResponse {
+            flights: flights.map(Into::into).collect(),
+        };
+
+        if dry_run {
+            t.rollback().await?;
+            return Ok(response);
+        }
+
+        idempotency::record(t.get_conn(), method, key, &response).await?;
+        t.commit().await?;
+
+        for flight in &response.flights {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
+        Ok(response)
+    }
+
+    /// Reserve or release seats of a cabin class, or set its overbooking allowance.
+    ///
+    /// The flight row is locked so that concurrent reservations cannot oversell the class.
+    async fn change_seats(
+        &self,
+        method: &str,
+        idempotency_key: Option<IdempotencyKey>,
+        change: SeatChange,
+        request: ChangeSeatsRequest,
+    ) -> Result<SeatAvailability, Status> {
+        let ChangeSeatsRequest {
+            flight_id,
+            class,
+            seats,
+        } = request;
+        let flight_id = parse_id(&flight_id)?;
+        let class_name = planes::parse_cabin_class(class)?;
+        let seats = i32::try_from(seats).map_err(|_| Status::invalid_argument("'seats'"))?;
+        if seats == 0 && !matches!(change, SeatChange::Allowance) {
+            return Err(Status::invalid_argument("'seats'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(availability) = idempotency::replay(t.get_conn(), method, key).await? {
+            return Ok(availability);
+        }
+
+        queries::lock_flight(t.get_conn(), &flight_id).await?;
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        if flight.state.is_cancelled {
+            return Err(Status::failed_precondition("flight is cancelled"));
+        }
+
+        let current = inventory::seat_availability(&flight.cabin_classes, &flight.inventory)
+            .into_iter()
+            .find(|a| a.class == class)
+            .unwrap_or_default();
+
+        match change {
+            SeatChange::Reserve => {
+                if current.available < seats as u32 {
+                    return Err(Status::failed_precondition(format!(
+                        "only {} seats available",
+                        current.available
+                    )));
+                }
+                queries::add_seats_sold(t.get_conn(), &flight_id, class_name, seats).await?;
+            }
+            SeatChange::Release => {
+                if current.sold < seats as u32 {
+                    return Err(Status::failed_precondition(format!(
+                        "only {} seats sold",
+                        current.sold
+                    )));
+                }
+                queries::remove_seats_sold(t.get_conn(), &flight_id, class_name, seats).await?;
+            }
+            SeatChange::Allowance => {
+                queries::set_overbooking_allowance(t.get_conn(), &flight_id, class_name, seats)
+                    .await?;
+            }
+        }
+
+        let flight = data::get_flight(t.get_conn(), flight_id).await?;
+        let availability = inventory::seat_availability(&flight.cabin_classes, &flight.inventory)
+            .into_iter()
+            .find(|a| a.class == class)
+            .unwrap_or(SeatAvailability {
+                class,
+                ..Default::default()
+            });
+
+        idempotency::record(t.get_conn(), method, key, &availability).await?;
+        t.commit().await?;
+
+        self.rabbitmq.notify_flight_update(&flight.into()).await?;
+
+        Ok(availability)
+    }
+}
+
+enum SeatChange {
+    Reserve,
+    Release,
+    Allowance,
+}
+
+/// Validate a flight number: one to four digits and an optional operational suffix letter.
+pub(crate) fn parse_flight_number(number: &str) -> Result<String, Status> {
+    let number = number.trim().to_uppercase();
+    let digits = number.trim_end_matches(|c: char| c.is_ascii_uppercase());
+    let valid = (1..=4).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && number.len() - digits.len() <= 1;
+    if !valid {
+        return Err(Status::invalid_argument("'flight_number'"));
+    }
+
+    Ok(number)
+}
+
+/// Ensure that no flight other than `flight_id` operates or markets a flight number on a date.
+///
+/// The number stays locked until the end of the transaction.
+async fn ensure_flight_number_available(
+    ex: &mut PgConnection,
+    number: &queries::FlightNumber,
+    departure_local_date: &time::Date,
+    flight_id: Option<&Uuid>,
+) -> Result<(), Status> {
+    queries::lock_flight_number(ex, number, departure_local_date).await?;
+    match queries::get_flight_by_number(ex, number, departure_local_date).await {
+        Ok(other) if Some(&other.id) == flight_id => Ok(()),
+        Ok(other) => Err(Status::already_exists(format!(
+            "flight number already used by flight {}",
+            other.id
+        ))),
+        Err(DatabaseError::NotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Report a flight number taken by a concurrent transaction like one already in use.
+fn number_taken(error: DatabaseError) -> Status {
+    match error.constraint() {
+        Some("flights_number_key" | "flight_codeshares_number_key") => {
+            Status::already_exists("flight number already used on that date")
+        }
+        _ => error.into(),
+    }
+}
+
+/// Get the non-cancelled flights of a plane overlapping a time window.
+pub(crate) async fn get_plane_flights(
+    ex: &mut PgConnection,
+    plane_id: Uuid,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> Result<Vec<Flight>, DatabaseError> {
+    let flights = data::get_plane_flights_during(ex, plane_id, from, Some(to)).await?;
+
+    Ok(flights.map(Into::into).collect())
+}
+
+/// Ensure that the seats sold on the future flights of a plane fit in its current cabin.
+///
+/// The flights are locked so that no seats are sold on them until the end of the transaction.
+pub(crate) async fn ensure_future_seats_fit(
+    ex: &mut PgConnection,
+    plane_id: Uuid,
+) -> Result<(), Status> {
+    let flights = data::lock_plane_flights_after(ex, plane_id, OffsetDateTime::now_utc()).await?;
+    for flight in flights {
+        inventory::ensure_seats_fit(&flight.cabin_classes, &flight.inventory).map_err(|e| {
+            Status::failed_precondition(format!("flight {}: {}", flight.flight.id, e.message()))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// A plane or an airport that flights depend on.
+pub(crate) enum FlightResource {
+    Plane(Uuid),
+    Airport(Uuid),
+}
+
+/// Cancel the future flights using a resource that is about to be deleted, or fail listing
+/// them if no cancellation reason is given.
+///
+/// Returns the cancelled flights, to be notified once the transaction is committed.
+pub(crate) async fn cancel_future_flights(
+    ex: &mut PgConnection,
+    resource: FlightResource,
+    reason: Option<String>,
+) -> Result<Vec<Flight>, Status> {
+    let now = OffsetDateTime::now_utc();
+    let (ids, name): (Vec<_>, _) = match resource {
+        FlightResource::Plane(id) => {
+            let flights = data::lock_plane_flights_after(ex, id, now).await?;
+            (flights.map(|f| f.flight.id).collect(), "plane")
+        }
+        FlightResource::Airport(id) => {
+            let flights = data::lock_airport_flights_after(ex, id, now).await?;
+            (flights.map(|f| f.flight.id).collect(), "airport")
+        }
+    };
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(reason) = reason else {
+        return Err(Status::failed_precondition(format!(
+            "{name} has future flights: {}",
+            ids.iter().join(", ")
+        )));
+    };
+
+    for id in &ids {
+        queries::add_event_cancelled(ex, id, reason.clone()).await?;
+        queries::refresh_flight_state(ex, Some(id)).await?;
+    }
+
+    let flights = data::get_flights(ex, &ids).await?;
+    Ok(flights.map(Into::into).collect())
+}
+
+/// Regenerate the current state projection of all flights from the event tables.
+pub async fn rebuild_current_state(db: &Database) -> Result<u64, DatabaseError> {
+    let mut t = db.begin().await?;
+
+    let count = queries::refresh_flight_state(t.get_conn(), None).await?;
+
+    t.commit().await?;
+    Ok(count)
+}
+
+/// A validated disruption at an airport during a time window.
+struct Disruption {
+    airport_id: Uuid,
+    window_start: OffsetDateTime,
+    window_end: OffsetDateTime,
+    reason: String,
+    dry_run: bool,
+}
+
+impl TryFrom<Option<AirportDisruption>> for Disruption {
+    type Error = Status;
+
+    fn try_from(disruption: Option<AirportDisruption>) -> Result<Self, Self::Error> {
+        let AirportDisruption {
+            airport_id,
+            window_start,
+            window_end,
+            reason,
+            dry_run,
+        } = disruption.ok_or(Status::invalid_argument("'disruption' is required"))?;
+        let airport_id = parse_id(&airport_id)?;
+        let window_start = parse_timestamp(window_start)?;
+        let window_end = parse_timestamp(window_end)?;
+        if window_end < window_start {
+            return Err(Status::invalid_argument(
+                "'window_end' must not be before 'window_start'",
+            ));
+        }
+
+        Ok(Self {
+            airport_id,
+            window_start,
+            window_end,
+            reason,
+            dry_run,
+        })
+    }
+}
+
+/// A validated status update of a single flight.
+struct FlightUpdate {
+    id: Uuid,
+    event: Event,
+    expected_version: Option<u64>,
+}
+
+impl TryFrom<UpdateFlightRequest> for FlightUpdate {
+    type Error = Status;
+
+    fn try_from(request: UpdateFlightRequest) -> Result<Self, Self::Error> {
+        let UpdateFlightRequest {
+            id,
+            status_event,
+            expected_version,
+        } = request;
+        let id = parse_id(&id)?;
+        let FlightStatusEvent { event, .. } =
+            status_event.ok_or(Status::invalid_argument("'status_event' is required"))?;
+        let event = event.ok_or(Status::invalid_argument("'status_event.event' is required"))?;
+
+        Ok(Self {
+            id,
+            event,
+            expected_version,
+        })
+    }
+}
+
+/// Record a status event and refresh the current state of the flight.
+///
+/// The caller must hold the lock on the flight row.
+async fn apply_update(ex: &mut PgConnection, update: FlightUpdate) -> Result<(), Status> {
+    let FlightUpdate {
+        id,
+        event,
+        expected_version,
+    } = update;
+
+    if let Some(expected_version) = expected_version {
+        let version = queries::get_flight_version(ex, &id).await?;
+        if version as u64 != expected_version {
+            return Err(Status::aborted(format!(
+                "flight was modified concurrently, current version is {version}"
+            )));
+        }
+    }
+
+    match event {
+        Event::FlightCancelled(FlightCancelled { reason }) => {
+            queries::add_event_cancelled(ex, &id, reason).await?;
+        }
+        Event::FlightDelayed(FlightDelayed {
+            arrival_time,
+            departure_time,
+            reason,
+        }) => {
+            let arrival_time = parse_timestamp(arrival_time)?;
+            let departure_time = parse_timestamp(departure_time)?;
+            queries::add_event_delayed(ex, &id, &departure_time, &arrival_time, reason).await?;
+        }
+        Event::FlightGateDeparture(FlightGateDeparture { gate }) => {
+            queries::add_event_gate_dep_set(ex, &id, &gate).await?;
+        }
+        Event::FlightGateArrival(FlightGateArrival { gate }) => {
+            queries::add_event_gate_arr_set(ex, &id, &gate).await?;
+        }
+        Event::FlightEventRetracted(FlightEventRetracted { event_id, reason }) => {
+            let event_id = event_id
+                .parse()
+                .map_err(|_| Status::invalid_argument("'event_id'"))?;
+            let flight = data::get_flight(ex, id).await?;
+            queries::ensure_event_retractable(ex, &id, &event_id).await?;
+            queries::add_event_retracted(ex, &id, &event_id, reason).await?;
+
+            // retracting a plane change hands the flight back to the previous plane
+            queries::refresh_flight_state(ex, Some(&id)).await?;
+            let plane_id = data::get_flight(ex, id).await?.state.plane_id;
+            if plane_id != flight.state.plane_id {
+                planes::ensure_plane_available(
+                    ex,
+                    &plane_id,
+                    &flight.expected_departure_time(),
+                    &flight.expected_arrival_time(),
+                )
+                .await?;
+                ensure_load_fits(ex, &flight, &plane_id).await?;
+            }
+        }
+        Event::FlightPlaneChanged(FlightPlaneChanged { plane_id }) => {
+            let plane_id = parse_id(&plane_id)?;
+            let flight = data::get_flight(ex, id).await?;
+            planes::ensure_plane_available(
+                ex,
+                &plane_id,
+                &flight.expected_departure_time(),
+                &flight.expected_arrival_time(),
+            )
+            .await?;
+            ensure_load_fits(ex, &flight, &plane_id).await?;
+            queries::add_event_plane_changed(ex, &id, &plane_id).await?;
+        }
+    };
+    queries::refresh_flight_state(ex, Some(&id)).await?;
+
+    Ok(())
+}
+
+fn batch_item_error(index: usize, error: Status) -> Status {
+    Status::new(
+        error.code(),
+        format!("updates[{index}]: {}", error.message()),
+    )
+}
+
+/// Ensure that the seats and the cargo already sold on a flight fit on another plane.
+async fn ensure_load_fits(
+    ex: &mut PgConnection,
+    flight: &data::FlightData,
+    plane_id: &Uuid,
+) -> Result<(), Status> {
+    let classes = planes::get_cabin_classes(ex, &[*plane_id])
+        .await?
+        .remove(plane_id)
+        .unwrap_or_default();
+    inventory::ensure_seats_fit(&classes, &flight.inventory)?;
+
+    let cargo_capacity_kg = planes::get_cargo_capacities(ex, &[*plane_id])
+        .await?
+        .remove(plane_id)
+        .unwrap_or_default();
+    if flight.cargo_booked_kg() > cargo_capacity_kg {
+        return Err(Status::failed_precondition(format!(
+            "{} kg of cargo booked but only {} kg fit",
+            flight.cargo_booked_kg(),
+            cargo_capacity_kg
+        )));
+    }
+
+    Ok(())
+}
+
+/// Ensure that the plane, the airline and the flight number of a flight can be used.
+async fn ensure_spec_valid(
+    ex: &mut PgConnection,
+    spec: &queries::FlightSpec,
+    flight_id: Option<&Uuid>,
+) -> Result<(), Status> {
+    planes::ensure_plane_available(ex, &spec.plane_id, &spec.departure_time, &spec.arrival_time)
+        .await?;
+
+    if let Some(number) = &spec.number {
+        airlines::ensure_airline_active(ex, &number.carrier_id).await?;
+        let departure_local_date =
+            queries::get_local_date(ex, &spec.origin_id, &spec.departure_time).await?;
+        ensure_flight_number_available(ex, number, &departure_local_date, flight_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Get the flights generated by a schedule.
+pub(crate) async fn get_schedule_instances(
+    ex: &mut PgConnection,
+    schedule_id: &Uuid,
+) -> Result<Vec<ScheduleInstance>, DatabaseError> {
+    queries::get_schedule_instances(ex, schedule_id).await
+}
+
+/// Find a plane of an aircraft type free to operate a flight.
+pub(crate) async fn find_available_plane(
+    ex: &mut PgConnection,
+    type_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Option<Uuid>, DatabaseError> {
+    let planes = queries::find_available_planes_of_type(ex, type_id, from, to).await?;
+
+    Ok(planes.first().copied())
+}
+
+/// Create a flight generated by a schedule.
+pub(crate) async fn create_scheduled_flight(
+    ex: &mut PgConnection,
+    schedule_id: &Uuid,
+    spec: &FlightSpec,
+    draft: bool,
+) -> Result<Flight, Status> {
+    ensure_spec_valid(ex, spec, None).await?;
+
+    Ok(data::create_flight(ex, spec, Some(schedule_id), draft)
+        .await
+        .map_err(number_taken)?
+        .into())
+}
+
+/// Replace the scheduled data of a flight that has no status events.
+pub(crate) async fn update_scheduled_flight(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    spec: &FlightSpec,
+) -> Result<Flight, Status> {
+    queries::lock_flight(ex, id).await?;
+    let flight = data::get_flight(ex, *id).await?;
+    ensure_spec_valid(ex, spec, Some(id)).await?;
+    if flight.flight.plane_id != spec.plane_id {
+        ensure_load_fits(ex, &flight, &spec.plane_id).await?;
+    }
+
+    queries::update_flight_spec(ex, id, spec)
+        .await
+        .map_err(number_taken)?;
+    queries::refresh_flight_state(ex, Some(id)).await?;
+
+    Ok(data::get_flight(ex, *id).await?.into())
+}
+
+/// Cancel a flight generated by a schedule.
+pub(crate) async fn cancel_scheduled_flight(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    reason: &str,
+) -> Result<Flight, DatabaseError> {
+    queries::lock_flight(ex, id).await?;
+    let event = queries::add_event_cancelled(ex, id, reason.to_string()).await?;
+    queries::add_schedule_event(ex, id, &event.id).await?;
+    queries::refresh_flight_state(ex, Some(id)).await?;
+
+    Ok(data::get_flight(ex, *id).await?.into())
+}
+
+/// Retract the cancellation of a flight by its schedule, with the scheduled data of its date.
+pub(crate) async fn restore_scheduled_flight(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    spec: &FlightSpec,
+    reason: &str,
+) -> Result<Flight, Status> {
+    // the plane may have been assigned elsewhere while the flight was cancelled
+    update_scheduled_flight(ex, id, spec).await?;
+
+    let cancellation_id = queries::get_schedule_cancellation(ex, id).await?;
+    let event = queries::add_event_retracted(ex, id, &cancellation_id, reason.to_string()).await?;
+    queries::add_schedule_event(ex, id, &event.id).await?;
+    queries::refresh_flight_state(ex, Some(id)).await?;
+
+    Ok(data::get_flight(ex, *id).await?.into())
+}
+
+/// Get the published flights between two airports scheduled to depart within a time window.
+pub(crate) async fn get_flights_between(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<Flight>, DatabaseError> {
+    let flights = data::get_flights_between(ex, origin_id, destination_id, from, to).await?;
+
+    Ok(flights.map(Into::into).collect())
+}
+
+/// Get the expected departure and arrival times of the flights of a plane overlapping a time
+/// window.
+pub(crate) async fn get_plane_flight_times(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<(Uuid, OffsetDateTime, OffsetDateTime)>, DatabaseError> {
+    let flights = data::get_plane_flights_during(ex, *plane_id, *from, Some(*to)).await?;
+
+    Ok(flights
+        .map(|f| {
+            (
+                f.flight.id,
+                f.expected_departure_time(),
+                f.expected_arrival_time(),
+            )
+        })
+        .collect())
 }
@@ -1,8 +1,10 @@
-use sqlx::types::time::OffsetDateTime;
+use sqlx::types::time::{Date, OffsetDateTime};
 use sqlx::types::Uuid;
 use sqlx::PgConnection;
 
-type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+use crate::db::DatabaseError;
+
+type Result<T> = std::result::Result<T, DatabaseError>;
 
 pub struct Flight {
     pub id: Uuid,
@@ -11,13 +13,77 @@ pub struct Flight {
     pub destination_id: Uuid,
     pub departure_time: OffsetDateTime,
     pub arrival_time: OffsetDateTime,
+    pub carrier_id: Option<Uuid>,
+    pub flight_number: Option<String>,
+    pub departure_local_date: Date,
+    pub schedule_id: Option<Uuid>,
+    pub published_at: Option<OffsetDateTime>,
 }
 
-pub async fn list_flights(ex: &mut PgConnection) -> Result<Vec<Flight>> {
+/// Scheduled data of a flight, before any status event.
+#[derive(PartialEq)]
+pub struct FlightSpec {
+    pub plane_id: Uuid,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub departure_time: OffsetDateTime,
+    pub arrival_time: OffsetDateTime,
+    pub number: Option<FlightNumber>,
+}
+
+/// Flight generated by a schedule.
+pub struct ScheduleInstance {
+    pub id: Uuid,
+    pub departure_local_date: Date,
+    pub plane_id: Uuid,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub departure_time: OffsetDateTime,
+    pub arrival_time: OffsetDateTime,
+    pub carrier_id: Option<Uuid>,
+    pub flight_number: Option<String>,
+    /// Whether status events were recorded for the flight, other than by its schedule.
+    pub modified: bool,
+    /// Whether the flight is cancelled, by its schedule unless it is modified.
+    pub cancelled: bool,
+}
+
+/// Published flight as it was at a point in time, from the events recorded until then.
+#[derive(Clone, PartialEq)]
+pub struct FlightSnapshot {
+    pub flight_id: Uuid,
+    pub designator: Option<String>,
+    pub plane_id: Uuid,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub departure_time: OffsetDateTime,
+    pub arrival_time: OffsetDateTime,
+    pub departure_gate: Option<String>,
+    pub arrival_gate: Option<String>,
+}
+
+/// Marketing flight number of another airline on an operating flight.
+pub struct Codeshare {
+    pub flight_id: Uuid,
+    pub carrier_id: Uuid,
+    pub flight_number: String,
+}
+
+/// Flight number of a flight, unique per carrier and local departure date.
+#[derive(PartialEq)]
+pub struct FlightNumber {
+    pub carrier_id: Uuid,
+    pub flight_number: String,
+}
+
+pub async fn list_flights(ex: &mut PgConnection, include_drafts: bool) -> Result<Vec<Flight>> {
     let flights = sqlx::query_as!(
         Flight,
-        "select * from flights \
-        where id not in (select flight_id from flight_cancellations)"
+        "select flights.* from flights \
+        join flight_current_state on flight_id = id \
+        where not is_cancelled and ($1 or published_at is not null) \
+        order by coalesce(expected_departure_time, departure_time)",
+        include_drafts
     )
     .fetch_all(ex)
     .await?;
@@ -25,10 +91,20 @@ pub async fn list_flights(ex: &mut PgConnection) -> Result<Vec<Flight>> {
     Ok(flights)
 }
 
-pub async fn list_flights_with_cancelled(ex: &mut PgConnection) -> Result<Vec<Flight>> {
-    let flights = sqlx::query_as!(Flight, "select * from flights")
-        .fetch_all(ex)
-        .await?;
+pub async fn list_flights_with_cancelled(
+    ex: &mut PgConnection,
+    include_drafts: bool,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "select flights.* from flights \
+        join flight_current_state on flight_id = id \
+        where $1 or published_at is not null \
+        order by coalesce(expected_departure_time, departure_time)",
+        include_drafts
+    )
+    .fetch_all(ex)
+    .await?;
 
     Ok(flights)
 }
@@ -38,16 +114,45 @@ pub async fn search_flights(
     origin_id: Uuid,
     destination_id: Uuid,
     departure_day: OffsetDateTime,
+    include_drafts: bool,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "select flights.* from flights \
+        join flight_current_state on flight_id = id \
+        where origin_id = $1 and destination_id = $2 and not is_cancelled \
+        and departure_time between $3 and $3 + interval '1 day' \
+        and ($4 or published_at is not null) \
+        order by coalesce(expected_departure_time, departure_time)",
+        origin_id,
+        destination_id,
+        departure_day,
+        include_drafts
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// Get the published flights between two airports scheduled to depart within a time window.
+pub async fn get_flights_between(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
 ) -> Result<Vec<Flight>> {
     let flights = sqlx::query_as!(
         Flight,
         "select * from flights \
-        where origin_id = $1 and destination_id = $2 \
-        and id not in (select flight_id from flight_cancellations) \
-        and departure_time between $3 and $3 + interval '1 day'",
+        where origin_id = $1 and destination_id = $2 and published_at is not null \
+        and departure_time between $3 and $4 \
+        order by departure_time",
         origin_id,
         destination_id,
-        departure_day
+        from,
+        to
     )
     .fetch_all(ex)
     .await?;
@@ -68,30 +173,500 @@ pub async fn get_flight(ex: &mut PgConnection, id: &Uuid) -> Result<Flight> {
     Ok(flight)
 }
 
+/// Lock and return the flights that are not cancelled and are expected to depart from
+/// or arrive at an airport within a time window, ordered by expected departure.
+///
+/// Rows are locked in id order like single flight updates, to avoid deadlocks.
+pub async fn lock_flights_at_airport(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    window_start: &OffsetDateTime,
+    window_end: &OffsetDateTime,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "with locked as ( \
+            select id from flights \
+            join flight_current_state on flight_id = id \
+            where not is_cancelled and ( \
+                (origin_id = $1 and coalesce(expected_departure_time, departure_time) between $2 and $3) \
+                or (destination_id = $1 and coalesce(expected_arrival_time, arrival_time) between $2 and $3) \
+            ) \
+            order by id \
+            for update of flights \
+        ) \
+        select flights.* from flights \
+        join locked using (id) \
+        join flight_current_state on flight_id = id \
+        order by coalesce(expected_departure_time, departure_time)",
+        airport_id,
+        window_start,
+        window_end
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// Lock and return the flights that are not cancelled and depart from or arrive at an airport
+/// after a given time, ordered by expected departure. Rows are locked in id order.
+pub async fn lock_airport_flights_after(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    after: &OffsetDateTime,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "with locked as ( \
+            select id from flights \
+            join flight_current_state on flight_id = id \
+            where (origin_id = $1 or destination_id = $1) and not is_cancelled \
+            and coalesce(expected_departure_time, departure_time) > $2 \
+            order by id \
+            for update of flights \
+        ) \
+        select flights.* from flights \
+        join locked using (id) \
+        join flight_current_state on flight_id = id \
+        order by coalesce(expected_departure_time, departure_time)",
+        airport_id,
+        after
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// Lock and return the flights that are not cancelled and are operated by a plane after a
+/// given time, ordered by expected departure. Rows are locked in id order.
+pub async fn lock_plane_flights_after(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    after: &OffsetDateTime,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "with locked as ( \
+            select id from flights \
+            join flight_current_state on flight_id = id \
+            where flight_current_state.plane_id = $1 and not is_cancelled \
+            and coalesce(expected_departure_time, departure_time) > $2 \
+            order by id \
+            for update of flights \
+        ) \
+        select flights.* from flights \
+        join locked using (id) \
+        join flight_current_state on flight_id = id \
+        order by coalesce(expected_departure_time, departure_time)",
+        plane_id,
+        after
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// Return the flights that are not cancelled and are operated by a plane during a time
+/// window, or after `from` if the window has no end.
+pub async fn get_plane_flights_during(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    from: &OffsetDateTime,
+    to: Option<&OffsetDateTime>,
+) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "select flights.* from flights \
+        join flight_current_state on flight_id = id \
+        where flight_current_state.plane_id = $1 and not is_cancelled \
+        and coalesce(expected_arrival_time, arrival_time) > $2 \
+        and ($3::timestamptz is null or coalesce(expected_departure_time, departure_time) < $3) \
+        order by coalesce(expected_departure_time, departure_time)",
+        plane_id,
+        from,
+        to
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// A plane that can replace another one on a flight.
+pub struct ReplacementPlane {
+    pub flight_id: Uuid,
+    pub plane_id: Uuid,
+}
+
+/// Find the planes that can replace another one on flights: in service during the flight,
+/// with at least the same capacity, and neither flying nor unavailable during the flight.
+/// Planes are ordered by capacity for each flight.
+pub async fn find_replacement_planes(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    flight_ids: &[Uuid],
+    departure_times: &[OffsetDateTime],
+    arrival_times: &[OffsetDateTime],
+) -> Result<Vec<ReplacementPlane>> {
+    let planes = sqlx::query_as!(
+        ReplacementPlane,
+        r#"select w.flight_id as "flight_id!", p.id as "plane_id!"
+        from unnest($2::uuid[], $3::timestamptz[], $4::timestamptz[])
+            as w(flight_id, departure_time, arrival_time)
+        cross join planes p
+        join plane_capacities c on c.plane_id = p.id
+        join plane_capacities original on original.plane_id = $1
+        where p.id <> original.plane_id and not p.deleted
+        and (p.in_service_date is null or p.in_service_date <= w.departure_time::date)
+        and (p.retirement_date is null or p.retirement_date > w.arrival_time::date)
+        and c.cabin_capacity >= original.cabin_capacity
+        and c.cargo_capacity_kg >= original.cargo_capacity_kg
+        and not exists (
+            select from flights f join flight_current_state s on s.flight_id = f.id
+            where s.plane_id = p.id and not s.is_cancelled
+            and coalesce(s.expected_departure_time, f.departure_time) < w.arrival_time
+            and coalesce(s.expected_arrival_time, f.arrival_time) > w.departure_time
+        )
+        and not exists (
+            select from plane_unavailabilities u
+            where u.plane_id = p.id and u.start_time < w.arrival_time
+            and (u.end_time is null or u.end_time > w.departure_time)
+        )
+        order by c.cabin_capacity, c.cargo_capacity_kg, p.id"#,
+        plane_id,
+        flight_ids,
+        departure_times,
+        arrival_times
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(planes)
+}
+
+/// Find the planes of an aircraft type that are in service, neither flying nor unavailable
+/// during a time window.
+pub async fn find_available_planes_of_type(
+    ex: &mut PgConnection,
+    type_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<Uuid>> {
+    let planes = sqlx::query_scalar!(
+        "select p.id from planes p \
+        where p.type_id = $1 and not p.deleted \
+        and (p.in_service_date is null or p.in_service_date <= $2::timestamptz::date) \
+        and (p.retirement_date is null or p.retirement_date > $3::timestamptz::date) \
+        and not exists ( \
+            select from flights f join flight_current_state s on s.flight_id = f.id \
+            where s.plane_id = p.id and not s.is_cancelled \
+            and coalesce(s.expected_departure_time, f.departure_time) < $3 \
+            and coalesce(s.expected_arrival_time, f.arrival_time) > $2 \
+        ) \
+        and not exists ( \
+            select from plane_unavailabilities u \
+            where u.plane_id = p.id and u.start_time < $3 \
+            and (u.end_time is null or u.end_time > $2) \
+        ) \
+        order by p.registration, p.id",
+        type_id,
+        from,
+        to
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(planes)
+}
+
+/// Get the given flights, in the same order as `id`.
+pub async fn get_flights(ex: &mut PgConnection, id: &[Uuid]) -> Result<Vec<Flight>> {
+    let flights = sqlx::query_as!(
+        Flight,
+        "select flights.* from flights \
+        join unnest($1::uuid[]) with ordinality as U(ids, n) on id = ids \
+        order by n",
+        id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(flights)
+}
+
+/// Lock the row of a flight until the end of the transaction.
+pub async fn lock_flight(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
+    sqlx::query!("select id from flights where id = $1 for update", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_flight_version(ex: &mut PgConnection, id: &Uuid) -> Result<i64> {
+    let version = sqlx::query_scalar!(
+        "select version from flight_current_state where flight_id = $1",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(version)
+}
+
 pub async fn create_flight(
     ex: &mut PgConnection,
-    plane_id: Uuid,
-    origin_id: Uuid,
-    destination_id: Uuid,
-    departure_time: OffsetDateTime,
-    arrival_time: OffsetDateTime,
+    spec: &FlightSpec,
+    schedule_id: Option<&Uuid>,
+    draft: bool,
 ) -> Result<Flight> {
     let flight = sqlx::query_as!(
         Flight,
-        "insert into flights (id, plane_id, origin_id, destination_id, departure_time, arrival_time) values (gen_random_uuid(), $1, $2, $3, $4, $5) returning *",
-        plane_id,
-        origin_id,
-        destination_id,
-        departure_time,
-        arrival_time
+        "insert into flights (id, plane_id, origin_id, destination_id, departure_time, arrival_time, carrier_id, flight_number, departure_local_date, schedule_id, published_at) values (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7, (select ($4 at time zone timezone)::date from airports where id = $2), $8, case when $9 then null else now() end) returning *",
+        spec.plane_id,
+        spec.origin_id,
+        spec.destination_id,
+        spec.departure_time,
+        spec.arrival_time,
+        spec.number.as_ref().map(|n| n.carrier_id),
+        spec.number.as_ref().map(|n| n.flight_number.as_str()),
+        schedule_id,
+        draft
     )
-    .fetch_one(ex)
+    .fetch_one(&mut *ex)
     .await?;
 
+    record_flight_spec(ex, &flight.id).await?;
     Ok(flight)
 }
 
+/// Publish a draft flight, returning whether it was a draft.
+pub async fn publish_flight(ex: &mut PgConnection, id: &Uuid) -> Result<bool> {
+    let result = sqlx::query!(
+        "update flights set published_at = now() where id = $1 and published_at is null",
+        id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Replace the scheduled data of a flight.
+pub async fn update_flight_spec(ex: &mut PgConnection, id: &Uuid, spec: &FlightSpec) -> Result<()> {
+    sqlx::query!(
+        "update flights set plane_id = $2, origin_id = $3, destination_id = $4, departure_time = $5, arrival_time = $6, carrier_id = $7, flight_number = $8, departure_local_date = (select ($5 at time zone timezone)::date from airports where id = $3) where id = $1",
+        id,
+        spec.plane_id,
+        spec.origin_id,
+        spec.destination_id,
+        spec.departure_time,
+        spec.arrival_time,
+        spec.number.as_ref().map(|n| n.carrier_id),
+        spec.number.as_ref().map(|n| n.flight_number.as_str())
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    record_flight_spec(ex, id).await
+}
+
+/// Keep the current scheduled data of a flight in its history.
+async fn record_flight_spec(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
+    sqlx::query!(
+        "insert into flight_schedule_history ( \
+            flight_id, plane_id, origin_id, destination_id, departure_time, arrival_time, \
+            carrier_id, flight_number \
+        ) \
+        select id, plane_id, origin_id, destination_id, departure_time, arrival_time, \
+            carrier_id, flight_number \
+        from flights where id = $1 \
+        on conflict (flight_id, valid_from) do update set \
+            plane_id = excluded.plane_id, \
+            origin_id = excluded.origin_id, \
+            destination_id = excluded.destination_id, \
+            departure_time = excluded.departure_time, \
+            arrival_time = excluded.arrival_time, \
+            carrier_id = excluded.carrier_id, \
+            flight_number = excluded.flight_number",
+        id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_schedule_instances(
+    ex: &mut PgConnection,
+    schedule_id: &Uuid,
+) -> Result<Vec<ScheduleInstance>> {
+    let instances = sqlx::query_as!(
+        ScheduleInstance,
+        r#"select f.id, f.departure_local_date, f.plane_id, f.origin_id, f.destination_id,
+        f.departure_time, f.arrival_time, f.carrier_id, f.flight_number,
+        s.version > (select count(*) from flight_schedule_events e where e.flight_id = f.id) as "modified!",
+        s.is_cancelled as cancelled
+        from flights f join flight_current_state s on s.flight_id = f.id
+        where f.schedule_id = $1 order by f.departure_local_date"#,
+        schedule_id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(instances)
+}
+
+/// Record a status event of a flight as made by its schedule.
+pub async fn add_schedule_event(ex: &mut PgConnection, id: &Uuid, event_id: &Uuid) -> Result<()> {
+    sqlx::query!(
+        "insert into flight_schedule_events (event_id, flight_id) values ($1, $2)",
+        event_id,
+        id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the cancellation of a flight by its schedule that was not retracted.
+pub async fn get_schedule_cancellation(ex: &mut PgConnection, id: &Uuid) -> Result<Uuid> {
+    let event_id = sqlx::query_scalar!(
+        "select c.id from flight_cancellations c \
+        join flight_schedule_events e on e.event_id = c.id \
+        where c.flight_id = $1 and c.id not in (select event_id from flight_retractions)",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(event_id)
+}
+
+pub struct FlightState {
+    pub flight_id: Uuid,
+    pub expected_departure_time: Option<OffsetDateTime>,
+    pub expected_arrival_time: Option<OffsetDateTime>,
+    pub departure_gate: Option<String>,
+    pub arrival_gate: Option<String>,
+    pub is_cancelled: bool,
+    pub status: String,
+    pub version: i64,
+    pub plane_id: Uuid,
+}
+
+pub async fn get_flight_state(ex: &mut PgConnection, id: &[Uuid]) -> Result<Vec<FlightState>> {
+    let states = sqlx::query_as!(
+        FlightState,
+        "select flight_current_state.* from flight_current_state join unnest($1::uuid[]) as U(ids) on flight_id = ids",
+        id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(states)
+}
+
+/// Recompute the current state projection of a flight from its status events,
+/// or of every flight if `id` is `None`. The fold itself is the
+/// `flight_state_fold` view.
+///
+/// Must be called in the same transaction that inserts status events.
+pub async fn refresh_flight_state(ex: &mut PgConnection, id: Option<&Uuid>) -> Result<u64> {
+    let res = sqlx::query!(
+        "insert into flight_current_state ( \
+            flight_id, expected_departure_time, expected_arrival_time, departure_gate, \
+            arrival_gate, is_cancelled, status, version, plane_id \
+        ) \
+        select \
+            flight_id, expected_departure_time, expected_arrival_time, departure_gate, \
+            arrival_gate, is_cancelled, status, version, plane_id \
+        from flight_state_fold \
+        where $1::uuid is null or flight_id = $1 \
+        on conflict (flight_id) do update set \
+            expected_departure_time = excluded.expected_departure_time, \
+            expected_arrival_time = excluded.expected_arrival_time, \
+            departure_gate = excluded.departure_gate, \
+            arrival_gate = excluded.arrival_gate, \
+            is_cancelled = excluded.is_cancelled, \
+            status = excluded.status, \
+            version = excluded.version, \
+            plane_id = excluded.plane_id",
+        id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Get the flights published and not cancelled at a point in time, as they were then,
+/// from the scheduled data and the status events recorded by then.
+pub async fn get_flight_snapshots(
+    ex: &mut PgConnection,
+    at: &OffsetDateTime,
+) -> Result<Vec<FlightSnapshot>> {
+    let snapshots = sqlx::query_as!(
+        FlightSnapshot,
+        "with retracted as ( \
+            select event_id from flight_retractions where timestamp <= $1 \
+        ) \
+        select \
+            f.id as flight_id, \
+            a.iata || h.flight_number as designator, \
+            coalesce(p.plane_id, h.plane_id) as \"plane_id!\", \
+            h.origin_id as \"origin_id!\", \
+            h.destination_id as \"destination_id!\", \
+            coalesce(d.departure_time, h.departure_time) as \"departure_time!\", \
+            coalesce(d.arrival_time, h.arrival_time) as \"arrival_time!\", \
+            gd.gate as \"departure_gate?\", \
+            ga.gate as \"arrival_gate?\" \
+        from flights f \
+        join lateral ( \
+            select * from flight_schedule_history e \
+            where e.flight_id = f.id and e.valid_from <= $1 \
+            order by e.valid_from desc limit 1 \
+        ) h on true \
+        left join airlines a on a.id = h.carrier_id \
+        left join lateral ( \
+            select departure_time, arrival_time from flight_delays e \
+            where e.flight_id = f.id and e.timestamp <= $1 and e.id not in (select event_id from retracted) \
+            order by e.timestamp desc limit 1 \
+        ) d on true \
+        left join lateral ( \
+            select gate from flight_departure_gates e \
+            where e.flight_id = f.id and e.timestamp <= $1 and e.id not in (select event_id from retracted) \
+            order by e.timestamp desc limit 1 \
+        ) gd on true \
+        left join lateral ( \
+            select gate from flight_arrival_gates e \
+            where e.flight_id = f.id and e.timestamp <= $1 and e.id not in (select event_id from retracted) \
+            order by e.timestamp desc limit 1 \
+        ) ga on true \
+        left join lateral ( \
+            select plane_id from flight_plane_changes e \
+            where e.flight_id = f.id and e.timestamp <= $1 and e.id not in (select event_id from retracted) \
+            order by e.timestamp desc limit 1 \
+        ) p on true \
+        where f.published_at <= $1 and not exists ( \
+            select from flight_cancellations e \
+            where e.flight_id = f.id and e.timestamp <= $1 and e.id not in (select event_id from retracted) \
+        ) \
+        order by coalesce(d.departure_time, h.departure_time)",
+        at
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(snapshots)
+}
+
 pub struct EventCancelled {
+    pub id: Uuid,
     pub flight_id: Uuid,
     pub timestamp: OffsetDateTime,
     pub reason: Option<String>,
@@ -130,10 +705,12 @@ pub async fn add_event_cancelled(
 }
 
 pub struct EventDelayed {
+    pub id: Uuid,
     pub flight_id: Uuid,
     pub timestamp: OffsetDateTime,
     pub departure_time: OffsetDateTime,
     pub arrival_time: OffsetDateTime,
+    pub reason: Option<String>,
 }
 
 pub async fn get_event_delayed(ex: &mut PgConnection, id: &[Uuid]) -> Result<Vec<EventDelayed>> {
@@ -151,13 +728,15 @@ pub async fn add_event_delayed(
     id: &Uuid,
     departure_time: &OffsetDateTime,
     arrival_time: &OffsetDateTime,
+    reason: String,
 ) -> Result<EventDelayed> {
     let e = sqlx::query_as!(
         EventDelayed,
-        "insert into flight_delays (flight_id, departure_time, arrival_time) values ($1, $2, $3) returning *",
+        "insert into flight_delays (flight_id, departure_time, arrival_time, reason) values ($1, $2, $3, $4) returning *",
         id,
         departure_time,
-        arrival_time
+        arrival_time,
+        reason
     )
     .fetch_one(ex)
     .await?;
@@ -166,6 +745,7 @@ pub async fn add_event_delayed(
 }
 
 pub struct EventGateDepartureSet {
+    pub id: Uuid,
     pub flight_id: Uuid,
     pub timestamp: OffsetDateTime,
     pub gate: String,
@@ -202,6 +782,7 @@ pub async fn add_event_gate_dep_set(
 }
 
 pub struct EventGateArrivalSet {
+    pub id: Uuid,
     pub flight_id: Uuid,
     pub timestamp: OffsetDateTime,
     pub gate: String,
@@ -236,3 +817,401 @@ pub async fn add_event_gate_arr_set(
 
     Ok(e)
 }
+
+pub struct EventRetracted {
+    pub id: Uuid,
+    pub flight_id: Uuid,
+    pub timestamp: OffsetDateTime,
+    pub event_id: Uuid,
+    pub reason: Option<String>,
+}
+
+pub async fn get_event_retracted(
+    ex: &mut PgConnection,
+    id: &[Uuid],
+) -> Result<Vec<EventRetracted>> {
+    let events = sqlx::query_as!(
+        EventRetracted,
+        "select flight_retractions.* from flight_retractions join unnest($1::uuid[]) as U(ids) on flight_id = ids",
+        id
+    ).fetch_all(ex).await?;
+
+    Ok(events)
+}
+
+pub async fn add_event_retracted(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    event_id: &Uuid,
+    reason: String,
+) -> Result<EventRetracted> {
+    let e = sqlx::query_as!(
+        EventRetracted,
+        "insert into flight_retractions (flight_id, event_id, reason) values ($1, $2, $3) returning *",
+        id,
+        event_id,
+        reason
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(e)
+}
+
+/// Ensure that `event_id` is a status event of flight `id` that has not been retracted yet.
+pub async fn ensure_event_retractable(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    event_id: &Uuid,
+) -> Result<()> {
+    sqlx::query!(
+        "select id from ( \
+            select id, flight_id from flight_cancellations \
+            union all select id, flight_id from flight_delays \
+            union all select id, flight_id from flight_departure_gates \
+            union all select id, flight_id from flight_arrival_gates \
+            union all select id, flight_id from flight_plane_changes \
+        ) as events \
+        where flight_id = $1 and id = $2 \
+        and id not in (select event_id from flight_retractions)",
+        id,
+        event_id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub struct EventPlaneChanged {
+    pub id: Uuid,
+    pub flight_id: Uuid,
+    pub timestamp: OffsetDateTime,
+    pub plane_id: Uuid,
+}
+
+pub async fn get_event_plane_changed(
+    ex: &mut PgConnection,
+    id: &[Uuid],
+) -> Result<Vec<EventPlaneChanged>> {
+    let events = sqlx::query_as!(
+        EventPlaneChanged,
+        "select flight_plane_changes.* from flight_plane_changes join unnest($1::uuid[]) as U(ids) on flight_id = ids",
+        id
+    ).fetch_all(ex).await?;
+
+    Ok(events)
+}
+
+pub async fn add_event_plane_changed(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    plane_id: &Uuid,
+) -> Result<EventPlaneChanged> {
+    let e = sqlx::query_as!(
+        EventPlaneChanged,
+        "insert into flight_plane_changes (flight_id, plane_id) values ($1, $2) returning *",
+        id,
+        plane_id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(e)
+}
+
+pub struct SeatInventory {
+    pub flight_id: Uuid,
+    pub class: String,
+    pub sold: i32,
+    pub overbooking_allowance: i32,
+}
+
+pub async fn get_seat_inventory(ex: &mut PgConnection, ids: &[Uuid]) -> Result<Vec<SeatInventory>> {
+    let inventory = sqlx::query_as!(
+        SeatInventory,
+        "select * from flight_seat_inventory where flight_id = any($1)",
+        ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(inventory)
+}
+
+pub async fn add_seats_sold(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    class: &str,
+    seats: i32,
+) -> Result<()> {
+    sqlx::query!(
+        "insert into flight_seat_inventory (flight_id, class, sold) values ($1, $2, $3) \
+        on conflict (flight_id, class) do update set sold = flight_seat_inventory.sold + excluded.sold",
+        id,
+        class,
+        seats
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_seats_sold(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    class: &str,
+    seats: i32,
+) -> Result<()> {
+    sqlx::query!(
+        "update flight_seat_inventory set sold = sold - $3 where flight_id = $1 and class = $2",
+        id,
+        class,
+        seats
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_overbooking_allowance(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    class: &str,
+    allowance: i32,
+) -> Result<()> {
+    sqlx::query!(
+        "insert into flight_seat_inventory (flight_id, class, overbooking_allowance) values ($1, $2, $3) \
+        on conflict (flight_id, class) do update set overbooking_allowance = excluded.overbooking_allowance",
+        id,
+        class,
+        allowance
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub struct CargoShipment {
+    pub flight_id: Uuid,
+    pub shipment_id: String,
+    pub weight_kg: i32,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+}
+
+pub async fn get_cargo_shipments(
+    ex: &mut PgConnection,
+    ids: &[Uuid],
+) -> Result<Vec<CargoShipment>> {
+    let shipments = sqlx::query_as!(
+        CargoShipment,
+        "select * from cargo_shipments where flight_id = any($1) order by shipment_id",
+        ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(shipments)
+}
+
+/// Add a shipment to a flight, returning false if the flight already carries it.
+pub async fn add_cargo_shipment(ex: &mut PgConnection, shipment: &CargoShipment) -> Result<bool> {
+    let res = sqlx::query!(
+        "insert into cargo_shipments (flight_id, shipment_id, weight_kg, origin_id, destination_id) \
+        values ($1, $2, $3, $4, $5) on conflict do nothing",
+        shipment.flight_id,
+        shipment.shipment_id,
+        shipment.weight_kg,
+        shipment.origin_id,
+        shipment.destination_id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn remove_cargo_shipment(
+    ex: &mut PgConnection,
+    flight_id: &Uuid,
+    shipment_id: &str,
+) -> Result<()> {
+    let res = sqlx::query!(
+        "delete from cargo_shipments where flight_id = $1 and shipment_id = $2",
+        flight_id,
+        shipment_id
+    )
+    .execute(ex)
+    .await?;
+
+    DatabaseError::ensure_single_affected(res)
+}
+
+pub struct FareBucket {
+    pub flight_id: Uuid,
+    pub booking_class: String,
+    pub cabin: String,
+    pub currency: String,
+    pub base_price: i64,
+    pub seats_allotted: i32,
+}
+
+pub async fn get_fare_buckets(ex: &mut PgConnection, ids: &[Uuid]) -> Result<Vec<FareBucket>> {
+    let buckets = sqlx::query_as!(
+        FareBucket,
+        "select * from fare_buckets where flight_id = any($1) order by base_price, booking_class",
+        ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(buckets)
+}
+
+/// Replace the fare buckets of a flight.
+pub async fn replace_fare_buckets(
+    ex: &mut PgConnection,
+    flight_id: &Uuid,
+    buckets: Vec<FareBucket>,
+) -> Result<()> {
+    sqlx::query!("delete from fare_buckets where flight_id = $1", flight_id)
+        .execute(&mut *ex)
+        .await?;
+
+    let mut booking_class = Vec::with_capacity(buckets.len());
+    let mut cabin = Vec::with_capacity(buckets.len());
+    let mut currency = Vec::with_capacity(buckets.len());
+    let mut base_price = Vec::with_capacity(buckets.len());
+    let mut seats_allotted = Vec::with_capacity(buckets.len());
+    for bucket in buckets {
+        booking_class.push(bucket.booking_class);
+        cabin.push(bucket.cabin);
+        currency.push(bucket.currency);
+        base_price.push(bucket.base_price);
+        seats_allotted.push(bucket.seats_allotted);
+    }
+    sqlx::query!(
+        "insert into fare_buckets (flight_id, booking_class, cabin, currency, base_price, seats_allotted) \
+        select $1, * from unnest($2::varchar[], $3::varchar[], $4::char(3)[], $5::bigint[], $6::int[])",
+        flight_id,
+        &booking_class,
+        &cabin,
+        &currency,
+        &base_price,
+        &seats_allotted
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Date of a point in time in the timezone of an airport.
+pub async fn get_local_date(
+    ex: &mut PgConnection,
+    airport_id: &Uuid,
+    time: &OffsetDateTime,
+) -> Result<Date> {
+    let date = sqlx::query_scalar!(
+        r#"select ($2::timestamptz at time zone timezone)::date as "date!" from airports where id = $1"#,
+        airport_id,
+        time
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(date)
+}
+
+/// Lock a flight number on a local departure date until the end of the transaction, as its
+/// uses as an operating and as a marketing number are checked together.
+pub async fn lock_flight_number(
+    ex: &mut PgConnection,
+    number: &FlightNumber,
+    departure_local_date: &Date,
+) -> Result<()> {
+    sqlx::query!(
+        "select from pg_advisory_xact_lock(hashtextextended($1::uuid || ' ' || $2 || ' ' || $3::date, 0))",
+        number.carrier_id,
+        number.flight_number,
+        departure_local_date
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Find the flight operating or marketing a flight number on a local departure date.
+///
+/// Cancelled flights keep their number for the date: tickets and schedules still refer to
+/// it, and a cancellation can be retracted.
+pub async fn get_flight_by_number(
+    ex: &mut PgConnection,
+    number: &FlightNumber,
+    departure_local_date: &Date,
+) -> Result<Flight> {
+    let flight = sqlx::query_as!(
+        Flight,
+        "select f.* from flights f \
+        where f.departure_local_date = $3 and ((f.carrier_id = $1 and f.flight_number = $2) \
+        or exists (select from flight_codeshares c \
+        where c.flight_id = f.id and c.carrier_id = $1 and c.flight_number = $2))",
+        number.carrier_id,
+        number.flight_number,
+        departure_local_date
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(flight)
+}
+
+pub async fn get_codeshares(ex: &mut PgConnection, ids: &[Uuid]) -> Result<Vec<Codeshare>> {
+    let codeshares = sqlx::query_as!(
+        Codeshare,
+        "select flight_id, carrier_id, flight_number from flight_codeshares \
+        where flight_id = any($1) order by flight_number",
+        ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(codeshares)
+}
+
+/// Replace the codeshares of a flight.
+pub async fn replace_codeshares(
+    ex: &mut PgConnection,
+    flight_id: &Uuid,
+    numbers: Vec<FlightNumber>,
+) -> Result<()> {
+    sqlx::query!(
+        "delete from flight_codeshares where flight_id = $1",
+        flight_id
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    let (carrier_id, flight_number): (Vec<_>, Vec<_>) = numbers
+        .into_iter()
+        .map(|n| (n.carrier_id, n.flight_number))
+        .unzip();
+    sqlx::query!(
+        "insert into flight_codeshares (flight_id, carrier_id, flight_number, departure_local_date) \
+        select f.id, c.carrier_id, c.flight_number, f.departure_local_date \
+        from flights f, unnest($2::uuid[], $3::varchar[]) as c(carrier_id, flight_number) \
+        where f.id = $1",
+        flight_id,
+        &carrier_id,
+        &flight_number
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use sqlx::types::Uuid;
+use time::{Duration, OffsetDateTime};
+
+use super::data::FlightData;
+
+/// Turnaround used when none is specified for the delay propagation.
+pub const DEFAULT_MIN_TURNAROUND: Duration = Duration::minutes(30);
+
+pub struct KnockOnDelay {
+    pub flight_id: Uuid,
+    pub departure_time: OffsetDateTime,
+    pub arrival_time: OffsetDateTime,
+}
+
+/// Compute the delays caused by a plane arriving at `arrival_time` on the flights it
+/// operates next, given in order of expected departure.
+///
+/// Each flight can depart at the earliest `min_turnaround` after the previous one arrived.
+/// The propagation stops at the first flight whose slack absorbs the delay.
+pub fn knock_on_delays<'a>(
+    arrival_time: OffsetDateTime,
+    min_turnaround: Duration,
+    next_flights: impl IntoIterator<Item = &'a FlightData>,
+) -> Vec<KnockOnDelay> {
+    let mut ready_time = arrival_time + min_turnaround;
+    let mut delays = Vec::new();
+
+    for flight in next_flights {
+        let departure_time = flight.expected_departure_time();
+        if departure_time >= ready_time {
+            break;
+        }
+
+        let shift = ready_time - departure_time;
+        let delay = KnockOnDelay {
+            flight_id: flight.flight.id,
+            departure_time: ready_time,
+            arrival_time: flight.expected_arrival_time() + shift,
+        };
+        ready_time = delay.arrival_time + min_turnaround;
+        delays.push(delay);
+    }
+
+    delays
+}
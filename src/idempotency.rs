@@ -0,0 +1,141 @@
+use prost::Message;
+use sqlx::PgConnection;
+use thiserror::Error;
+use tonic::Request;
+
+use crate::db::DatabaseError;
+
+/// Metadata key carrying the client supplied idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Number of hours a response is kept for replay.
+const RETENTION_HOURS: i32 = 24;
+
+/// Client supplied idempotency key along with the request it was sent with.
+pub struct IdempotencyKey {
+    key: String,
+    request: Vec<u8>,
+}
+
+pub fn get_key<T: Message>(
+    request: &Request<T>,
+) -> Result<Option<IdempotencyKey>, IdempotencyError> {
+    request
+        .metadata()
+        .get(IDEMPOTENCY_KEY)
+        .map(|v| {
+            v.to_str().map(|key| IdempotencyKey {
+                key: key.to_owned(),
+                request: request.get_ref().encode_to_vec(),
+            })
+        })
+        .transpose()
+        .map_err(|_| IdempotencyError::InvalidKey)
+}
+
+/// Look up the response stored for a previous request with the same key.
+///
+/// Fails if the key was used for a different request.
+pub async fn replay<M: Message + Default>(
+    ex: &mut PgConnection,
+    method: &str,
+    key: Option<&IdempotencyKey>,
+) -> Result<Option<M>, IdempotencyError> {
+    let Some(key) = key else {
+        return Ok(None);
+    };
+
+    let stored = sqlx::query!(
+        "select response, request_hash = sha256($4) as \"same_request!\" \
+        from idempotency_keys \
+        where method = $1 and key = $2 and created_at > now() - make_interval(hours => $3)",
+        method,
+        key.key,
+        RETENTION_HOURS,
+        key.request
+    )
+    .fetch_optional(&mut *ex)
+    .await
+    .map_err(DatabaseError::from)?;
+
+    let Some(stored) = stored else {
+        return Ok(None);
+    };
+    if !stored.same_request {
+        return Err(IdempotencyError::Mismatch);
+    }
+
+    Ok(Some(M::decode(stored.response.as_slice())?))
+}
+
+/// Store the response of a request so that retries with the same key can be replayed.
+///
+/// Must be called in the same transaction that performs the request.
+pub async fn record<M: Message>(
+    ex: &mut PgConnection,
+    method: &str,
+    key: Option<&IdempotencyKey>,
+    response: &M,
+) -> Result<(), IdempotencyError> {
+    let Some(key) = key else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "delete from idempotency_keys where created_at < now() - make_interval(hours => $1)",
+        RETENTION_HOURS
+    )
+    .execute(&mut *ex)
+    .await
+    .map_err(DatabaseError::from)?;
+
+    // a conflicting row means a concurrent request with the same key committed first
+    let res = sqlx::query!(
+        "insert into idempotency_keys (method, key, response, request_hash) \
+        values ($1, $2, $3, sha256($4)) \
+        on conflict (method, key) do nothing",
+        method,
+        key.key,
+        response.encode_to_vec(),
+        key.request
+    )
+    .execute(&mut *ex)
+    .await
+    .map_err(DatabaseError::from)?;
+
+    match res.rows_affected() {
+        1 => Ok(()),
+        _ => Err(IdempotencyError::Conflict),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum IdempotencyError {
+    #[error("invalid idempotency key")]
+    InvalidKey,
+    #[error("request with the same idempotency key is already in progress")]
+    Conflict,
+    #[error("idempotency key was already used for a different request")]
+    Mismatch,
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("could not decode stored response: {0}")]
+    Decode(#[from] prost::DecodeError),
+}
+
+impl From<IdempotencyError> for tonic::Status {
+    fn from(error: IdempotencyError) -> Self {
+        match error {
+            IdempotencyError::InvalidKey => {
+                tonic::Status::invalid_argument(format!("'{IDEMPOTENCY_KEY}'"))
+            }
+            IdempotencyError::Conflict => tonic::Status::aborted(error.to_string()),
+            IdempotencyError::Mismatch => tonic::Status::invalid_argument(error.to_string()),
+            IdempotencyError::Database(error) => error.into(),
+            IdempotencyError::Decode(_) => {
+                tracing::error!(%error, "internal error");
+                tonic::Status::internal("internal error")
+            }
+        }
+    }
+}
@@ -1,28 +1,63 @@
+// `tonic::Status` is large, but it is the error type of every handler and of the
+// helpers validating their requests, so boxing it would only add noise at each call.
+#![allow(clippy::result_large_err)]
+
+use std::sync::Arc;
+
 use db::Database;
 use rabbitmq::Rabbit;
 use sqlx::PgPool;
 use tonic::transport::server::Routes;
 
+use crate::aircraft_types::AircraftTypesApp;
+use crate::airlines::AirlinesApp;
 use crate::airports::AirportsApp;
 use crate::flights::FlightsApp;
 use crate::planes::PlanesApp;
+use crate::proto::flightmngr::aircraft_types_server::AircraftTypesServer;
+use crate::proto::flightmngr::airlines_server::AirlinesServer;
 use crate::proto::flightmngr::airports_server::AirportsServer;
 use crate::proto::flightmngr::flights_server::FlightsServer;
 use crate::proto::flightmngr::planes_server::PlanesServer;
+use crate::proto::flightmngr::routes_server::RoutesServer;
+use crate::proto::flightmngr::schedules_server::SchedulesServer;
+use crate::routes::RoutesApp;
+use crate::schedules::SchedulesApp;
 
+pub mod aircraft_types;
+pub mod airlines;
 pub mod airports;
+pub mod audit;
 mod datautils;
 pub mod db;
 pub mod flights;
+pub mod idempotency;
 pub mod planes;
 pub mod proto;
 pub mod rabbitmq;
+pub mod routes;
+pub mod schedules;
+pub mod ticketing;
 
 pub fn build_services(db_pool: PgPool, rabbitmq: Rabbit) -> Routes {
     let db = Database::from_pool(db_pool);
+    let rabbitmq = Arc::new(rabbitmq);
 
     Routes::default()
-        .add_service(PlanesServer::new(PlanesApp::new(db.clone())))
-        .add_service(AirportsServer::new(AirportsApp::new(db.clone())))
-        .add_service(FlightsServer::new(FlightsApp::new(db.clone(), rabbitmq)))
+        .add_service(PlanesServer::new(PlanesApp::new(
+            db.clone(),
+            rabbitmq.clone(),
+        )))
+        .add_service(AircraftTypesServer::new(AircraftTypesApp::new(db.clone())))
+        .add_service(AirlinesServer::new(AirlinesApp::new(db.clone())))
+        .add_service(AirportsServer::new(AirportsApp::new(
+            db.clone(),
+            rabbitmq.clone(),
+        )))
+        .add_service(FlightsServer::new(FlightsApp::new(
+            db.clone(),
+            rabbitmq.clone(),
+        )))
+        .add_service(RoutesServer::new(RoutesApp::new(db.clone())))
+        .add_service(SchedulesServer::new(SchedulesApp::new(db, rabbitmq)))
 }
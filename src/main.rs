@@ -26,6 +26,14 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("running migrations");
     flightmngr::db::MIGRATOR.run(&db_pool).await?;
 
+    if std::env::args().nth(1).as_deref() == Some("rebuild-current-state") {
+        tracing::info!("rebuilding flight current state");
+        let db = flightmngr::db::Database::from_pool(db_pool);
+        let count = flightmngr::flights::rebuild_current_state(&db).await?;
+        tracing::info!(count, "flight current state rebuilt");
+        return Ok(());
+    }
+
     // Create the rabbitmq channel
     tracing::info!("connecting to rabbitmq broker...");
     let rabbitmq = flightmngr::rabbitmq::Rabbit::new(
@@ -39,6 +47,16 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .await?;
     tracing::info!("successfully connected to rabbitmq broker and channel created...");
 
+    // consume ticketing events to track bookings
+    flightmngr::ticketing::TicketingConsumer::start(
+        flightmngr::db::Database::from_pool(db_pool.clone()),
+        &rabbitmq,
+        &opt.ticketing_exchange,
+        &opt.ticketing_queue,
+    )
+    .await?;
+    tracing::info!(exchange = %opt.ticketing_exchange, "consuming ticketing events");
+
     // build grpc services
     let services = flightmngr::build_services(db_pool, rabbitmq);
     // build reflection service
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+
+use super::queries;
+
+use crate::{
+    aircraft_types::{self, AircraftType},
+    db::DatabaseError,
+};
+
+type Result<T> = std::result::Result<T, DatabaseError>;
+
+impl PlaneData {
+    /// Cabin capacity of the plane, or the typical seats of its type.
+    pub fn cabin_capacity(&self) -> i32 {
+        self.plane
+            .cabin_capacity
+            .or(self.aircraft_type.as_ref().map(|t| t.typical_seats))
+            .unwrap_or_default()
+    }
+
+    /// ICAO designator of the type of the plane.
+    pub fn icao_type(&self) -> Option<String> {
+        self.aircraft_type
+            .as_ref()
+            .map(|t| t.icao_designator.clone())
+            .or_else(|| self.plane.icao_type.clone())
+    }
+
+    /// Cargo capacity of the plane, or the cargo capacity of its type.
+    pub fn cargo_capacity_kg(&self) -> i32 {
+        self.plane
+            .cargo_capacity_kg
+            .or(self.aircraft_type.as_ref().map(|t| t.cargo_capacity_kg))
+            .unwrap_or_default()
+    }
+}
+
+pub struct PlaneData {
+    pub plane: queries::Plane,
+    pub aircraft_type: Option<AircraftType>,
+    pub cabin_classes: Vec<queries::CabinClass>,
+    pub seats: Vec<queries::Seat>,
+}
+
+pub async fn load_planes_data(
+    ex: &mut PgConnection,
+    planes: Vec<queries::Plane>,
+) -> Result<impl Iterator<Item = PlaneData>> {
+    let type_ids = planes
+        .iter()
+        .filter_map(|p| p.type_id)
+        .unique()
+        .collect::<Vec<_>>();
+
+    let ids = planes.iter().map(|p| p.id).collect::<Vec<_>>();
+
+    let types = aircraft_types::get_aircraft_types(ex, &type_ids).await?;
+    let types: HashMap<_, _> = types.into_iter().map(|t| (t.id, t)).collect();
+
+    let classes = queries::get_cabin_classes(ex, &ids).await?;
+    let mut classes = classes.into_iter().into_group_map_by(|c| c.plane_id);
+
+    let seats = queries::get_seats(ex, &ids).await?;
+    let mut seats = seats.into_iter().into_group_map_by(|s| s.plane_id);
+
+    let planes = planes.into_iter().map(move |p| {
+        let aircraft_type = p.type_id.and_then(|id| types.get(&id)).cloned();
+        let classes = classes.remove(&p.id).unwrap_or_default();
+        let seats = seats.remove(&p.id).unwrap_or_default();
+        PlaneData {
+            plane: p,
+            aircraft_type,
+            cabin_classes: classes,
+            seats,
+        }
+    });
+
+    Ok(planes)
+}
+
+pub async fn list_planes(
+    ex: &mut PgConnection,
+    show_deleted: bool,
+) -> Result<impl Iterator<Item = PlaneData>> {
+    let planes = if show_deleted {
+        queries::list_planes_with_deleted(ex).await?
+    } else {
+        queries::list_planes(ex).await?
+    };
+
+    load_planes_data(ex, planes).await
+}
+
+pub async fn get_plane(ex: &mut PgConnection, id: &Uuid) -> Result<PlaneData> {
+    let plane = queries::get_plane(ex, id).await?;
+
+    single(load_planes_data(ex, vec![plane]).await?)
+}
+
+pub async fn get_plane_by_registration(
+    ex: &mut PgConnection,
+    registration: &str,
+) -> Result<PlaneData> {
+    let plane = queries::get_plane_by_registration(ex, registration).await?;
+
+    single(load_planes_data(ex, vec![plane]).await?)
+}
+
+pub async fn create_plane(
+    ex: &mut PgConnection,
+    model: String,
+    type_id: Option<&Uuid>,
+    cabin_capacity: Option<i32>,
+    cargo_capacity_kg: Option<i32>,
+    details: queries::PlaneDetails,
+) -> Result<PlaneData> {
+    let plane = queries::create_plane(
+        ex,
+        model,
+        type_id,
+        cabin_capacity,
+        cargo_capacity_kg,
+        details,
+    )
+    .await?;
+
+    single(load_planes_data(ex, vec![plane]).await?)
+}
+
+pub async fn restore_plane(ex: &mut PgConnection, id: &Uuid) -> Result<PlaneData> {
+    let plane = queries::restore_plane(ex, id).await?;
+
+    single(load_planes_data(ex, vec![plane]).await?)
+}
+
+fn single(mut planes: impl Iterator<Item = PlaneData>) -> Result<PlaneData> {
+    planes
+        .next()
+        .ok_or(DatabaseError::Unexpected("plane data not loaded"))
+}
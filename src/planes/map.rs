@@ -1,14 +1,274 @@
-use super::queries;
-use crate::proto;
+use std::collections::{HashMap, HashSet};
+
+use sqlx::types::Uuid;
+use tonic::Status;
+
+use super::{data::PlaneData, queries};
+use crate::{
+    datautils::{convert_date_to_timestamp, convert_odt_to_timestamp, parse_date},
+    proto::{
+        self,
+        flightmngr::{CabinClass, CabinClassCapacity, CabinConfiguration, UnavailabilityKind},
+    },
+};
+
+impl From<PlaneData> for proto::flightmngr::Plane {
+    fn from(data: PlaneData) -> Self {
+        let cabin_capacity = data.cabin_capacity() as u32;
+        let cargo_capacity_kg = data.cargo_capacity_kg() as u32;
+        let icao_type = data.icao_type();
+        let PlaneData {
+            plane,
+            aircraft_type,
+            cabin_classes: classes,
+            seats,
+        } = data;
+        let cabin_configuration = CabinConfiguration {
+            classes: cabin_classes(classes, cabin_capacity as i32),
+            seats: seats.into_iter().map(Into::into).collect(),
+        };
 
-impl From<queries::Plane> for proto::flightmngr::Plane {
-    fn from(plane: queries::Plane) -> Self {
         Self {
             id: plane.id.to_string(),
             model: plane.model,
-            cabin_capacity: plane.cabin_capacity as u32,
-            cargo_capacity_kg: plane.cargo_capacity_kg as u32,
+            cabin_capacity,
+            cargo_capacity_kg,
             deleted: plane.deleted,
+            registration: plane.registration.unwrap_or_default(),
+            icao_type: icao_type.unwrap_or_default(),
+            manufacturer: plane.manufacturer.unwrap_or_default(),
+            in_service_date: plane.in_service_date.map(convert_date_to_timestamp),
+            retirement_date: plane.retirement_date.map(convert_date_to_timestamp),
+            type_id: plane.type_id.map(|id| id.to_string()).unwrap_or_default(),
+            cabin_capacity_override: plane.type_id.and(plane.cabin_capacity).map(|c| c as u32),
+            cargo_capacity_kg_override: plane
+                .type_id
+                .and(plane.cargo_capacity_kg)
+                .map(|c| c as u32),
+            aircraft_type: aircraft_type.map(Into::into),
+            cabin_configuration: Some(cabin_configuration),
+        }
+    }
+}
+
+impl From<queries::Seat> for proto::flightmngr::Seat {
+    fn from(seat: queries::Seat) -> Self {
+        Self {
+            row: seat.row_number as u32,
+            letter: seat.letter,
+            class: cabin_class_from_str(&seat.class).into(),
+            exit_row: seat.exit_row,
+            blocked: seat.blocked,
+        }
+    }
+}
+
+/// Capacity of each cabin class, a single economy class with the whole cabin by default.
+pub fn cabin_classes(
+    classes: Vec<queries::CabinClass>,
+    cabin_capacity: i32,
+) -> Vec<CabinClassCapacity> {
+    if classes.is_empty() {
+        return vec![CabinClassCapacity {
+            class: CabinClass::Economy.into(),
+            capacity: cabin_capacity as u32,
+        }];
+    }
+
+    let mut classes: Vec<_> = classes
+        .into_iter()
+        .map(|c| CabinClassCapacity {
+            class: cabin_class_from_str(&c.class).into(),
+            capacity: c.capacity as u32,
+        })
+        .collect();
+    // from the front of the cabin
+    classes.sort_by_key(|c| std::cmp::Reverse(c.class));
+    classes
+}
+
+pub fn cabin_class_from_str(class: &str) -> CabinClass {
+    match class {
+        "first" => CabinClass::First,
+        "business" => CabinClass::Business,
+        "premium_economy" => CabinClass::PremiumEconomy,
+        _ => CabinClass::Economy,
+    }
+}
+
+pub fn parse_cabin_class(class: i32) -> Result<&'static str, Status> {
+    match CabinClass::try_from(class) {
+        Ok(CabinClass::Economy) => Ok("economy"),
+        Ok(CabinClass::PremiumEconomy) => Ok("premium_economy"),
+        Ok(CabinClass::Business) => Ok("business"),
+        Ok(CabinClass::First) => Ok("first"),
+        Err(_) => Err(Status::invalid_argument("'class'")),
+    }
+}
+
+/// Validate a cabin configuration against the cabin capacity of a plane.
+pub fn parse_cabin_configuration(
+    plane_id: Uuid,
+    configuration: CabinConfiguration,
+    cabin_capacity: i32,
+) -> Result<(Vec<queries::CabinClass>, Vec<queries::Seat>), Status> {
+    let mut classes = Vec::with_capacity(configuration.classes.len());
+    for CabinClassCapacity { class, capacity } in configuration.classes {
+        let class = parse_cabin_class(class)?;
+        if classes
+            .iter()
+            .any(|c: &queries::CabinClass| c.class == class)
+        {
+            return Err(Status::invalid_argument(format!(
+                "class {class} is configured more than once"
+            )));
+        }
+        let capacity = i32::try_from(capacity)
+            .ok()
+            .filter(|c| *c > 0)
+            .ok_or(Status::invalid_argument("'capacity'"))?;
+        classes.push(queries::CabinClass {
+            plane_id,
+            class: class.to_string(),
+            capacity,
+        });
+    }
+
+    let total: i32 = classes.iter().map(|c| c.capacity).sum();
+    if total != cabin_capacity {
+        return Err(Status::invalid_argument(format!(
+            "class capacities add up to {total} instead of the cabin capacity {cabin_capacity}"
+        )));
+    }
+
+    let mut positions = HashSet::new();
+    let mut available: HashMap<_, i32> = HashMap::new();
+    let mut seats = Vec::with_capacity(configuration.seats.len());
+    for seat in configuration.seats {
+        let class = parse_cabin_class(seat.class)?;
+        if !classes.iter().any(|c| c.class == class) {
+            return Err(Status::invalid_argument(format!(
+                "seat {}{} is in unconfigured class {class}",
+                seat.row, seat.letter
+            )));
+        }
+        let row_number = i32::try_from(seat.row)
+            .ok()
+            .filter(|r| *r > 0)
+            .ok_or(Status::invalid_argument("'row'"))?;
+        let letter = seat.letter.to_uppercase();
+        if letter.len() != 1 || !letter.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(Status::invalid_argument("'letter'"));
+        }
+        if !positions.insert((row_number, letter.clone())) {
+            return Err(Status::invalid_argument(format!(
+                "seat {row_number}{letter} is defined more than once"
+            )));
+        }
+        if !seat.blocked {
+            *available.entry(class).or_default() += 1;
+        }
+        seats.push(queries::Seat {
+            plane_id,
+            row_number,
+            letter,
+            class: class.to_string(),
+            exit_row: seat.exit_row,
+            blocked: seat.blocked,
+        });
+    }
+
+    // a seat map must provide exactly the capacity of each class
+    if !seats.is_empty() {
+        for class in &classes {
+            let count = available
+                .get(class.class.as_str())
+                .copied()
+                .unwrap_or_default();
+            if count != class.capacity {
+                return Err(Status::invalid_argument(format!(
+                    "seat map has {count} available seats in class {} instead of {}",
+                    class.class, class.capacity
+                )));
+            }
+        }
+    }
+
+    Ok((classes, seats))
+}
+
+impl From<queries::Unavailability> for proto::flightmngr::PlaneUnavailability {
+    fn from(unavailability: queries::Unavailability) -> Self {
+        let kind = match unavailability.kind.as_str() {
+            "maintenance" => UnavailabilityKind::Maintenance,
+            _ => UnavailabilityKind::Aog,
+        };
+
+        Self {
+            id: unavailability.id.to_string(),
+            plane_id: unavailability.plane_id.to_string(),
+            kind: kind.into(),
+            start_time: Some(convert_odt_to_timestamp(unavailability.start_time)),
+            end_time: unavailability.end_time.map(convert_odt_to_timestamp),
+            reason: unavailability.reason,
+            location_id: unavailability.location_id.map(|id| id.to_string()),
         }
     }
 }
+
+pub fn parse_unavailability_kind(kind: i32) -> Result<&'static str, Status> {
+    match UnavailabilityKind::try_from(kind) {
+        Ok(UnavailabilityKind::Aog) => Ok("aog"),
+        Ok(UnavailabilityKind::Maintenance) => Ok("maintenance"),
+        Err(_) => Err(Status::invalid_argument("'kind'")),
+    }
+}
+
+/// Validate the descriptive fields of a plane, normalizing codes to uppercase.
+pub fn parse_plane_details(
+    plane: &proto::flightmngr::Plane,
+) -> Result<queries::PlaneDetails, Status> {
+    let registration = plane.registration.trim().to_uppercase();
+    let valid_registration = registration.len() <= 10
+        && registration
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_registration {
+        return Err(Status::invalid_argument("'registration'"));
+    }
+
+    let icao_type = plane.icao_type.trim().to_uppercase();
+    let valid_icao_type = icao_type.is_empty()
+        || ((2..=4).contains(&icao_type.len())
+            && icao_type.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid_icao_type {
+        return Err(Status::invalid_argument("'icao_type'"));
+    }
+
+    let in_service_date = plane
+        .in_service_date
+        .clone()
+        .map(|d| parse_date(Some(d)))
+        .transpose()?;
+    let retirement_date = plane
+        .retirement_date
+        .clone()
+        .map(|d| parse_date(Some(d)))
+        .transpose()?;
+    if let (Some(in_service_date), Some(retirement_date)) = (in_service_date, retirement_date) {
+        if retirement_date < in_service_date {
+            return Err(Status::invalid_argument(
+                "'retirement_date' must not be before 'in_service_date'",
+            ));
+        }
+    }
+
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    Ok(queries::PlaneDetails {
+        registration: non_empty(registration),
+        icao_type: non_empty(icao_type),
+        manufacturer: non_empty(plane.manufacturer.trim().to_string()),
+        in_service_date,
+        retirement_date,
+    })
+}
@@ -1,19 +1,37 @@
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+use time::{Duration, OffsetDateTime};
 use tonic::{Request, Response, Status};
 
 use crate::{
-    datautils::parse_id,
-    db::Database,
+    aircraft_types, airports, audit,
+    datautils::{parse_id, parse_timestamp},
+    db::{Database, DatabaseError},
+    flights::{self, FlightResource},
+    idempotency,
     proto::flightmngr::{
-        planes_server::Planes, CreatePlaneRequest, DeletePlaneRequest, GetPlaneRequest,
-        ListPlanesRequest, ListPlanesResponse, Plane,
+        planes_server::Planes, CabinClassCapacity, CabinConfiguration, CreatePlaneRequest,
+        DeletePlaneRequest, GetPlaneByRegistrationRequest, GetPlaneRequest,
+        GetPlaneScheduleRequest, ListPlaneUnavailabilitiesRequest,
+        ListPlaneUnavailabilitiesResponse, ListPlanesRequest, ListPlanesResponse, Plane,
+        PlaneSchedule, PlaneUnavailability, RestorePlaneRequest, ReturnPlaneToServiceRequest,
+        ScheduleMaintenanceRequest, SetPlaneCabinConfigurationRequest, SetPlaneUnavailableRequest,
     },
+    rabbitmq::Rabbit,
 };
 
+mod data;
 mod map;
 mod queries;
 
+pub(crate) use map::{cabin_class_from_str, parse_cabin_class};
+pub(crate) use queries::Unavailability;
+
 pub struct PlanesApp {
     db: Database,
+    rabbitmq: Arc<Rabbit>,
 }
 
 #[tonic::async_trait]
@@ -25,13 +43,9 @@ impl Planes for PlanesApp {
         let ListPlanesRequest { show_deleted } = request.into_inner();
         let mut t = self.db.begin().await?;
 
-        let planes = if show_deleted {
-            queries::list_planes_with_deleted(t.get_conn()).await?
-        } else {
-            queries::list_planes(t.get_conn()).await?
-        };
+        let planes = data::list_planes(t.get_conn(), show_deleted).await?;
 
-        let planes = planes.into_iter().map(Into::into).collect();
+        let planes = planes.map(Into::into).collect();
         Ok(Response::new(ListPlanesResponse { planes }))
     }
 
@@ -43,7 +57,22 @@ impl Planes for PlanesApp {
         let id = parse_id(&id)?;
         let mut t = self.db.begin().await?;
 
-        let plane = queries::get_plane(t.get_conn(), &id).await?.into();
+        let plane = data::get_plane(t.get_conn(), &id).await?.into();
+
+        Ok(Response::new(plane))
+    }
+
+    async fn get_plane_by_registration(
+        &self,
+        request: Request<GetPlaneByRegistrationRequest>,
+    ) -> Result<Response<Plane>, Status> {
+        let GetPlaneByRegistrationRequest { registration } = request.into_inner();
+        let registration = registration.trim().to_uppercase();
+        let mut t = self.db.begin().await?;
+
+        let plane = data::get_plane_by_registration(t.get_conn(), &registration)
+            .await?
+            .into();
 
         Ok(Response::new(plane))
     }
@@ -52,23 +81,76 @@ impl Planes for PlanesApp {
         &self,
         request: Request<CreatePlaneRequest>,
     ) -> std::result::Result<Response<Plane>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let plane = request.into_inner().plane.unwrap_or_default();
+        let mut details = map::parse_plane_details(&plane)?;
         let Plane {
             model,
             cabin_capacity,
             cargo_capacity_kg,
+            type_id,
+            cabin_capacity_override,
+            cargo_capacity_kg_override,
             ..
-        } = request.into_inner().plane.unwrap_or_default();
+        } = plane;
+        let type_id = (!type_id.is_empty())
+            .then(|| parse_id(&type_id))
+            .transpose()?;
+        // without a type the capacities are mandatory
+        let (cabin_capacity, cargo_capacity_kg) = match type_id {
+            Some(_) => (cabin_capacity_override, cargo_capacity_kg_override),
+            None => (
+                Some(cabin_capacity_override.unwrap_or(cabin_capacity)),
+                Some(cargo_capacity_kg_override.unwrap_or(cargo_capacity_kg)),
+            ),
+        };
         let mut t = self.db.begin().await?;
 
-        let plane = queries::create_plane(
+        let key = idempotency_key.as_ref();
+        if let Some(plane) = idempotency::replay(t.get_conn(), "CreatePlane", key).await? {
+            return Ok(Response::new(plane));
+        }
+
+        if let Some(registration) = &details.registration {
+            match queries::get_plane_by_registration(t.get_conn(), registration).await {
+                Ok(_) => return Err(registration_taken()),
+                Err(DatabaseError::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if let Some(type_id) = &type_id {
+            let aircraft_type =
+                aircraft_types::ensure_aircraft_type_active(t.get_conn(), type_id).await?;
+            // the designator follows the type of the plane
+            if (details.icao_type.take()).is_some_and(|i| i != aircraft_type.icao_designator) {
+                return Err(Status::invalid_argument(
+                    "'icao_type' does not match the aircraft type",
+                ));
+            }
+        }
+
+        let plane = data::create_plane(
             t.get_conn(),
             model,
-            cabin_capacity as i32,
-            cargo_capacity_kg as i32,
+            type_id.as_ref(),
+            cabin_capacity.map(|c| c as i32),
+            cargo_capacity_kg.map(|c| c as i32),
+            details,
         )
-        .await?
+        .await
+        .map_err(|e| {
+            // a concurrent request registered the plane first
+            if e.is_unique_violation() {
+                registration_taken()
+            } else {
+                e.into()
+            }
+        })?
         .into();
 
+        idempotency::record(t.get_conn(), "CreatePlane", key, &plane).await?;
+
         t.commit().await?;
         Ok(Response::new(plane))
     }
@@ -77,19 +159,359 @@ impl Planes for PlanesApp {
         &self,
         request: Request<DeletePlaneRequest>,
     ) -> std::result::Result<Response<()>, Status> {
-        let DeletePlaneRequest { id } = request.into_inner();
+        let actor = audit::get_actor(&request)?;
+        let DeletePlaneRequest {
+            id,
+            cancel_future_flights,
+            cancellation_reason,
+        } = request.into_inner();
         let id = parse_id(&id)?;
+        let reason = cancel_future_flights.then_some(cancellation_reason);
+        if reason.as_ref().is_some_and(String::is_empty) {
+            return Err(Status::invalid_argument("'cancellation_reason'"));
+        }
         let mut t = self.db.begin().await?;
 
         queries::delete_plane(t.get_conn(), &id).await?;
+        let cancelled =
+            flights::cancel_future_flights(t.get_conn(), FlightResource::Plane(id), reason).await?;
+        audit::record(t.get_conn(), "plane", &id, "delete", actor.as_deref()).await?;
 
         t.commit().await?;
+
+        for flight in &cancelled {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
         Ok(Response::new(()))
     }
+
+    async fn restore_plane(
+        &self,
+        request: Request<RestorePlaneRequest>,
+    ) -> std::result::Result<Response<Plane>, Status> {
+        let actor = audit::get_actor(&request)?;
+        let RestorePlaneRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let plane = queries::lock_plane(t.get_conn(), &id).await?;
+        if !plane.deleted {
+            let plane = data::get_plane(t.get_conn(), &id).await?;
+            return Ok(Response::new(plane.into()));
+        }
+
+        let plane = data::restore_plane(t.get_conn(), &id)
+            .await
+            .map_err(|e| {
+                // another active plane carries the registration
+                if e.is_unique_violation() {
+                    registration_taken()
+                } else {
+                    e.into()
+                }
+            })?
+            .into();
+        audit::record(t.get_conn(), "plane", &id, "restore", actor.as_deref()).await?;
+
+        t.commit().await?;
+        Ok(Response::new(plane))
+    }
+
+    async fn list_plane_unavailabilities(
+        &self,
+        request: Request<ListPlaneUnavailabilitiesRequest>,
+    ) -> Result<Response<ListPlaneUnavailabilitiesResponse>, Status> {
+        let ListPlaneUnavailabilitiesRequest { plane_id } = request.into_inner();
+        let plane_id = parse_id(&plane_id)?;
+        let mut t = self.db.begin().await?;
+
+        let unavailabilities = queries::list_unavailabilities(t.get_conn(), &plane_id).await?;
+
+        let unavailabilities = unavailabilities.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListPlaneUnavailabilitiesResponse {
+            unavailabilities,
+        }))
+    }
+
+    async fn set_plane_unavailable(
+        &self,
+        request: Request<SetPlaneUnavailableRequest>,
+    ) -> std::result::Result<Response<PlaneUnavailability>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let PlaneUnavailability {
+            plane_id,
+            kind,
+            start_time,
+            end_time,
+            reason,
+            location_id,
+            ..
+        } = request.into_inner().unavailability.unwrap_or_default();
+        let plane_id = parse_id(&plane_id)?;
+        let location_id = location_id.as_deref().map(parse_id).transpose()?;
+        let kind = map::parse_unavailability_kind(kind)?;
+        let start_time = parse_timestamp(start_time)?;
+        let end_time = end_time.map(|t| parse_timestamp(Some(t))).transpose()?;
+        if end_time.is_some_and(|end_time| end_time <= start_time) {
+            return Err(Status::invalid_argument(
+                "'end_time' must be after 'start_time'",
+            ));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(u) = idempotency::replay(t.get_conn(), "SetPlaneUnavailable", key).await? {
+            return Ok(Response::new(u));
+        }
+
+        queries::get_plane(t.get_conn(), &plane_id).await?;
+        if let Some(location_id) = &location_id {
+            airports::ensure_airport_active(t.get_conn(), location_id).await?;
+        }
+        let unavailability = queries::create_unavailability(
+            t.get_conn(),
+            &plane_id,
+            kind,
+            &start_time,
+            end_time.as_ref(),
+            reason,
+            location_id.as_ref(),
+        )
+        .await?
+        .into();
+
+        idempotency::record(t.get_conn(), "SetPlaneUnavailable", key, &unavailability).await?;
+
+        t.commit().await?;
+        Ok(Response::new(unavailability))
+    }
+
+    async fn return_plane_to_service(
+        &self,
+        request: Request<ReturnPlaneToServiceRequest>,
+    ) -> std::result::Result<Response<ListPlaneUnavailabilitiesResponse>, Status> {
+        let ReturnPlaneToServiceRequest { plane_id, time } = request.into_inner();
+        let plane_id = parse_id(&plane_id)?;
+        let time = parse_timestamp(time)?;
+        let mut t = self.db.begin().await?;
+
+        let unavailabilities =
+            queries::end_unavailabilities(t.get_conn(), &plane_id, &time).await?;
+
+        t.commit().await?;
+
+        let unavailabilities = unavailabilities.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListPlaneUnavailabilitiesResponse {
+            unavailabilities,
+        }))
+    }
+
+    async fn schedule_maintenance(
+        &self,
+        request: Request<ScheduleMaintenanceRequest>,
+    ) -> std::result::Result<Response<PlaneUnavailability>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let ScheduleMaintenanceRequest {
+            plane_id,
+            start_time,
+            end_time,
+            location_id,
+            reason,
+        } = request.into_inner();
+        let plane_id = parse_id(&plane_id)?;
+        let location_id = parse_id(&location_id)?;
+        let start_time = parse_timestamp(start_time)?;
+        let end_time = parse_timestamp(end_time)?;
+        if end_time <= start_time {
+            return Err(Status::invalid_argument(
+                "'end_time' must be after 'start_time'",
+            ));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(u) = idempotency::replay(t.get_conn(), "ScheduleMaintenance", key).await? {
+            return Ok(Response::new(u));
+        }
+
+        queries::get_plane(t.get_conn(), &plane_id).await?;
+        airports::ensure_airport_active(t.get_conn(), &location_id).await?;
+        let unavailability = queries::create_unavailability(
+            t.get_conn(),
+            &plane_id,
+            "maintenance",
+            &start_time,
+            Some(&end_time),
+            reason,
+            Some(&location_id),
+        )
+        .await?
+        .into();
+
+        idempotency::record(t.get_conn(), "ScheduleMaintenance", key, &unavailability).await?;
+
+        t.commit().await?;
+        Ok(Response::new(unavailability))
+    }
+
+    async fn get_plane_schedule(
+        &self,
+        request: Request<GetPlaneScheduleRequest>,
+    ) -> std::result::Result<Response<PlaneSchedule>, Status> {
+        let GetPlaneScheduleRequest { plane_id, from, to } = request.into_inner();
+        let plane_id = parse_id(&plane_id)?;
+        let from = parse_timestamp(from)?;
+        let to = parse_timestamp(to)?;
+        if to <= from {
+            return Err(Status::invalid_argument("'to' must be after 'from'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        queries::get_plane(t.get_conn(), &plane_id).await?;
+        let flights = flights::get_plane_flights(t.get_conn(), plane_id, from, to).await?;
+        let unavailabilities =
+            queries::get_overlapping_unavailabilities(t.get_conn(), &plane_id, &from, &to).await?;
+
+        let unavailabilities = unavailabilities.into_iter().map(Into::into).collect();
+        Ok(Response::new(PlaneSchedule {
+            flights,
+            unavailabilities,
+        }))
+    }
+
+    async fn set_plane_cabin_configuration(
+        &self,
+        request: Request<SetPlaneCabinConfigurationRequest>,
+    ) -> std::result::Result<Response<Plane>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let SetPlaneCabinConfigurationRequest {
+            plane_id,
+            configuration,
+        } = request.into_inner();
+        let plane_id = parse_id(&plane_id)?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(plane) =
+            idempotency::replay(t.get_conn(), "SetPlaneCabinConfiguration", key).await?
+        {
+            return Ok(Response::new(plane));
+        }
+
+        queries::lock_plane(t.get_conn(), &plane_id).await?;
+        let plane = data::get_plane(t.get_conn(), &plane_id).await?;
+        let (classes, seats) = map::parse_cabin_configuration(
+            plane_id,
+            configuration.unwrap_or_default(),
+            plane.cabin_capacity(),
+        )?;
+        queries::replace_cabin_configuration(t.get_conn(), &plane_id, classes, seats).await?;
+        flights::ensure_future_seats_fit(t.get_conn(), plane_id).await?;
+        let plane = data::get_plane(t.get_conn(), &plane_id).await?.into();
+
+        idempotency::record(t.get_conn(), "SetPlaneCabinConfiguration", key, &plane).await?;
+        t.commit().await?;
+        Ok(Response::new(plane))
+    }
 }
 
 impl PlanesApp {
-    pub fn new(db: Database) -> Self {
-        Self { db }
+    pub fn new(db: Database, rabbitmq: Arc<Rabbit>) -> Self {
+        Self { db, rabbitmq }
+    }
+}
+
+pub(crate) async fn get_unavailability(
+    ex: &mut PgConnection,
+    id: &Uuid,
+) -> Result<Unavailability, DatabaseError> {
+    queries::get_unavailability(ex, id).await
+}
+
+/// Ensure that a plane is in service and available to be scheduled between two times.
+pub(crate) async fn ensure_plane_available(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<(), Status> {
+    let plane = queries::get_plane(ex, plane_id).await?;
+    if plane.deleted {
+        return Err(Status::failed_precondition("plane is deleted"));
+    }
+    if plane.in_service_date.is_some_and(|d| from.date() < d) {
+        return Err(Status::failed_precondition("plane is not in service yet"));
+    }
+    if plane.retirement_date.is_some_and(|d| to.date() >= d) {
+        return Err(Status::failed_precondition("plane is retired"));
     }
+
+    let unavailabilities =
+        queries::get_overlapping_unavailabilities(ex, plane_id, from, to).await?;
+    if let Some(u) = unavailabilities.first() {
+        return Err(Status::failed_precondition(format!(
+            "plane is unavailable ({}) from {}",
+            u.kind, u.start_time
+        )));
+    }
+
+    Ok(())
+}
+
+/// Get the minimum turnaround time of a plane, as defined by its aircraft type.
+pub(crate) async fn get_min_turnaround(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+) -> Result<Option<Duration>, DatabaseError> {
+    let plane = data::get_plane(ex, plane_id).await?;
+
+    Ok(plane
+        .aircraft_type
+        .map(|t| Duration::minutes(t.min_turnaround_minutes.into())))
+}
+
+/// Get the capacity of each cabin class of planes.
+pub(crate) async fn get_cabin_classes(
+    ex: &mut PgConnection,
+    plane_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<CabinClassCapacity>>, DatabaseError> {
+    let capacities = queries::get_cabin_capacities(ex, plane_ids).await?;
+    let classes = queries::get_cabin_classes(ex, plane_ids).await?;
+    let mut classes = classes.into_iter().into_group_map_by(|c| c.plane_id);
+
+    Ok(capacities
+        .into_iter()
+        .map(|(plane_id, cabin_capacity)| {
+            let classes = classes.remove(&plane_id).unwrap_or_default();
+            (plane_id, map::cabin_classes(classes, cabin_capacity))
+        })
+        .collect())
+}
+
+/// Get the cargo capacity of planes, in kilograms.
+pub(crate) async fn get_cargo_capacities(
+    ex: &mut PgConnection,
+    plane_ids: &[Uuid],
+) -> Result<HashMap<Uuid, i32>, DatabaseError> {
+    let capacities = queries::get_cargo_capacities(ex, plane_ids).await?;
+
+    Ok(capacities.into_iter().collect())
+}
+
+/// Get the cabin classes and the seat map of a plane.
+pub(crate) async fn get_cabin_configuration(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+) -> Result<CabinConfiguration, DatabaseError> {
+    let plane: Plane = data::get_plane(ex, plane_id).await?.into();
+
+    Ok(plane.cabin_configuration.unwrap_or_default())
+}
+
+fn registration_taken() -> Status {
+    Status::already_exists("registration is used by an active plane")
 }
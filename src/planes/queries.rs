@@ -1,4 +1,5 @@
 use sqlx::{types::Uuid, PgConnection};
+use time::{Date, OffsetDateTime};
 
 use crate::db::DatabaseError;
 
@@ -7,9 +8,23 @@ type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
 pub struct Plane {
     pub id: Uuid,
     pub model: String,
-    pub cabin_capacity: i32,
-    pub cargo_capacity_kg: i32,
+    pub cabin_capacity: Option<i32>,
+    pub cargo_capacity_kg: Option<i32>,
     pub deleted: bool,
+    pub registration: Option<String>,
+    pub icao_type: Option<String>,
+    pub manufacturer: Option<String>,
+    pub in_service_date: Option<Date>,
+    pub retirement_date: Option<Date>,
+    pub type_id: Option<Uuid>,
+}
+
+pub struct PlaneDetails {
+    pub registration: Option<String>,
+    pub icao_type: Option<String>,
+    pub manufacturer: Option<String>,
+    pub in_service_date: Option<Date>,
+    pub retirement_date: Option<Date>,
 }
 
 pub async fn list_planes(ex: &mut PgConnection) -> Result<Vec<Plane>> {
@@ -36,18 +51,48 @@ pub async fn get_plane(ex: &mut PgConnection, id: &Uuid) -> Result<Plane> {
     Ok(plane)
 }
 
+/// Lock the row of a plane until the end of the transaction and return it.
+pub async fn lock_plane(ex: &mut PgConnection, id: &Uuid) -> Result<Plane> {
+    let plane = sqlx::query_as!(Plane, "select * from planes where id = $1 for update", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(plane)
+}
+
+/// Get the active plane carrying a registration.
+pub async fn get_plane_by_registration(ex: &mut PgConnection, registration: &str) -> Result<Plane> {
+    let plane = sqlx::query_as!(
+        Plane,
+        "select * from planes where registration = $1 and not deleted",
+        registration
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(plane)
+}
+
 pub async fn create_plane(
     ex: &mut PgConnection,
     model: String,
-    cabin_cap: i32,
-    cargo_cap_kg: i32,
+    type_id: Option<&Uuid>,
+    cabin_cap: Option<i32>,
+    cargo_cap_kg: Option<i32>,
+    details: PlaneDetails,
 ) -> Result<Plane> {
     let plane = sqlx::query_as!(
         Plane,
-        "insert into planes (id, model, cabin_capacity, cargo_capacity_kg) values (gen_random_uuid(), $1, $2, $3) returning *",
+        "insert into planes (id, model, type_id, cabin_capacity, cargo_capacity_kg, registration, icao_type, manufacturer, in_service_date, retirement_date) values (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7, $8, $9) returning *",
         model,
+        type_id,
         cabin_cap,
-        cargo_cap_kg
+        cargo_cap_kg,
+        details.registration,
+        details.icao_type,
+        details.manufacturer,
+        details.in_service_date,
+        details.retirement_date
     )
     .fetch_one(ex)
     .await?;
@@ -62,3 +107,255 @@ pub async fn delete_plane(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
 
     DatabaseError::ensure_single_affected(res)
 }
+
+pub async fn restore_plane(ex: &mut PgConnection, id: &Uuid) -> Result<Plane> {
+    let plane = sqlx::query_as!(
+        Plane,
+        "update planes set deleted = false where id = $1 returning *",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(plane)
+}
+
+pub struct Unavailability {
+    pub id: Uuid,
+    pub plane_id: Uuid,
+    pub kind: String,
+    pub start_time: OffsetDateTime,
+    pub end_time: Option<OffsetDateTime>,
+    pub reason: String,
+    pub timestamp: OffsetDateTime,
+    pub location_id: Option<Uuid>,
+}
+
+pub async fn list_unavailabilities(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+) -> Result<Vec<Unavailability>> {
+    let unavailabilities = sqlx::query_as!(
+        Unavailability,
+        "select * from plane_unavailabilities where plane_id = $1 order by start_time",
+        plane_id
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(unavailabilities)
+}
+
+pub async fn get_unavailability(ex: &mut PgConnection, id: &Uuid) -> Result<Unavailability> {
+    let unavailability = sqlx::query_as!(
+        Unavailability,
+        "select * from plane_unavailabilities where id = $1",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(unavailability)
+}
+
+/// Get the unavailabilities of a plane overlapping a time window.
+pub async fn get_overlapping_unavailabilities(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+) -> Result<Vec<Unavailability>> {
+    let unavailabilities = sqlx::query_as!(
+        Unavailability,
+        "select * from plane_unavailabilities \
+        where plane_id = $1 and start_time < $3 and (end_time is null or end_time > $2) \
+        order by start_time",
+        plane_id,
+        from,
+        to
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(unavailabilities)
+}
+
+pub async fn create_unavailability(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    kind: &str,
+    start_time: &OffsetDateTime,
+    end_time: Option<&OffsetDateTime>,
+    reason: String,
+    location_id: Option<&Uuid>,
+) -> Result<Unavailability> {
+    let unavailability = sqlx::query_as!(
+        Unavailability,
+        "insert into plane_unavailabilities (id, plane_id, kind, start_time, end_time, reason, location_id) values (gen_random_uuid(), $1, $2, $3, $4, $5, $6) returning *",
+        plane_id,
+        kind,
+        start_time,
+        end_time,
+        reason,
+        location_id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(unavailability)
+}
+
+/// End at `time` the unavailabilities of a plane that are ongoing at that time.
+pub async fn end_unavailabilities(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    time: &OffsetDateTime,
+) -> Result<Vec<Unavailability>> {
+    let unavailabilities = sqlx::query_as!(
+        Unavailability,
+        "update plane_unavailabilities set end_time = $2 \
+        where plane_id = $1 and start_time <= $2 and (end_time is null or end_time > $2) \
+        returning *",
+        plane_id,
+        time
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(unavailabilities)
+}
+
+pub struct CabinClass {
+    pub plane_id: Uuid,
+    pub class: String,
+    pub capacity: i32,
+}
+
+pub struct Seat {
+    pub plane_id: Uuid,
+    pub row_number: i32,
+    pub letter: String,
+    pub class: String,
+    pub exit_row: bool,
+    pub blocked: bool,
+}
+
+pub async fn get_cabin_classes(
+    ex: &mut PgConnection,
+    plane_ids: &[Uuid],
+) -> Result<Vec<CabinClass>> {
+    let classes = sqlx::query_as!(
+        CabinClass,
+        "select * from plane_cabin_classes where plane_id = any($1)",
+        plane_ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(classes)
+}
+
+pub async fn get_seats(ex: &mut PgConnection, plane_ids: &[Uuid]) -> Result<Vec<Seat>> {
+    let seats = sqlx::query_as!(
+        Seat,
+        "select * from plane_seats where plane_id = any($1) order by row_number, letter",
+        plane_ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(seats)
+}
+
+/// Get the resolved cabin capacity of planes.
+pub async fn get_cabin_capacities(
+    ex: &mut PgConnection,
+    plane_ids: &[Uuid],
+) -> Result<Vec<(Uuid, i32)>> {
+    let capacities = sqlx::query!(
+        r#"select plane_id as "plane_id!", cabin_capacity as "cabin_capacity!" from plane_capacities where plane_id = any($1)"#,
+        plane_ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(capacities
+        .into_iter()
+        .map(|c| (c.plane_id, c.cabin_capacity))
+        .collect())
+}
+
+/// Get the resolved cargo capacity of planes.
+pub async fn get_cargo_capacities(
+    ex: &mut PgConnection,
+    plane_ids: &[Uuid],
+) -> Result<Vec<(Uuid, i32)>> {
+    let capacities = sqlx::query!(
+        r#"select plane_id as "plane_id!", cargo_capacity_kg as "cargo_capacity_kg!" from plane_capacities where plane_id = any($1)"#,
+        plane_ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(capacities
+        .into_iter()
+        .map(|c| (c.plane_id, c.cargo_capacity_kg))
+        .collect())
+}
+
+/// Replace the cabin classes and the seat map of a plane.
+pub async fn replace_cabin_configuration(
+    ex: &mut PgConnection,
+    plane_id: &Uuid,
+    classes: Vec<CabinClass>,
+    seats: Vec<Seat>,
+) -> Result<()> {
+    sqlx::query!("delete from plane_seats where plane_id = $1", plane_id)
+        .execute(&mut *ex)
+        .await?;
+    sqlx::query!(
+        "delete from plane_cabin_classes where plane_id = $1",
+        plane_id
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    let (class, capacity): (Vec<_>, Vec<_>) =
+        classes.into_iter().map(|c| (c.class, c.capacity)).unzip();
+    sqlx::query!(
+        "insert into plane_cabin_classes (plane_id, class, capacity) \
+        select $1, * from unnest($2::varchar[], $3::int[])",
+        plane_id,
+        &class,
+        &capacity
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    let mut row_number = Vec::with_capacity(seats.len());
+    let mut letter = Vec::with_capacity(seats.len());
+    let mut class = Vec::with_capacity(seats.len());
+    let mut exit_row = Vec::with_capacity(seats.len());
+    let mut blocked = Vec::with_capacity(seats.len());
+    for seat in seats {
+        row_number.push(seat.row_number);
+        letter.push(seat.letter);
+        class.push(seat.class);
+        exit_row.push(seat.exit_row);
+        blocked.push(seat.blocked);
+    }
+    sqlx::query!(
+        "insert into plane_seats (plane_id, row_number, letter, class, exit_row, blocked) \
+        select $1, * from unnest($2::int[], $3::varchar[], $4::varchar[], $5::bool[], $6::bool[])",
+        plane_id,
+        &row_number,
+        &letter,
+        &class,
+        &exit_row,
+        &blocked
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    Ok(())
+}
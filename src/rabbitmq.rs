@@ -12,7 +12,7 @@ use prost::Message;
 use thiserror::Error;
 
 pub struct Rabbit {
-    _connection: Connection,
+    connection: Connection,
     channel: Channel,
     exchange_name: String,
 }
@@ -52,7 +52,7 @@ impl Rabbit {
         rabbitmq_channel
             .exchange_declare(ExchangeDeclareArguments {
                 exchange: exchange_name.clone(),
-                exchange_type: String::from(exchange_type),
+                exchange_type,
                 passive: false, // if does not exist, then is created. If set to true, an error is raised if exchange does not exist
                 durable: true,  // survive broker restart
                 auto_delete: false, // survive even if no queue is bound
@@ -63,13 +63,26 @@ impl Rabbit {
             .await?;
 
         Ok(Rabbit {
-            _connection: rabbitmq,
+            connection: rabbitmq,
             channel: rabbitmq_channel,
             exchange_name,
         })
     }
 
+    /// Open another channel on the connection, e.g. to consume messages.
+    pub async fn open_channel(&self) -> Result<Channel, amqprs::error::Error> {
+        let channel = self.connection.open_channel(None).await?;
+        channel.register_callback(DefaultChannelCallback).await?;
+
+        Ok(channel)
+    }
+
+    /// Drafts are not announced until they are published.
     pub async fn notify_flight_update(&self, message: &Flight) -> Result<(), NotifyError> {
+        if message.draft {
+            return Ok(());
+        }
+
         let message = message.encode_to_vec();
 
         let args = BasicPublishArguments::new(&self.exchange_name, "");
@@ -95,11 +108,7 @@ pub enum NotifyError {
 
 impl From<NotifyError> for tonic::Status {
     fn from(error: NotifyError) -> Self {
-        match error {
-            _ => {
-                tracing::error!(%error, "internal error");
-                tonic::Status::internal("internal error")
-            }
-        }
+        tracing::error!(%error, "internal error");
+        tonic::Status::internal("internal error")
     }
 }
@@ -0,0 +1,52 @@
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+
+use super::queries;
+
+use crate::db::DatabaseError;
+
+type Result<T> = std::result::Result<T, DatabaseError>;
+
+pub struct RouteData(pub queries::Route, pub Vec<queries::BlockTime>);
+
+pub async fn load_routes_data(
+    ex: &mut PgConnection,
+    routes: Vec<queries::Route>,
+) -> Result<impl Iterator<Item = RouteData>> {
+    let ids = routes.iter().map(|r| r.id).collect::<Vec<_>>();
+
+    let block_times = queries::get_block_times(ex, &ids).await?;
+    let mut block_times = block_times.into_iter().into_group_map_by(|b| b.route_id);
+
+    let routes = routes.into_iter().map(move |r| {
+        let block_times = block_times.remove(&r.id).unwrap_or_default();
+        RouteData(r, block_times)
+    });
+
+    Ok(routes)
+}
+
+pub async fn list_routes(
+    ex: &mut PgConnection,
+    show_deleted: bool,
+) -> Result<impl Iterator<Item = RouteData>> {
+    let routes = if show_deleted {
+        queries::list_routes_with_deleted(ex).await?
+    } else {
+        queries::list_routes(ex).await?
+    };
+
+    load_routes_data(ex, routes).await
+}
+
+pub async fn get_route(ex: &mut PgConnection, id: &Uuid) -> Result<RouteData> {
+    let route = queries::get_route(ex, id).await?;
+
+    single(load_routes_data(ex, vec![route]).await?)
+}
+
+fn single(mut routes: impl Iterator<Item = RouteData>) -> Result<RouteData> {
+    routes
+        .next()
+        .ok_or(DatabaseError::Unexpected("route data not loaded"))
+}
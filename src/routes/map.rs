@@ -0,0 +1,24 @@
+use super::{data::RouteData, queries};
+use crate::proto;
+
+impl From<queries::BlockTime> for proto::flightmngr::BlockTime {
+    fn from(block_time: queries::BlockTime) -> Self {
+        Self {
+            aircraft_type_id: block_time.aircraft_type_id.to_string(),
+            minutes: block_time.block_minutes as u32,
+        }
+    }
+}
+
+impl From<RouteData> for proto::flightmngr::Route {
+    fn from(RouteData(route, block_times): RouteData) -> Self {
+        Self {
+            id: route.id.to_string(),
+            origin_id: route.origin_id.to_string(),
+            destination_id: route.destination_id.to_string(),
+            distance_km: route.distance_km as u32,
+            block_times: block_times.into_iter().map(Into::into).collect(),
+            deleted: route.deleted,
+        }
+    }
+}
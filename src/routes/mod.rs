@@ -0,0 +1,258 @@
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+use time::Duration;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    aircraft_types, airports,
+    datautils::{parse_id, parse_timestamp},
+    db::{Database, DatabaseError},
+    flights, idempotency,
+    proto::flightmngr::{
+        self, routes_server::Routes, CreateRouteRequest, DeleteRouteRequest, Flight,
+        GetRouteRequest, ListFlightsResponse, ListRoutesRequest, ListRoutesResponse,
+        RouteFlightsRequest, RoutePerformance, SetRouteBlockTimesRequest,
+    },
+};
+
+mod data;
+mod map;
+mod queries;
+
+/// Mean radius of the Earth used for great-circle distances.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Delay under which a departure still counts as on time.
+const ON_TIME_THRESHOLD: Duration = Duration::minutes(15);
+
+pub struct RoutesApp {
+    db: Database,
+}
+
+#[tonic::async_trait]
+impl Routes for RoutesApp {
+    async fn list_routes(
+        &self,
+        request: Request<ListRoutesRequest>,
+    ) -> Result<Response<ListRoutesResponse>, Status> {
+        let ListRoutesRequest { show_deleted } = request.into_inner();
+        let mut t = self.db.begin().await?;
+
+        let routes = data::list_routes(t.get_conn(), show_deleted).await?;
+
+        let routes = routes.map(Into::into).collect();
+        Ok(Response::new(ListRoutesResponse { routes }))
+    }
+
+    async fn get_route(
+        &self,
+        request: Request<GetRouteRequest>,
+    ) -> Result<Response<flightmngr::Route>, Status> {
+        let GetRouteRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let route = data::get_route(t.get_conn(), &id).await?.into();
+
+        Ok(Response::new(route))
+    }
+
+    async fn create_route(
+        &self,
+        request: Request<CreateRouteRequest>,
+    ) -> Result<Response<flightmngr::Route>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let flightmngr::Route {
+            origin_id,
+            destination_id,
+            ..
+        } = request.into_inner().route.unwrap_or_default();
+        let origin_id = parse_id(&origin_id)?;
+        let destination_id = parse_id(&destination_id)?;
+        if origin_id == destination_id {
+            return Err(Status::invalid_argument("'destination_id'"));
+        }
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(r) = idempotency::replay(t.get_conn(), "CreateRoute", key).await? {
+            return Ok(Response::new(r));
+        }
+
+        airports::ensure_airport_active(t.get_conn(), &origin_id).await?;
+        airports::ensure_airport_active(t.get_conn(), &destination_id).await?;
+        if let Some(other) =
+            queries::get_route_between(t.get_conn(), &origin_id, &destination_id).await?
+        {
+            return Err(Status::already_exists(format!(
+                "airports already connected by route {}",
+                other.id
+            )));
+        }
+        let origin = airports::get_coordinates(t.get_conn(), &origin_id).await?;
+        let destination = airports::get_coordinates(t.get_conn(), &destination_id).await?;
+        let (Some(origin), Some(destination)) = (origin, destination) else {
+            return Err(Status::failed_precondition("airport has no coordinates"));
+        };
+        let distance_km = great_circle_km(origin, destination).round() as i32;
+
+        let route =
+            queries::create_route(t.get_conn(), &origin_id, &destination_id, distance_km).await?;
+        let route = data::get_route(t.get_conn(), &route.id).await?.into();
+
+        idempotency::record(t.get_conn(), "CreateRoute", key, &route).await?;
+
+        t.commit().await?;
+        Ok(Response::new(route))
+    }
+
+    async fn delete_route(
+        &self,
+        request: Request<DeleteRouteRequest>,
+    ) -> Result<Response<()>, Status> {
+        let DeleteRouteRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        queries::delete_route(t.get_conn(), &id).await?;
+
+        t.commit().await?;
+        Ok(Response::new(()))
+    }
+
+    async fn set_route_block_times(
+        &self,
+        request: Request<SetRouteBlockTimesRequest>,
+    ) -> Result<Response<flightmngr::Route>, Status> {
+        let SetRouteBlockTimesRequest {
+            route_id,
+            block_times,
+        } = request.into_inner();
+        let route_id = parse_id(&route_id)?;
+        let block_times = block_times
+            .into_iter()
+            .map(|b| match b.minutes {
+                0 => Err(Status::invalid_argument("'minutes'")),
+                minutes => Ok((parse_id(&b.aircraft_type_id)?, minutes as i32)),
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        if !block_times.iter().map(|(id, _)| id).all_unique() {
+            return Err(Status::invalid_argument("'block_times'"));
+        }
+        let mut t = self.db.begin().await?;
+
+        let route = queries::lock_route(t.get_conn(), &route_id).await?;
+        if route.deleted {
+            return Err(Status::failed_precondition("route is deleted"));
+        }
+        for (aircraft_type_id, _) in &block_times {
+            aircraft_types::ensure_aircraft_type_active(t.get_conn(), aircraft_type_id).await?;
+        }
+
+        queries::replace_block_times(t.get_conn(), &route_id, block_times).await?;
+        let route = data::get_route(t.get_conn(), &route_id).await?.into();
+
+        t.commit().await?;
+        Ok(Response::new(route))
+    }
+
+    async fn list_route_flights(
+        &self,
+        request: Request<RouteFlightsRequest>,
+    ) -> Result<Response<ListFlightsResponse>, Status> {
+        let mut t = self.db.begin().await?;
+
+        let (_, flights) = get_route_flights(t.get_conn(), request.into_inner()).await?;
+
+        Ok(Response::new(ListFlightsResponse { flights }))
+    }
+
+    async fn get_route_performance(
+        &self,
+        request: Request<RouteFlightsRequest>,
+    ) -> Result<Response<RoutePerformance>, Status> {
+        let mut t = self.db.begin().await?;
+
+        let (route, flights) = get_route_flights(t.get_conn(), request.into_inner()).await?;
+
+        Ok(Response::new(performance(&route, &flights)))
+    }
+}
+
+impl RoutesApp {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+async fn get_route_flights(
+    ex: &mut PgConnection,
+    request: RouteFlightsRequest,
+) -> Result<(queries::Route, Vec<Flight>), Status> {
+    let RouteFlightsRequest { route_id, from, to } = request;
+    let route_id = parse_id(&route_id)?;
+    let from = parse_timestamp(from)?;
+    let to = parse_timestamp(to)?;
+    if to < from {
+        return Err(Status::invalid_argument("'to'"));
+    }
+
+    let route = queries::get_route(ex, &route_id).await?;
+    let flights =
+        flights::get_flights_between(ex, &route.origin_id, &route.destination_id, &from, &to)
+            .await?;
+
+    Ok((route, flights))
+}
+
+/// Great-circle distance between two points given as latitude and longitude in degrees.
+fn great_circle_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let half_dlat = (lat2 - lat1) / 2.0;
+    let half_dlon = (lon2 - lon1).to_radians() / 2.0;
+
+    let a = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlon.sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Summarize the punctuality and load of the flights of a route.
+fn performance(route: &queries::Route, flights: &[Flight]) -> RoutePerformance {
+    let operated = flights.iter().filter(|f| !f.is_cancelled).collect_vec();
+    let delays = operated
+        .iter()
+        .map(|f| {
+            let scheduled = f.departure_time.clone().unwrap_or_default();
+            let expected = f
+                .expected_departure_time
+                .clone()
+                .unwrap_or(scheduled.clone());
+            Duration::seconds((expected.seconds - scheduled.seconds).max(0))
+        })
+        .collect_vec();
+    let average = |total: f64| match operated.len() {
+        0 => 0.0,
+        n => total / n as f64,
+    };
+
+    RoutePerformance {
+        route_id: route.id.to_string(),
+        flights: flights.len() as u32,
+        cancelled: (flights.len() - operated.len()) as u32,
+        on_time: delays.iter().filter(|d| **d < ON_TIME_THRESHOLD).count() as u32,
+        average_delay_minutes: average(delays.iter().map(|d| d.as_seconds_f64() / 60.0).sum()),
+        booked_seats: operated.iter().map(|f| f.booked_seats as u64).sum(),
+        average_load_factor: average(operated.iter().map(|f| f.load_factor).sum()),
+    }
+}
+
+/// Get the block time of the aircraft type of a plane on the route between two airports.
+pub(crate) async fn get_block_time(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    plane_id: &Uuid,
+) -> Result<Option<Duration>, DatabaseError> {
+    let minutes = queries::get_plane_block_minutes(ex, origin_id, destination_id, plane_id).await?;
+
+    Ok(minutes.map(|m| Duration::minutes(m.into())))
+}
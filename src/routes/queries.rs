@@ -0,0 +1,155 @@
+use sqlx::{types::Uuid, PgConnection};
+
+use crate::db::DatabaseError;
+
+type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+
+pub struct Route {
+    pub id: Uuid,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub distance_km: i32,
+    pub deleted: bool,
+}
+
+pub struct BlockTime {
+    pub route_id: Uuid,
+    pub aircraft_type_id: Uuid,
+    pub block_minutes: i32,
+}
+
+pub async fn list_routes(ex: &mut PgConnection) -> Result<Vec<Route>> {
+    let routes = sqlx::query_as!(Route, "select * from routes where not deleted")
+        .fetch_all(ex)
+        .await?;
+
+    Ok(routes)
+}
+
+pub async fn list_routes_with_deleted(ex: &mut PgConnection) -> Result<Vec<Route>> {
+    let routes = sqlx::query_as!(Route, "select * from routes")
+        .fetch_all(ex)
+        .await?;
+
+    Ok(routes)
+}
+
+pub async fn get_route(ex: &mut PgConnection, id: &Uuid) -> Result<Route> {
+    let route = sqlx::query_as!(Route, "select * from routes where id = $1", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(route)
+}
+
+pub async fn lock_route(ex: &mut PgConnection, id: &Uuid) -> Result<Route> {
+    let route = sqlx::query_as!(Route, "select * from routes where id = $1 for update", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(route)
+}
+
+pub async fn get_route_between(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+) -> Result<Option<Route>> {
+    let route = sqlx::query_as!(
+        Route,
+        "select * from routes where not deleted and origin_id = $1 and destination_id = $2",
+        origin_id,
+        destination_id
+    )
+    .fetch_optional(ex)
+    .await?;
+
+    Ok(route)
+}
+
+pub async fn create_route(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    distance_km: i32,
+) -> Result<Route> {
+    let route = sqlx::query_as!(
+        Route,
+        "insert into routes (id, origin_id, destination_id, distance_km) values (gen_random_uuid(), $1, $2, $3) returning *",
+        origin_id,
+        destination_id,
+        distance_km
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(route)
+}
+
+pub async fn delete_route(ex: &mut PgConnection, id: &Uuid) -> Result<()> {
+    let res = sqlx::query!("update routes set deleted = true where id = $1", id)
+        .execute(ex)
+        .await?;
+
+    DatabaseError::ensure_single_affected(res)
+}
+
+pub async fn get_block_times(ex: &mut PgConnection, route_ids: &[Uuid]) -> Result<Vec<BlockTime>> {
+    let block_times = sqlx::query_as!(
+        BlockTime,
+        "select * from route_block_times where route_id = any($1) order by block_minutes",
+        route_ids
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(block_times)
+}
+
+pub async fn replace_block_times(
+    ex: &mut PgConnection,
+    route_id: &Uuid,
+    block_times: Vec<(Uuid, i32)>,
+) -> Result<()> {
+    sqlx::query!(
+        "delete from route_block_times where route_id = $1",
+        route_id
+    )
+    .execute(&mut *ex)
+    .await?;
+
+    let (aircraft_type_id, block_minutes): (Vec<_>, Vec<_>) = block_times.into_iter().unzip();
+    sqlx::query!(
+        "insert into route_block_times (route_id, aircraft_type_id, block_minutes) \
+        select $1, * from unnest($2::uuid[], $3::int[])",
+        route_id,
+        &aircraft_type_id,
+        &block_minutes
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the block time of the aircraft type of a plane on the route between two airports.
+pub async fn get_plane_block_minutes(
+    ex: &mut PgConnection,
+    origin_id: &Uuid,
+    destination_id: &Uuid,
+    plane_id: &Uuid,
+) -> Result<Option<i32>> {
+    let block_minutes = sqlx::query_scalar!(
+        "select b.block_minutes from routes r \
+        join route_block_times b on b.route_id = r.id \
+        join planes p on p.type_id = b.aircraft_type_id \
+        where not r.deleted and r.origin_id = $1 and r.destination_id = $2 and p.id = $3",
+        origin_id,
+        destination_id,
+        plane_id
+    )
+    .fetch_optional(ex)
+    .await?;
+
+    Ok(block_minutes)
+}
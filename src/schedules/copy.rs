@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use sqlx::{types::Uuid, PgConnection};
+use time::{Date, OffsetDateTime};
+
+use super::{
+    generator,
+    queries::{self, Schedule, ScheduleSpec},
+    seasons::Season,
+};
+use crate::{airports, db::DatabaseError, flights};
+
+type Result<T> = std::result::Result<T, DatabaseError>;
+
+pub enum ConflictKind {
+    PlaneOverlap,
+    AirportClosed,
+}
+
+/// Reason a copied schedule cannot fly on a date as planned.
+pub struct Conflict {
+    pub schedule_id: Uuid,
+    pub source_schedule_id: Uuid,
+    pub date: Date,
+    pub kind: ConflictKind,
+    pub description: String,
+}
+
+/// Schedule copied from another season.
+pub struct CopiedSchedule {
+    pub source_id: Uuid,
+    pub schedule: Schedule,
+}
+
+/// A scheduled flight of a copy.
+struct Instance<'a> {
+    copy: &'a CopiedSchedule,
+    date: Date,
+    departure_time: OffsetDateTime,
+    arrival_time: OffsetDateTime,
+}
+
+/// Copy the schedules valid during a season to another season.
+///
+/// Dates are shifted by the whole number of weeks between the season starts, so flights keep
+/// their days of the week, and clipped to the target season.
+pub async fn copy_season(
+    ex: &mut PgConnection,
+    from: Season,
+    to: Season,
+) -> Result<Vec<CopiedSchedule>> {
+    let shift = to.start() - from.start();
+    let schedules = queries::list_schedules_during(ex, &from.start(), &from.end()).await?;
+
+    let mut copies = Vec::new();
+    for schedule in schedules {
+        let valid_from = schedule.valid_from.max(from.start()) + shift;
+        let valid_to = (schedule.valid_to.min(from.end()) + shift).min(to.end());
+        if valid_from > valid_to {
+            continue;
+        }
+
+        let spec = ScheduleSpec {
+            carrier_id: schedule.carrier_id,
+            flight_number: schedule.flight_number,
+            origin_id: schedule.origin_id,
+            destination_id: schedule.destination_id,
+            plane_id: schedule.plane_id,
+            aircraft_type_id: schedule.aircraft_type_id,
+            departure_local_time: schedule.departure_local_time,
+            arrival_local_time: schedule.arrival_local_time,
+            arrival_day_offset: schedule.arrival_day_offset,
+            days_of_week: schedule.days_of_week,
+            valid_from,
+            valid_to,
+            exceptions: schedule
+                .exceptions
+                .into_iter()
+                .map(|d| d + shift)
+                .filter(|d| (valid_from..=valid_to).contains(d))
+                .collect(),
+        };
+        let copy = queries::create_schedule(ex, spec).await?;
+        queries::add_schedule_copy(ex, &copy.id, &schedule.id).await?;
+        copies.push(CopiedSchedule {
+            source_id: schedule.id,
+            schedule: copy,
+        });
+    }
+
+    Ok(copies)
+}
+
+/// Whether the schedules of a season were already copied to another season, and some copies
+/// were not deleted since.
+pub async fn is_copied(ex: &mut PgConnection, from: Season, to: Season) -> Result<bool> {
+    queries::has_copies(ex, &from.start(), &from.end(), &to.start(), &to.end()).await
+}
+
+/// Find the scheduled flights of the copies that would use a plane already flying, or an
+/// airport that is closed.
+pub async fn find_conflicts(
+    ex: &mut PgConnection,
+    copies: &[CopiedSchedule],
+    season: Season,
+) -> Result<Vec<Conflict>> {
+    let mut instances = Vec::new();
+    for copy in copies {
+        let dates =
+            generator::scheduled_dates(&copy.schedule, copy.schedule.valid_from, season.end());
+        let times = queries::get_scheduled_times(ex, &copy.schedule.id, &dates).await?;
+        instances.extend(times.into_iter().map(|t| Instance {
+            copy,
+            date: t.date,
+            departure_time: t.departure_time,
+            arrival_time: t.arrival_time,
+        }));
+    }
+    let Some((from, to)) = instances
+        .iter()
+        .map(|i| (i.departure_time, i.arrival_time))
+        .reduce(|(from, to), (d, a)| (from.min(d), to.max(a)))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut conflicts = Vec::new();
+    find_airport_closures(ex, &instances, &from, &to, &mut conflicts).await?;
+    find_plane_overlaps(ex, &instances, &from, &to, &mut conflicts).await?;
+
+    Ok(conflicts)
+}
+
+async fn find_airport_closures(
+    ex: &mut PgConnection,
+    instances: &[Instance<'_>],
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<()> {
+    let airport_ids = instances
+        .iter()
+        .flat_map(|i| [i.copy.schedule.origin_id, i.copy.schedule.destination_id])
+        .unique()
+        .collect_vec();
+    let mut closures = HashMap::new();
+    for airport_id in airport_ids {
+        let c = airports::get_closures_during(ex, &airport_id, from, to).await?;
+        closures.insert(airport_id, c);
+    }
+
+    for instance in instances {
+        let schedule = &instance.copy.schedule;
+        let movements = [
+            (schedule.origin_id, instance.departure_time, "departure"),
+            (schedule.destination_id, instance.arrival_time, "arrival"),
+        ];
+        for (airport_id, time, movement) in movements {
+            let closure = closures[&airport_id]
+                .iter()
+                .find(|c| c.start_time <= time && time < c.end_time);
+            if let Some(closure) = closure {
+                conflicts.push(conflict(
+                    instance,
+                    ConflictKind::AirportClosed,
+                    format!(
+                        "airport {airport_id} closed at {movement} time: {}",
+                        closure.reason
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn find_plane_overlaps(
+    ex: &mut PgConnection,
+    instances: &[Instance<'_>],
+    from: &OffsetDateTime,
+    to: &OffsetDateTime,
+    conflicts: &mut Vec<Conflict>,
+) -> Result<()> {
+    // planes of aircraft type schedules are only assigned when generating flights
+    let by_plane = instances
+        .iter()
+        .filter_map(|i| Some((i.copy.schedule.plane_id?, i)))
+        .into_group_map();
+
+    for (plane_id, instances) in by_plane {
+        let flights = flights::get_plane_flight_times(ex, &plane_id, from, to).await?;
+        for instance in &instances {
+            let flight = flights.iter().find(|(_, departure, arrival)| {
+                *departure < instance.arrival_time && instance.departure_time < *arrival
+            });
+            if let Some((flight_id, _, _)) = flight {
+                conflicts.push(conflict(
+                    instance,
+                    ConflictKind::PlaneOverlap,
+                    format!("plane {plane_id} operates flight {flight_id}"),
+                ));
+            }
+        }
+
+        // copies sharing the plane, compared with the one arriving last so far
+        let mut last: Option<&Instance> = None;
+        for instance in instances.into_iter().sorted_by_key(|i| i.departure_time) {
+            match last {
+                Some(other) if instance.departure_time < other.arrival_time => {
+                    conflicts.push(conflict(
+                        instance,
+                        ConflictKind::PlaneOverlap,
+                        format!(
+                            "plane {plane_id} operates schedule {} on {}",
+                            other.copy.schedule.id, other.date
+                        ),
+                    ));
+                    if instance.arrival_time > other.arrival_time {
+                        last = Some(instance);
+                    }
+                }
+                _ => last = Some(instance),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn conflict(instance: &Instance, kind: ConflictKind, description: String) -> Conflict {
+    Conflict {
+        schedule_id: instance.copy.schedule.id,
+        source_schedule_id: instance.copy.source_id,
+        date: instance.date,
+        kind,
+        description,
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{types::Uuid, PgConnection};
+use time::{Date, OffsetDateTime};
+use tonic::{Code, Status};
+
+use super::queries::{self, Schedule};
+use crate::flights::{self, FlightNumber, FlightSpec, ScheduleInstance};
+use crate::proto::flightmngr::Flight;
+
+/// Flights created, updated and cancelled to follow a schedule.
+#[derive(Default)]
+pub struct Changes {
+    pub created: Vec<Flight>,
+    pub updated: Vec<Flight>,
+    pub cancelled: Vec<Flight>,
+    /// Scheduled dates left without an up to date flight, with the reason.
+    pub skipped: Vec<(Date, String)>,
+}
+
+/// Local departure dates of a schedule between two dates, inclusive.
+pub fn scheduled_dates(schedule: &Schedule, from: Date, to: Date) -> Vec<Date> {
+    if schedule.deleted {
+        return Vec::new();
+    }
+
+    let mut dates = Vec::new();
+    let mut date = from.max(schedule.valid_from);
+    let to = to.min(schedule.valid_to);
+    while date <= to {
+        let weekday = date.weekday().number_from_monday() as i32;
+        if schedule.days_of_week.contains(&weekday) && !schedule.exceptions.contains(&date) {
+            dates.push(date);
+        }
+        let Some(next) = date.next_day() else { break };
+        date = next;
+    }
+
+    dates
+}
+
+/// Bring the flights generated by a schedule in line with it.
+///
+/// Future flights without status events are updated, or cancelled when the schedule no longer
+/// flies on their date and restored when it does again; flights modified individually are left
+/// alone. Missing flights are created up to `until`, or up to the last flight already generated.
+///
+/// With `keep_planes`, updated flights keep the plane of the aircraft type they were assigned.
+/// With `draft`, created flights are drafts until they are published.
+pub async fn sync_flights(
+    ex: &mut PgConnection,
+    schedule: &Schedule,
+    until: Option<Date>,
+    keep_planes: bool,
+    draft: bool,
+) -> Result<Changes, Status> {
+    let now = OffsetDateTime::now_utc();
+    let instances = flights::get_schedule_instances(ex, &schedule.id).await?;
+
+    let mut changes = Changes::default();
+    let until = until.or(instances.last().map(|i| i.departure_local_date));
+    let dates = match until {
+        // local dates can be a day behind UTC
+        Some(until) => scheduled_dates(schedule, now.date().previous_day().unwrap(), until),
+        None => Vec::new(),
+    };
+    let times: HashMap<_, _> = queries::get_scheduled_times(ex, &schedule.id, &dates)
+        .await?
+        .into_iter()
+        .filter(|t| t.departure_time > now)
+        .map(|t| (t.date, t))
+        .collect();
+
+    let generated: HashSet<_> = instances.iter().map(|i| i.departure_local_date).collect();
+    for instance in &instances {
+        if instance.modified || instance.departure_time <= now {
+            continue;
+        }
+        let Some(times) = times.get(&instance.departure_local_date) else {
+            if !instance.cancelled {
+                let flight =
+                    flights::cancel_scheduled_flight(ex, &instance.id, "removed from schedule")
+                        .await?;
+                changes.cancelled.push(flight);
+            }
+            continue;
+        };
+
+        let plane_id = match schedule.plane_id {
+            Some(plane_id) => plane_id,
+            None if keep_planes => instance.plane_id,
+            None => match find_plane(ex, schedule, times).await? {
+                Some(plane_id) => plane_id,
+                None => {
+                    changes.skipped.push((times.date, no_plane_reason()));
+                    continue;
+                }
+            },
+        };
+        let spec = flight_spec(schedule, plane_id, times);
+        let result = if instance.cancelled {
+            flights::restore_scheduled_flight(ex, &instance.id, &spec, "back in schedule").await
+        } else if spec != instance_spec(instance) {
+            flights::update_scheduled_flight(ex, &instance.id, &spec).await
+        } else {
+            continue;
+        };
+        match result {
+            Ok(flight) => changes.updated.push(flight),
+            Err(status) => changes.skipped.push((times.date, skip_reason(status)?)),
+        }
+    }
+
+    for date in &dates {
+        let Some(times) = times.get(date) else {
+            continue;
+        };
+        if generated.contains(date) {
+            continue;
+        }
+
+        let plane_id = match schedule.plane_id {
+            Some(plane_id) => Some(plane_id),
+            None => find_plane(ex, schedule, times).await?,
+        };
+        let Some(plane_id) = plane_id else {
+            changes.skipped.push((*date, no_plane_reason()));
+            continue;
+        };
+        let spec = flight_spec(schedule, plane_id, times);
+        match flights::create_scheduled_flight(ex, &schedule.id, &spec, draft).await {
+            Ok(flight) => changes.created.push(flight),
+            Err(status) => changes.skipped.push((*date, skip_reason(status)?)),
+        }
+    }
+
+    Ok(changes)
+}
+
+async fn find_plane(
+    ex: &mut PgConnection,
+    schedule: &Schedule,
+    times: &queries::ScheduledTimes,
+) -> Result<Option<Uuid>, Status> {
+    let Some(type_id) = schedule.aircraft_type_id else {
+        return Ok(None);
+    };
+
+    let plane_id =
+        flights::find_available_plane(ex, &type_id, &times.departure_time, &times.arrival_time)
+            .await?;
+
+    Ok(plane_id)
+}
+
+fn no_plane_reason() -> String {
+    "no plane of the aircraft type available".to_string()
+}
+
+/// Reason to skip a date when a flight cannot be created or updated, or the error if it is not
+/// caused by the schedule.
+fn skip_reason(status: Status) -> Result<String, Status> {
+    match status.code() {
+        Code::FailedPrecondition | Code::AlreadyExists => Ok(status.message().to_string()),
+        _ => Err(status),
+    }
+}
+
+fn flight_spec(schedule: &Schedule, plane_id: Uuid, times: &queries::ScheduledTimes) -> FlightSpec {
+    let number = schedule.carrier_id.zip(schedule.flight_number.clone()).map(
+        |(carrier_id, flight_number)| FlightNumber {
+            carrier_id,
+            flight_number,
+        },
+    );
+
+    FlightSpec {
+        plane_id,
+        origin_id: schedule.origin_id,
+        destination_id: schedule.destination_id,
+        departure_time: times.departure_time,
+        arrival_time: times.arrival_time,
+        number,
+    }
+}
+
+fn instance_spec(instance: &ScheduleInstance) -> FlightSpec {
+    let number = instance.carrier_id.zip(instance.flight_number.clone()).map(
+        |(carrier_id, flight_number)| FlightNumber {
+            carrier_id,
+            flight_number,
+        },
+    );
+
+    FlightSpec {
+        plane_id: instance.plane_id,
+        origin_id: instance.origin_id,
+        destination_id: instance.destination_id,
+        departure_time: instance.departure_time,
+        arrival_time: instance.arrival_time,
+        number,
+    }
+}
@@ -0,0 +1,167 @@
+use itertools::Itertools;
+use time::Time;
+use tonic::Status;
+
+use super::{
+    copy::{Conflict, ConflictKind},
+    queries,
+    seasons::Season,
+};
+use crate::{
+    datautils::{convert_date_to_timestamp, parse_date, parse_id},
+    flights,
+    proto::{self, flightmngr::SeasonConflictKind},
+};
+
+impl From<queries::Schedule> for proto::flightmngr::Schedule {
+    fn from(schedule: queries::Schedule) -> Self {
+        Self {
+            id: schedule.id.to_string(),
+            carrier_id: schedule
+                .carrier_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            flight_number: schedule.flight_number.unwrap_or_default(),
+            origin_id: schedule.origin_id.to_string(),
+            destination_id: schedule.destination_id.to_string(),
+            plane_id: schedule
+                .plane_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            aircraft_type_id: schedule
+                .aircraft_type_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            departure_local_minutes: minutes_from_time(schedule.departure_local_time),
+            arrival_local_minutes: minutes_from_time(schedule.arrival_local_time),
+            arrival_day_offset: schedule.arrival_day_offset as u32,
+            days_of_week: schedule
+                .days_of_week
+                .into_iter()
+                .map(|d| d as u32)
+                .collect(),
+            valid_from: Some(convert_date_to_timestamp(schedule.valid_from)),
+            valid_to: Some(convert_date_to_timestamp(schedule.valid_to)),
+            exceptions: schedule
+                .exceptions
+                .into_iter()
+                .map(convert_date_to_timestamp)
+                .collect(),
+            deleted: schedule.deleted,
+        }
+    }
+}
+
+impl TryFrom<proto::flightmngr::Schedule> for queries::ScheduleSpec {
+    type Error = Status;
+
+    fn try_from(schedule: proto::flightmngr::Schedule) -> Result<Self, Self::Error> {
+        let (carrier_id, flight_number) = match (
+            schedule.carrier_id.is_empty(),
+            schedule.flight_number.is_empty(),
+        ) {
+            (true, true) => (None, None),
+            (false, false) => (
+                Some(parse_id(&schedule.carrier_id)?),
+                Some(flights::parse_flight_number(&schedule.flight_number)?),
+            ),
+            (true, false) => return Err(Status::invalid_argument("'carrier_id'")),
+            (false, true) => return Err(Status::invalid_argument("'flight_number'")),
+        };
+        let (plane_id, aircraft_type_id) = match (
+            schedule.plane_id.is_empty(),
+            schedule.aircraft_type_id.is_empty(),
+        ) {
+            (false, true) => (Some(parse_id(&schedule.plane_id)?), None),
+            (true, false) => (None, Some(parse_id(&schedule.aircraft_type_id)?)),
+            _ => return Err(Status::invalid_argument("'plane_id'")),
+        };
+
+        if schedule.arrival_day_offset > 2 {
+            return Err(Status::invalid_argument("'arrival_day_offset'"));
+        }
+        if schedule.days_of_week.is_empty()
+            || !schedule.days_of_week.iter().all(|d| (1..=7).contains(d))
+        {
+            return Err(Status::invalid_argument("'days_of_week'"));
+        }
+
+        let valid_from = parse_date(schedule.valid_from)?;
+        let valid_to = parse_date(schedule.valid_to)?;
+        if valid_to < valid_from {
+            return Err(Status::invalid_argument("'valid_to'"));
+        }
+        let exceptions = schedule
+            .exceptions
+            .into_iter()
+            .map(|d| parse_date(Some(d)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            carrier_id,
+            flight_number,
+            origin_id: parse_id(&schedule.origin_id)?,
+            destination_id: parse_id(&schedule.destination_id)?,
+            plane_id,
+            aircraft_type_id,
+            departure_local_time: time_from_minutes(schedule.departure_local_minutes)
+                .ok_or(Status::invalid_argument("'departure_local_minutes'"))?,
+            arrival_local_time: time_from_minutes(schedule.arrival_local_minutes)
+                .ok_or(Status::invalid_argument("'arrival_local_minutes'"))?,
+            arrival_day_offset: schedule.arrival_day_offset as i32,
+            days_of_week: schedule
+                .days_of_week
+                .into_iter()
+                .sorted()
+                .dedup()
+                .map(|d| d as i32)
+                .collect(),
+            valid_from,
+            valid_to,
+            exceptions: exceptions.into_iter().sorted().dedup().collect(),
+        })
+    }
+}
+
+impl From<Season> for proto::flightmngr::Season {
+    fn from(season: Season) -> Self {
+        Self {
+            code: season.code(),
+            start: Some(convert_date_to_timestamp(season.start())),
+            end: Some(convert_date_to_timestamp(season.end())),
+        }
+    }
+}
+
+impl From<Conflict> for proto::flightmngr::SeasonConflict {
+    fn from(conflict: Conflict) -> Self {
+        let kind = match conflict.kind {
+            ConflictKind::PlaneOverlap => SeasonConflictKind::PlaneOverlap,
+            ConflictKind::AirportClosed => SeasonConflictKind::AirportClosed,
+        };
+
+        Self {
+            schedule_id: conflict.schedule_id.to_string(),
+            source_schedule_id: conflict.source_schedule_id.to_string(),
+            date: Some(convert_date_to_timestamp(conflict.date)),
+            kind: kind.into(),
+            description: conflict.description,
+        }
+    }
+}
+
+pub fn parse_season(code: &str) -> Result<Season, Status> {
+    Season::parse(code).ok_or(Status::invalid_argument("'season'"))
+}
+
+fn time_from_minutes(minutes: u32) -> Option<Time> {
+    if minutes >= 24 * 60 {
+        return None;
+    }
+
+    Time::from_hms((minutes / 60) as u8, (minutes % 60) as u8, 0).ok()
+}
+
+fn minutes_from_time(time: Time) -> u32 {
+    time.hour() as u32 * 60 + time.minute() as u32
+}
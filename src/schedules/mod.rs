@@ -0,0 +1,305 @@
+use std::sync::Arc;
+
+use sqlx::PgConnection;
+use tonic::{Request, Response, Status};
+
+use crate::{
+    aircraft_types, airlines, airports,
+    datautils::{convert_date_to_timestamp, parse_date, parse_id},
+    db::Database,
+    idempotency,
+    proto::flightmngr::{
+        self, get_season_request, schedules_server::Schedules, CopySeasonRequest,
+        CopySeasonResponse, CreateScheduleRequest, DeleteScheduleRequest,
+        GenerateScheduleFlightsRequest, GetScheduleRequest, GetSeasonRequest, ListSchedulesRequest,
+        ListSchedulesResponse, ScheduleFlightsResponse, SkippedDate, UpdateScheduleRequest,
+    },
+    rabbitmq::Rabbit,
+};
+
+mod copy;
+mod generator;
+mod map;
+mod queries;
+mod seasons;
+
+use seasons::Season;
+
+pub struct SchedulesApp {
+    db: Database,
+    rabbitmq: Arc<Rabbit>,
+}
+
+#[tonic::async_trait]
+impl Schedules for SchedulesApp {
+    async fn list_schedules(
+        &self,
+        request: Request<ListSchedulesRequest>,
+    ) -> Result<Response<ListSchedulesResponse>, Status> {
+        let ListSchedulesRequest { show_deleted } = request.into_inner();
+        let mut t = self.db.begin().await?;
+
+        let schedules = if show_deleted {
+            queries::list_schedules_with_deleted(t.get_conn()).await?
+        } else {
+            queries::list_schedules(t.get_conn()).await?
+        };
+
+        let schedules = schedules.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListSchedulesResponse { schedules }))
+    }
+
+    async fn get_schedule(
+        &self,
+        request: Request<GetScheduleRequest>,
+    ) -> Result<Response<flightmngr::Schedule>, Status> {
+        let GetScheduleRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        let schedule = queries::get_schedule(t.get_conn(), &id).await?.into();
+
+        Ok(Response::new(schedule))
+    }
+
+    async fn create_schedule(
+        &self,
+        request: Request<CreateScheduleRequest>,
+    ) -> Result<Response<flightmngr::Schedule>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let spec = request
+            .into_inner()
+            .schedule
+            .unwrap_or_default()
+            .try_into()?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(s) = idempotency::replay(t.get_conn(), "CreateSchedule", key).await? {
+            return Ok(Response::new(s));
+        }
+
+        ensure_references_active(t.get_conn(), &spec).await?;
+        let schedule = queries::create_schedule(t.get_conn(), spec).await?.into();
+
+        idempotency::record(t.get_conn(), "CreateSchedule", key, &schedule).await?;
+
+        t.commit().await?;
+        Ok(Response::new(schedule))
+    }
+
+    async fn update_schedule(
+        &self,
+        request: Request<UpdateScheduleRequest>,
+    ) -> Result<Response<ScheduleFlightsResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let schedule = request.into_inner().schedule.unwrap_or_default();
+        let id = parse_id(&schedule.id)?;
+        let spec: queries::ScheduleSpec = schedule.try_into()?;
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref();
+        if let Some(r) = idempotency::replay(t.get_conn(), "UpdateSchedule", key).await? {
+            return Ok(Response::new(r));
+        }
+
+        let previous = queries::lock_schedule(t.get_conn(), &id).await?;
+        ensure_references_active(t.get_conn(), &spec).await?;
+        // planes assigned from the same aircraft type remain valid
+        let keep_planes =
+            spec.aircraft_type_id.is_some() && spec.aircraft_type_id == previous.aircraft_type_id;
+        let schedule = queries::update_schedule(t.get_conn(), &id, spec).await?;
+
+        let changes =
+            generator::sync_flights(t.get_conn(), &schedule, None, keep_planes, false).await?;
+        let response = flights_response(schedule, changes);
+
+        idempotency::record(t.get_conn(), "UpdateSchedule", key, &response).await?;
+        t.commit().await?;
+
+        self.notify(&response).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn delete_schedule(
+        &self,
+        request: Request<DeleteScheduleRequest>,
+    ) -> Result<Response<ScheduleFlightsResponse>, Status> {
+        let DeleteScheduleRequest { id } = request.into_inner();
+        let id = parse_id(&id)?;
+        let mut t = self.db.begin().await?;
+
+        queries::lock_schedule(t.get_conn(), &id).await?;
+        let schedule = queries::delete_schedule(t.get_conn(), &id).await?;
+
+        let changes = generator::sync_flights(t.get_conn(), &schedule, None, true, false).await?;
+        let response = flights_response(schedule, changes);
+
+        t.commit().await?;
+
+        self.notify(&response).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn generate_schedule_flights(
+        &self,
+        request: Request<GenerateScheduleFlightsRequest>,
+    ) -> Result<Response<ScheduleFlightsResponse>, Status> {
+        let GenerateScheduleFlightsRequest {
+            schedule_id,
+            until,
+            draft,
+        } = request.into_inner();
+        let id = parse_id(&schedule_id)?;
+        let until = parse_date(until)?;
+        let mut t = self.db.begin().await?;
+
+        // serialize generations of the same schedule
+        let schedule = queries::lock_schedule(t.get_conn(), &id).await?;
+        if schedule.deleted {
+            return Err(Status::failed_precondition("schedule is deleted"));
+        }
+
+        let changes =
+            generator::sync_flights(t.get_conn(), &schedule, Some(until), true, draft).await?;
+        let response = flights_response(schedule, changes);
+
+        t.commit().await?;
+
+        self.notify(&response).await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_season(
+        &self,
+        request: Request<GetSeasonRequest>,
+    ) -> Result<Response<flightmngr::Season>, Status> {
+        let season = match request.into_inner().season {
+            Some(get_season_request::Season::Code(code)) => map::parse_season(&code)?,
+            Some(get_season_request::Season::Date(date)) => {
+                Season::containing(parse_date(Some(date))?)
+            }
+            None => return Err(Status::invalid_argument("'season'")),
+        };
+
+        Ok(Response::new(season.into()))
+    }
+
+    async fn copy_season(
+        &self,
+        request: Request<CopySeasonRequest>,
+    ) -> Result<Response<CopySeasonResponse>, Status> {
+        let idempotency_key = idempotency::get_key(&request)?;
+        let CopySeasonRequest {
+            season,
+            target_season,
+            dry_run,
+            ignore_conflicts,
+        } = request.into_inner();
+        let season = map::parse_season(&season)?;
+        let target_season = if target_season.is_empty() {
+            season.next()
+        } else {
+            map::parse_season(&target_season)
+                .map_err(|_| Status::invalid_argument("'target_season'"))?
+        };
+        if target_season == season {
+            return Err(Status::invalid_argument("'target_season'"));
+        }
+
+        let mut t = self.db.begin().await?;
+
+        let key = idempotency_key.as_ref().filter(|_| !dry_run);
+        if let Some(r) = idempotency::replay(t.get_conn(), "CopySeason", key).await? {
+            return Ok(Response::new(r));
+        }
+
+        // a retry without an idempotency key must not copy the schedules again
+        queries::lock_season_copies(t.get_conn(), &target_season.code()).await?;
+        if copy::is_copied(t.get_conn(), season, target_season).await? {
+            return Err(Status::already_exists(format!(
+                "season {} was already copied to {}",
+                season.code(),
+                target_season.code()
+            )));
+        }
+
+        let copies = copy::copy_season(t.get_conn(), season, target_season).await?;
+        let conflicts = copy::find_conflicts(t.get_conn(), &copies, target_season).await?;
+        if !dry_run && !ignore_conflicts && !conflicts.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "{} conflicts in season {}, preview them with a dry run",
+                conflicts.len(),
+                target_season.code()
+            )));
+        }
+
+        let response = CopySeasonResponse {
+            season: Some(season.into()),
+            target_season: Some(target_season.into()),
+            schedules: copies.into_iter().map(|c| c.schedule.into()).collect(),
+            conflicts: conflicts.into_iter().map(Into::into).collect(),
+        };
+
+        if dry_run {
+            t.rollback().await?;
+            return Ok(Response::new(response));
+        }
+
+        idempotency::record(t.get_conn(), "CopySeason", key, &response).await?;
+        t.commit().await?;
+
+        Ok(Response::new(response))
+    }
+}
+
+impl SchedulesApp {
+    pub fn new(db: Database, rabbitmq: Arc<Rabbit>) -> Self {
+        Self { db, rabbitmq }
+    }
+
+    /// Notify the changes to existing flights.
+    async fn notify(&self, response: &ScheduleFlightsResponse) -> Result<(), Status> {
+        for flight in response.updated.iter().chain(&response.cancelled) {
+            self.rabbitmq.notify_flight_update(flight).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn ensure_references_active(
+    ex: &mut PgConnection,
+    spec: &queries::ScheduleSpec,
+) -> Result<(), Status> {
+    airports::ensure_airport_active(ex, &spec.origin_id).await?;
+    airports::ensure_airport_active(ex, &spec.destination_id).await?;
+    if let Some(carrier_id) = &spec.carrier_id {
+        airlines::ensure_airline_active(ex, carrier_id).await?;
+    }
+    if let Some(type_id) = &spec.aircraft_type_id {
+        aircraft_types::ensure_aircraft_type_active(ex, type_id).await?;
+    }
+
+    Ok(())
+}
+
+fn flights_response(
+    schedule: queries::Schedule,
+    changes: generator::Changes,
+) -> ScheduleFlightsResponse {
+    ScheduleFlightsResponse {
+        schedule: Some(schedule.into()),
+        created: changes.created,
+        updated: changes.updated,
+        cancelled: changes.cancelled,
+        skipped: changes
+            .skipped
+            .into_iter()
+            .map(|(date, reason)| SkippedDate {
+                date: Some(convert_date_to_timestamp(date)),
+                reason,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,261 @@
+use sqlx::{types::Uuid, PgConnection};
+use time::{Date, OffsetDateTime, Time};
+
+type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+
+pub struct Schedule {
+    pub id: Uuid,
+    pub carrier_id: Option<Uuid>,
+    pub flight_number: Option<String>,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub plane_id: Option<Uuid>,
+    pub aircraft_type_id: Option<Uuid>,
+    pub departure_local_time: Time,
+    pub arrival_local_time: Time,
+    pub arrival_day_offset: i32,
+    pub days_of_week: Vec<i32>,
+    pub valid_from: Date,
+    pub valid_to: Date,
+    pub exceptions: Vec<Date>,
+    pub deleted: bool,
+}
+
+pub struct ScheduleSpec {
+    pub carrier_id: Option<Uuid>,
+    pub flight_number: Option<String>,
+    pub origin_id: Uuid,
+    pub destination_id: Uuid,
+    pub plane_id: Option<Uuid>,
+    pub aircraft_type_id: Option<Uuid>,
+    pub departure_local_time: Time,
+    pub arrival_local_time: Time,
+    pub arrival_day_offset: i32,
+    pub days_of_week: Vec<i32>,
+    pub valid_from: Date,
+    pub valid_to: Date,
+    pub exceptions: Vec<Date>,
+}
+
+/// Departure and arrival times of a schedule on a local departure date.
+pub struct ScheduledTimes {
+    pub date: Date,
+    pub departure_time: OffsetDateTime,
+    pub arrival_time: OffsetDateTime,
+}
+
+pub async fn list_schedules(ex: &mut PgConnection) -> Result<Vec<Schedule>> {
+    let schedules = sqlx::query_as!(
+        Schedule,
+        "select * from schedules where not deleted order by valid_from, id"
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(schedules)
+}
+
+pub async fn list_schedules_with_deleted(ex: &mut PgConnection) -> Result<Vec<Schedule>> {
+    let schedules = sqlx::query_as!(Schedule, "select * from schedules order by valid_from, id")
+        .fetch_all(ex)
+        .await?;
+
+    Ok(schedules)
+}
+
+/// List the active schedules valid on some day between two dates.
+pub async fn list_schedules_during(
+    ex: &mut PgConnection,
+    from: &Date,
+    to: &Date,
+) -> Result<Vec<Schedule>> {
+    let schedules = sqlx::query_as!(
+        Schedule,
+        "select * from schedules \
+        where not deleted and valid_from <= $2 and valid_to >= $1 \
+        order by valid_from, id",
+        from,
+        to
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(schedules)
+}
+
+/// Lock the copies into a season until the end of the transaction.
+pub async fn lock_season_copies(ex: &mut PgConnection, season: &str) -> Result<()> {
+    sqlx::query!(
+        "select from pg_advisory_xact_lock(hashtextextended('season ' || $1, 0))",
+        season
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn add_schedule_copy(
+    ex: &mut PgConnection,
+    schedule_id: &Uuid,
+    source_id: &Uuid,
+) -> Result<()> {
+    sqlx::query!(
+        "insert into schedule_copies (schedule_id, source_id) values ($1, $2)",
+        schedule_id,
+        source_id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Whether active schedules valid between two dates were copied from schedules valid
+/// between two other dates.
+pub async fn has_copies(
+    ex: &mut PgConnection,
+    source_from: &Date,
+    source_to: &Date,
+    from: &Date,
+    to: &Date,
+) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"select exists(
+            select 1 from schedule_copies c
+            join schedules source on source.id = c.source_id
+            join schedules copy on copy.id = c.schedule_id
+            where not copy.deleted
+            and source.valid_from <= $2 and source.valid_to >= $1
+            and copy.valid_from <= $4 and copy.valid_to >= $3
+        ) as "exists!""#,
+        source_from,
+        source_to,
+        from,
+        to
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(exists)
+}
+
+pub async fn get_schedule(ex: &mut PgConnection, id: &Uuid) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(Schedule, "select * from schedules where id = $1", id)
+        .fetch_one(ex)
+        .await?;
+
+    Ok(schedule)
+}
+
+/// Get a schedule, locking it until the end of the transaction.
+pub async fn lock_schedule(ex: &mut PgConnection, id: &Uuid) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(
+        Schedule,
+        "select * from schedules where id = $1 for update",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn create_schedule(ex: &mut PgConnection, spec: ScheduleSpec) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(
+        Schedule,
+        "insert into schedules (id, carrier_id, flight_number, origin_id, destination_id, \
+        plane_id, aircraft_type_id, departure_local_time, arrival_local_time, arrival_day_offset, \
+        days_of_week, valid_from, valid_to, exceptions) \
+        values (gen_random_uuid(), $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13) \
+        returning *",
+        spec.carrier_id,
+        spec.flight_number,
+        spec.origin_id,
+        spec.destination_id,
+        spec.plane_id,
+        spec.aircraft_type_id,
+        spec.departure_local_time,
+        spec.arrival_local_time,
+        spec.arrival_day_offset,
+        &spec.days_of_week,
+        spec.valid_from,
+        spec.valid_to,
+        &spec.exceptions
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn update_schedule(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    spec: ScheduleSpec,
+) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(
+        Schedule,
+        "update schedules set carrier_id = $2, flight_number = $3, origin_id = $4, \
+        destination_id = $5, plane_id = $6, aircraft_type_id = $7, departure_local_time = $8, \
+        arrival_local_time = $9, arrival_day_offset = $10, days_of_week = $11, valid_from = $12, \
+        valid_to = $13, exceptions = $14 \
+        where id = $1 and not deleted returning *",
+        id,
+        spec.carrier_id,
+        spec.flight_number,
+        spec.origin_id,
+        spec.destination_id,
+        spec.plane_id,
+        spec.aircraft_type_id,
+        spec.departure_local_time,
+        spec.arrival_local_time,
+        spec.arrival_day_offset,
+        &spec.days_of_week,
+        spec.valid_from,
+        spec.valid_to,
+        &spec.exceptions
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(schedule)
+}
+
+pub async fn delete_schedule(ex: &mut PgConnection, id: &Uuid) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(
+        Schedule,
+        "update schedules set deleted = true where id = $1 and not deleted returning *",
+        id
+    )
+    .fetch_one(ex)
+    .await?;
+
+    Ok(schedule)
+}
+
+/// Convert the local times of a schedule to instants on the given local departure dates.
+pub async fn get_scheduled_times(
+    ex: &mut PgConnection,
+    id: &Uuid,
+    dates: &[Date],
+) -> Result<Vec<ScheduledTimes>> {
+    let times = sqlx::query_as!(
+        ScheduledTimes,
+        r#"select d.date as "date!",
+            (d.date + s.departure_local_time) at time zone o.timezone as "departure_time!",
+            (d.date + s.arrival_day_offset + s.arrival_local_time) at time zone a.timezone as "arrival_time!"
+        from schedules s
+        join airports o on o.id = s.origin_id
+        join airports a on a.id = s.destination_id
+        cross join unnest($2::date[]) as d(date)
+        where s.id = $1
+        order by d.date"#,
+        id,
+        dates
+    )
+    .fetch_all(ex)
+    .await?;
+
+    Ok(times)
+}
@@ -0,0 +1,91 @@
+use time::{Date, Duration, Month};
+
+/// IATA scheduling season: summer starts on the last Sunday of March and winter on the last
+/// Sunday of October, each ending the day before the next one starts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Season {
+    pub summer: bool,
+    pub year: i32,
+}
+
+impl Season {
+    /// Parse a season code, e.g. S26 or W26.
+    pub fn parse(code: &str) -> Option<Self> {
+        let code = code.trim().to_uppercase();
+        let summer = match code.get(..1)? {
+            "S" => true,
+            "W" => false,
+            _ => return None,
+        };
+        let year = code
+            .get(1..)
+            .filter(|y| y.len() == 2)?
+            .parse::<i32>()
+            .ok()?;
+
+        Some(Self {
+            summer,
+            year: 2000 + year,
+        })
+    }
+
+    /// Season a date falls in.
+    pub fn containing(date: Date) -> Self {
+        let year = date.year();
+        if date < last_sunday(year, Month::March) {
+            Self {
+                summer: false,
+                year: year - 1,
+            }
+        } else if date < last_sunday(year, Month::October) {
+            Self { summer: true, year }
+        } else {
+            Self {
+                summer: false,
+                year,
+            }
+        }
+    }
+
+    pub fn code(&self) -> String {
+        let prefix = if self.summer { 'S' } else { 'W' };
+        format!("{prefix}{:02}", self.year % 100)
+    }
+
+    pub fn start(&self) -> Date {
+        let month = if self.summer {
+            Month::March
+        } else {
+            Month::October
+        };
+        last_sunday(self.year, month)
+    }
+
+    /// Last day of the season.
+    pub fn end(&self) -> Date {
+        self.next().start() - Duration::DAY
+    }
+
+    pub fn next(&self) -> Self {
+        if self.summer {
+            Self {
+                summer: false,
+                year: self.year,
+            }
+        } else {
+            Self {
+                summer: true,
+                year: self.year + 1,
+            }
+        }
+    }
+}
+
+fn last_sunday(year: i32, month: Month) -> Date {
+    let last_day =
+        Date::from_calendar_date(year, month, time::util::days_in_year_month(year, month))
+            .expect("valid date");
+    let days_after_sunday = last_day.weekday().number_days_from_sunday();
+
+    last_day - Duration::days(days_after_sunday as i64)
+}
@@ -0,0 +1,216 @@
+use amqprs::{
+    channel::{
+        BasicAckArguments, BasicConsumeArguments, BasicNackArguments, BasicQosArguments, Channel,
+        ConsumerMessage, ExchangeDeclareArguments, QueueBindArguments, QueueDeclareArguments,
+    },
+    FieldTable, FieldValue,
+};
+use prost::Message;
+use sqlx::types::Uuid;
+use thiserror::Error;
+use tokio::{sync::mpsc::UnboundedReceiver, task::JoinHandle};
+
+use crate::db::{Database, DatabaseError};
+use crate::planes;
+use crate::proto::flightmngr::{TicketCancelled, TicketCreated};
+use crate::rabbitmq::Rabbit;
+
+mod queries;
+
+/// Number of unacknowledged messages delivered to the consumer at once.
+const PREFETCH_COUNT: u16 = 16;
+
+/// Number of hours a message id is kept to recognize redeliveries.
+const RETENTION_HOURS: i32 = 7 * 24;
+
+/// Consumer of the events published by the ticketing service.
+///
+/// Every ticket created or cancelled updates the seats sold in its cabin class on its flight.
+/// Messages are acknowledged once applied; those that cannot be decoded, or refer to an
+/// unknown flight or a cabin class it does not have, are rejected to the dead-letter
+/// exchange `<exchange>.dead-letter`.
+pub struct TicketingConsumer {
+    db: Database,
+    channel: Channel,
+}
+
+impl TicketingConsumer {
+    /// Bind `queue` to the ticketing `exchange` and start consuming in the background.
+    pub async fn start(
+        db: Database,
+        rabbitmq: &Rabbit,
+        exchange: &str,
+        queue: &str,
+    ) -> Result<JoinHandle<()>, amqprs::error::Error> {
+        let channel = rabbitmq.open_channel().await?;
+
+        let dead_letter_exchange = format!("{exchange}.dead-letter");
+        let dead_letter_queue = format!("{queue}.dead-letter");
+        for exchange in [exchange, &dead_letter_exchange] {
+            channel
+                .exchange_declare(
+                    ExchangeDeclareArguments::new(exchange, "fanout")
+                        .durable(true)
+                        .finish(),
+                )
+                .await?;
+        }
+
+        channel
+            .queue_declare(QueueDeclareArguments::durable_client_named(
+                &dead_letter_queue,
+            ))
+            .await?;
+        channel
+            .queue_bind(QueueBindArguments::new(
+                &dead_letter_queue,
+                &dead_letter_exchange,
+                "",
+            ))
+            .await?;
+
+        let mut arguments = FieldTable::new();
+        arguments.insert(
+            "x-dead-letter-exchange".try_into().unwrap(),
+            FieldValue::from(dead_letter_exchange),
+        );
+        channel
+            .queue_declare(
+                QueueDeclareArguments::durable_client_named(queue)
+                    .arguments(arguments)
+                    .finish(),
+            )
+            .await?;
+        channel
+            .queue_bind(QueueBindArguments::new(queue, exchange, ""))
+            .await?;
+
+        channel
+            .basic_qos(BasicQosArguments::new(0, PREFETCH_COUNT, false))
+            .await?;
+        let (_, messages) = channel
+            .basic_consume_rx(BasicConsumeArguments::new(queue, ""))
+            .await?;
+
+        let consumer = TicketingConsumer { db, channel };
+        Ok(tokio::spawn(consumer.run(messages)))
+    }
+
+    async fn run(self, mut messages: UnboundedReceiver<ConsumerMessage>) {
+        while let Some(message) = messages.recv().await {
+            let Some(deliver) = message.deliver.as_ref() else {
+                continue;
+            };
+            let delivery_tag = deliver.delivery_tag();
+
+            let result = match self.handle(&message).await {
+                Ok(()) => {
+                    self.channel
+                        .basic_ack(BasicAckArguments::new(delivery_tag, false))
+                        .await
+                }
+                Err(error) => {
+                    // retry later unless the message can never be applied
+                    let requeue = matches!(error, HandleError::Database(_));
+                    tracing::warn!(%error, requeue, "could not process ticketing message");
+                    self.channel
+                        .basic_nack(BasicNackArguments::new(delivery_tag, false, requeue))
+                        .await
+                }
+            };
+
+            if let Err(error) = result {
+                tracing::error!(%error, "could not acknowledge ticketing message");
+            }
+        }
+
+        tracing::info!("ticketing consumer stopped");
+    }
+
+    async fn handle(&self, message: &ConsumerMessage) -> Result<(), HandleError> {
+        let properties = message
+            .basic_properties
+            .as_ref()
+            .ok_or(HandleError::Invalid("missing properties"))?;
+        let message_id = properties
+            .message_id()
+            .ok_or(HandleError::Invalid("missing message id"))?;
+        let content = message.content.as_deref().unwrap_or_default();
+
+        let event = match properties.message_type().map(String::as_str) {
+            Some("flightmngr.TicketCreated") => {
+                let TicketCreated {
+                    flight_id, class, ..
+                } = TicketCreated::decode(content)?;
+                TicketEvent::Created(parse_flight_id(&flight_id)?, class)
+            }
+            Some("flightmngr.TicketCancelled") => {
+                let TicketCancelled {
+                    flight_id, class, ..
+                } = TicketCancelled::decode(content)?;
+                TicketEvent::Cancelled(parse_flight_id(&flight_id)?, class)
+            }
+            _ => return Err(HandleError::Invalid("unknown message type")),
+        };
+        let (TicketEvent::Created(flight_id, class) | TicketEvent::Cancelled(flight_id, class)) =
+            event;
+        let class_name = parse_class(class)?;
+
+        let mut t = self.db.begin().await?;
+
+        queries::delete_expired_messages(t.get_conn(), RETENTION_HOURS).await?;
+        if !queries::mark_processed(t.get_conn(), message_id).await? {
+            tracing::debug!(message_id, "ignoring redelivered ticketing message");
+            return Ok(());
+        }
+
+        // serialize with the seat changes of the same flight
+        let plane_id = queries::lock_flight(t.get_conn(), &flight_id)
+            .await?
+            .ok_or(HandleError::Invalid("unknown flight"))?;
+        let cabin_classes = planes::get_cabin_classes(t.get_conn(), &[plane_id])
+            .await?
+            .remove(&plane_id)
+            .unwrap_or_default();
+        if !cabin_classes.iter().any(|c| c.class == class) {
+            return Err(HandleError::Invalid("cabin class not on the flight"));
+        }
+
+        match event {
+            TicketEvent::Created(..) => {
+                queries::add_booking(t.get_conn(), &flight_id, class_name).await?;
+            }
+            TicketEvent::Cancelled(..) => {
+                queries::remove_booking(t.get_conn(), &flight_id, class_name).await?;
+            }
+        }
+
+        t.commit().await?;
+
+        Ok(())
+    }
+}
+
+enum TicketEvent {
+    Created(Uuid, i32),
+    Cancelled(Uuid, i32),
+}
+
+fn parse_flight_id(id: &str) -> Result<Uuid, HandleError> {
+    id.parse()
+        .map_err(|_| HandleError::Invalid("invalid flight id"))
+}
+
+fn parse_class(class: i32) -> Result<&'static str, HandleError> {
+    planes::parse_cabin_class(class).map_err(|_| HandleError::Invalid("invalid cabin class"))
+}
+
+#[derive(Error, Debug)]
+enum HandleError {
+    #[error("invalid message: {0}")]
+    Invalid(&'static str),
+    #[error("could not decode message: {0}")]
+    Decode(#[from] prost::DecodeError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
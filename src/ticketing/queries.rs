@@ -0,0 +1,67 @@
+use sqlx::{types::Uuid, PgConnection};
+
+type Result<T> = std::result::Result<T, crate::db::DatabaseError>;
+
+pub async fn delete_expired_messages(ex: &mut PgConnection, retention_hours: i32) -> Result<()> {
+    sqlx::query!(
+        "delete from processed_messages where processed_at < now() - make_interval(hours => $1)",
+        retention_hours
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark a message as processed, returning false if it already was.
+pub async fn mark_processed(ex: &mut PgConnection, message_id: &str) -> Result<bool> {
+    let res = sqlx::query!(
+        "insert into processed_messages (message_id) values ($1) on conflict do nothing",
+        message_id
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(res.rows_affected() == 1)
+}
+
+/// Lock a flight, returning the plane currently assigned to it if it exists.
+pub async fn lock_flight(ex: &mut PgConnection, id: &Uuid) -> Result<Option<Uuid>> {
+    let plane_id = sqlx::query_scalar!(
+        "select s.plane_id from flights f \
+        join flight_current_state s on s.flight_id = f.id \
+        where f.id = $1 for update of f",
+        id
+    )
+    .fetch_optional(ex)
+    .await?;
+
+    Ok(plane_id)
+}
+
+/// Count a ticket as a seat sold in its cabin class.
+pub async fn add_booking(ex: &mut PgConnection, flight_id: &Uuid, class: &str) -> Result<()> {
+    sqlx::query!(
+        "insert into flight_seat_inventory (flight_id, class, sold) values ($1, $2, 1) \
+        on conflict (flight_id, class) do update set sold = flight_seat_inventory.sold + 1",
+        flight_id,
+        class
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_booking(ex: &mut PgConnection, flight_id: &Uuid, class: &str) -> Result<()> {
+    sqlx::query!(
+        "update flight_seat_inventory set sold = sold - 1 \
+        where flight_id = $1 and class = $2 and sold > 0",
+        flight_id,
+        class
+    )
+    .execute(ex)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use flightmngr::proto::flightmngr::{
+    AircraftType, CreateAircraftTypeRequest, CreatePlaneRequest, DeleteAircraftTypeRequest,
+    GetPlaneRequest, ListAircraftTypesRequest, Plane, UpdateAircraftTypeRequest,
+};
+use sqlx::PgPool;
+
+mod common;
+
+fn example_aircraft_type() -> AircraftType {
+    AircraftType {
+        id: Default::default(),
+        icao_designator: "a20n".to_string(),
+        name: "Airbus A320neo".to_string(),
+        typical_seats: 180,
+        cargo_capacity_kg: 3000,
+        max_range_km: 6300,
+        cruise_speed_kmh: 833,
+        min_turnaround_minutes: 35,
+        deleted: false,
+    }
+}
+
+#[sqlx::test]
+async fn create_update_delete(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    // create
+    let r = client
+        .aircraft_types
+        .create_aircraft_type(CreateAircraftTypeRequest {
+            aircraft_type: Some(example_aircraft_type()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.icao_designator, "A20N");
+    assert_eq!(r.typical_seats, 180);
+
+    // update
+    let r = client
+        .aircraft_types
+        .update_aircraft_type(UpdateAircraftTypeRequest {
+            aircraft_type: Some(AircraftType {
+                id: r.id,
+                typical_seats: 186,
+                ..example_aircraft_type()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.typical_seats, 186);
+
+    // delete
+    let _ = client
+        .aircraft_types
+        .delete_aircraft_type(DeleteAircraftTypeRequest { id: r.id })
+        .await
+        .unwrap();
+
+    let r = client
+        .aircraft_types
+        .list_aircraft_types(ListAircraftTypesRequest {
+            show_deleted: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.aircraft_types.len(), 0);
+}
+
+#[sqlx::test]
+async fn plane_resolves_type(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let aircraft_type = client
+        .aircraft_types
+        .create_aircraft_type(CreateAircraftTypeRequest {
+            aircraft_type: Some(example_aircraft_type()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // the designator must match the type
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                model: "A320neo".to_string(),
+                type_id: aircraft_type.id.clone(),
+                icao_type: "B38M".to_string(),
+                ..Default::default()
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // create with a cabin override
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                model: "A320neo".to_string(),
+                type_id: aircraft_type.id.clone(),
+                cabin_capacity_override: Some(174),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cabin_capacity, 174);
+    assert_eq!(r.cargo_capacity_kg, 3000);
+    assert_eq!(r.icao_type, "A20N");
+    assert_eq!(r.cabin_capacity_override, Some(174));
+    assert_eq!(r.cargo_capacity_kg_override, None);
+
+    // type changes are reflected in the plane
+    let _ = client
+        .aircraft_types
+        .update_aircraft_type(UpdateAircraftTypeRequest {
+            aircraft_type: Some(AircraftType {
+                id: aircraft_type.id.clone(),
+                cargo_capacity_kg: 3500,
+                ..example_aircraft_type()
+            }),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .planes
+        .get_plane(GetPlaneRequest { id: r.id })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cargo_capacity_kg, 3500);
+    assert_eq!(r.aircraft_type.unwrap().min_turnaround_minutes, 35);
+
+    // a type in use cannot be deleted
+    let r = client
+        .aircraft_types
+        .delete_aircraft_type(DeleteAircraftTypeRequest {
+            id: aircraft_type.id,
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+}
@@ -0,0 +1,130 @@
+use flightmngr::proto::flightmngr::{
+    Airline, CreateAirlineRequest, DeleteAirlineRequest, GetAirlineRequest, ListAirlinesRequest,
+    UpdateAirlineRequest,
+};
+use sqlx::PgPool;
+
+mod common;
+
+fn example_airline() -> Airline {
+    Airline {
+        id: Default::default(),
+        iata: "TA".to_string(),
+        icao: "TAL".to_string(),
+        name: "Test Airline".to_string(),
+        callsign: "TEST".to_string(),
+        deleted: false,
+    }
+}
+
+#[sqlx::test]
+async fn create_update_delete(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    // create
+    let airline = client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(example_airline()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_ne!(airline.id, "");
+    assert_eq!(airline.iata, "TA");
+
+    // update
+    let r = client
+        .airlines
+        .update_airline(UpdateAirlineRequest {
+            airline: Some(Airline {
+                name: "Renamed Airline".to_string(),
+                ..airline.clone()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.name, "Renamed Airline");
+
+    let r = client
+        .airlines
+        .get_airline(GetAirlineRequest {
+            id: airline.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.name, "Renamed Airline");
+
+    // delete
+    client
+        .airlines
+        .delete_airline(DeleteAirlineRequest {
+            id: airline.id.clone(),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .airlines
+        .list_airlines(ListAirlinesRequest {
+            show_deleted: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.airlines.is_empty());
+
+    // codes of deleted airlines can be reused
+    client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(example_airline()),
+        })
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn code_conflict(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(example_airline()),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(Airline {
+                icao: "TAX".to_string(),
+                ..example_airline()
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // invalid IATA code
+    let r = client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(Airline {
+                iata: "TAXI".to_string(),
+                icao: "TAX".to_string(),
+                ..example_airline()
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+}
@@ -1,5 +1,9 @@
+use flightmngr::audit::ACTOR;
+use flightmngr::idempotency::IDEMPOTENCY_KEY;
 use flightmngr::proto::flightmngr::{
-    Airport, CreateAirportRequest, DeleteAirportRequest, GetAirportRequest, ListAirportsRequest,
+    Airport, AirportClosure, CreateAirportRequest, DeleteAirportRequest, GetAirportRequest,
+    ListAirportClosuresRequest, ListAirportsRequest, RestoreAirportRequest,
+    ScheduleAirportClosureRequest,
 };
 use sqlx::{types::Uuid, PgPool};
 
@@ -14,6 +18,9 @@ fn example_airport_1() -> Airport {
         icao: "TSTT".to_string(),
         id: Default::default(),
         deleted: false,
+        timezone: "Europe/Rome".to_string(),
+        latitude: Some(41.8),
+        longitude: Some(12.25),
     }
 }
 
@@ -26,6 +33,9 @@ fn example_airport_2() -> Airport {
         icao: "TSST".to_string(),
         id: Default::default(),
         deleted: false,
+        timezone: "Europe/Rome".to_string(),
+        latitude: None,
+        longitude: None,
     }
 }
 
@@ -54,9 +64,10 @@ fn list_create_get(db: PgPool) {
         .into_inner();
 
     assert_ne!(r.id, "");
-    assert_eq!(r.deleted, false);
+    assert!(!r.deleted);
     assert_eq!(r.name, "Test Airport 1");
     assert_eq!(r.city, "Test City 1");
+    assert_eq!(r.timezone, "Europe/Rome");
 
     let id = r.id;
 
@@ -116,12 +127,15 @@ async fn delete(db: PgPool) {
         .into_inner();
 
     let id = r.id;
-    assert_eq!(r.deleted, false);
+    assert!(!r.deleted);
 
     // delete
     let _ = client
         .airports
-        .delete_airport(DeleteAirportRequest { id: id.clone() })
+        .delete_airport(DeleteAirportRequest {
+            id: id.clone(),
+            ..Default::default()
+        })
         .await
         .unwrap();
 
@@ -134,7 +148,7 @@ async fn delete(db: PgPool) {
         .into_inner();
 
     assert_eq!(r.id, id);
-    assert_eq!(r.deleted, true);
+    assert!(r.deleted);
 }
 
 #[sqlx::test]
@@ -191,7 +205,10 @@ async fn show_deleted(db: PgPool) {
     // delete first
     let _ = client
         .airports
-        .delete_airport(DeleteAirportRequest { id: id1.clone() })
+        .delete_airport(DeleteAirportRequest {
+            id: id1.clone(),
+            ..Default::default()
+        })
         .await
         .unwrap();
 
@@ -222,7 +239,10 @@ async fn show_deleted(db: PgPool) {
     // delete second
     let _ = client
         .airports
-        .delete_airport(DeleteAirportRequest { id: id2.clone() })
+        .delete_airport(DeleteAirportRequest {
+            id: id2.clone(),
+            ..Default::default()
+        })
         .await
         .unwrap();
 
@@ -250,3 +270,230 @@ async fn show_deleted(db: PgPool) {
     assert_eq!(r.airports.len(), 2);
     assert!(r.airports.iter().all(|a| a.deleted));
 }
+
+#[sqlx::test]
+async fn idempotent_create(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let request = || {
+        let mut r = tonic::Request::new(CreateAirportRequest {
+            airport: Some(example_airport_1()),
+        });
+        r.metadata_mut()
+            .insert(IDEMPOTENCY_KEY, "create-airport-1".parse().unwrap());
+        r
+    };
+
+    // create
+    let r1 = client
+        .airports
+        .create_airport(request())
+        .await
+        .unwrap()
+        .into_inner();
+
+    // retry
+    let r2 = client
+        .airports
+        .create_airport(request())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r1, r2);
+
+    // list
+    let r = client
+        .airports
+        .list_airports(ListAirportsRequest { show_deleted: true })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.airports.len(), 1);
+}
+
+#[sqlx::test]
+async fn restore(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    // create
+    let r = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(example_airport_1()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let id = r.id;
+
+    // delete
+    let _ = client
+        .airports
+        .delete_airport(DeleteAirportRequest {
+            id: id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // restore
+    let mut request = tonic::Request::new(RestoreAirportRequest { id: id.clone() });
+    request
+        .metadata_mut()
+        .insert(ACTOR, "ops@example.com".parse().unwrap());
+    let r = client
+        .airports
+        .restore_airport(request)
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.id, id);
+    assert!(!r.deleted);
+
+    let actors: Vec<(String, Option<String>)> = sqlx::query_as(
+        "select action, actor from audit_log where entity_id = $1::uuid order by timestamp",
+    )
+    .bind(&id)
+    .fetch_all(&db)
+    .await
+    .unwrap();
+
+    assert_eq!(
+        actors,
+        vec![
+            ("delete".to_string(), None),
+            ("restore".to_string(), Some("ops@example.com".to_string()))
+        ]
+    );
+
+    // delete and reuse the codes
+    let _ = client
+        .airports
+        .delete_airport(DeleteAirportRequest {
+            id: id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let _ = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(example_airport_1()),
+        })
+        .await
+        .unwrap();
+
+    // restore conflicting
+    let r = client
+        .airports
+        .restore_airport(RestoreAirportRequest { id: id.clone() })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    let r = client
+        .airports
+        .get_airport(GetAirportRequest { id })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.deleted);
+}
+
+#[sqlx::test]
+async fn closures(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(example_airport_1()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let closure = AirportClosure {
+        airport_id: airport.id.clone(),
+        start_time: Some(prost_types::Timestamp {
+            seconds: 1_800_000_000,
+            nanos: 0,
+        }),
+        end_time: Some(prost_types::Timestamp {
+            seconds: 1_800_003_600,
+            nanos: 0,
+        }),
+        reason: "runway works".to_string(),
+        ..Default::default()
+    };
+
+    let created = client
+        .airports
+        .schedule_airport_closure(ScheduleAirportClosureRequest {
+            closure: Some(closure.clone()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        created,
+        AirportClosure {
+            id: created.id.clone(),
+            ..closure.clone()
+        }
+    );
+
+    let r = client
+        .airports
+        .list_airport_closures(ListAirportClosuresRequest {
+            airport_id: airport.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.closures, vec![created]);
+
+    let r = client
+        .airports
+        .schedule_airport_closure(ScheduleAirportClosureRequest {
+            closure: Some(AirportClosure {
+                end_time: closure.start_time.clone(),
+                ..closure
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+}
+
+#[sqlx::test]
+async fn invalid_coordinates(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    for (latitude, longitude) in [
+        (Some(91.0), Some(0.0)),
+        (Some(0.0), Some(-181.0)),
+        (Some(0.0), None),
+    ] {
+        let r = client
+            .airports
+            .create_airport(CreateAirportRequest {
+                airport: Some(Airport {
+                    latitude,
+                    longitude,
+                    ..example_airport_1()
+                }),
+            })
+            .await;
+
+        assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+    }
+}
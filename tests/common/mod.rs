@@ -3,20 +3,28 @@ use tonic::transport::{Channel, Endpoint, Server, Uri};
 use tower::service_fn;
 
 use flightmngr::proto::flightmngr::{
+    aircraft_types_client::AircraftTypesClient, airlines_client::AirlinesClient,
     airports_client::AirportsClient, flights_client::FlightsClient, planes_client::PlanesClient,
+    routes_client::RoutesClient, schedules_client::SchedulesClient,
 };
+use flightmngr::rabbitmq::Rabbit;
 
 mod config;
 
+#[allow(dead_code)]
 pub struct Clients {
+    pub aircraft_types: AircraftTypesClient<Channel>,
+    pub airlines: AirlinesClient<Channel>,
     pub airports: AirportsClient<Channel>,
     pub planes: PlanesClient<Channel>,
     pub flights: FlightsClient<Channel>,
+    pub routes: RoutesClient<Channel>,
+    pub schedules: SchedulesClient<Channel>,
 }
 
-pub async fn make_test_client(db: PgPool) -> Result<Clients, Box<dyn std::error::Error>> {
+pub async fn connect_rabbitmq() -> Result<Rabbit, Box<dyn std::error::Error>> {
     let opt = envy::from_env::<config::Options>()?;
-    let rabbitmq = flightmngr::rabbitmq::Rabbit::new(
+    let rabbitmq = Rabbit::new(
         &opt.rabbitmq_host,
         opt.rabbitmq_port,
         &opt.rabbitmq_username,
@@ -26,6 +34,12 @@ pub async fn make_test_client(db: PgPool) -> Result<Clients, Box<dyn std::error:
     )
     .await?;
 
+    Ok(rabbitmq)
+}
+
+pub async fn make_test_client(db: PgPool) -> Result<Clients, Box<dyn std::error::Error>> {
+    let rabbitmq = connect_rabbitmq().await?;
+
     let (client, server) = tokio::io::duplex(1024);
 
     tokio::spawn(async move {
@@ -45,19 +59,20 @@ pub async fn make_test_client(db: PgPool) -> Result<Clients, Box<dyn std::error:
                 if let Some(client) = client {
                     Ok(client)
                 } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Client already taken",
-                    ))
+                    Err(std::io::Error::other("Client already taken"))
                 }
             }
         }))
         .await?;
 
     let clients = Clients {
+        aircraft_types: AircraftTypesClient::new(channel.clone()),
+        airlines: AirlinesClient::new(channel.clone()),
         airports: AirportsClient::new(channel.clone()),
         planes: PlanesClient::new(channel.clone()),
-        flights: FlightsClient::new(channel),
+        flights: FlightsClient::new(channel.clone()),
+        routes: RoutesClient::new(channel.clone()),
+        schedules: SchedulesClient::new(channel),
     };
 
     Ok(clients)
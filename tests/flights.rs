@@ -1,8 +1,26 @@
+use amqprs::{channel::BasicPublishArguments, channel::QueueDeleteArguments, BasicProperties};
+use flightmngr::db::Database;
+use flightmngr::idempotency::IDEMPOTENCY_KEY;
 use flightmngr::proto::flightmngr::{
-    Airport, CreateAirportRequest, CreateFlightRequest, CreatePlaneRequest, Flight,
-    GetFlightRequest, Plane,
+    flight_status_event::Event, AddCargoShipmentRequest, Airline, Airport, AirportDisruption,
+    BatchUpdateFlightsRequest, CabinClass, CabinClassCapacity, CabinConfiguration,
+    CancelAirportFlightsRequest, CargoShipment, ChangeSeatsRequest, Codeshare,
+    CreateAirlineRequest, CreateAirportRequest, CreateFlightRequest, CreatePlaneRequest,
+    DelayAirportFlightsRequest, DeletePlaneRequest, DiffFlightsRequest, FareBucket, Flight,
+    FlightCancelled, FlightDelayed, FlightEventRetracted, FlightGateArrival, FlightGateDeparture,
+    FlightPlaneChanged, FlightStatusEvent, GetFlightByNumberRequest, GetFlightRequest,
+    GetFlightSeatMapRequest, GetPlaneScheduleRequest, ListCargoShipmentsRequest,
+    ListFareBucketsRequest, ListFlightsRequest, ListImpactedFlightsRequest, Plane,
+    PlaneUnavailability, PropagateDelayRequest, PublishFlightsRequest, RemoveCargoShipmentRequest,
+    ScheduleMaintenanceRequest, SearchFlightsRequest, SeatAvailability, SetCodesharesRequest,
+    SetFareBucketsRequest, SetPlaneCabinConfigurationRequest, SetPlaneUnavailableRequest,
+    UnavailabilityKind, UpdateFlightRequest,
 };
-use sqlx::PgPool;
+use flightmngr::proto::flightmngr::{TicketCancelled, TicketCreated};
+use flightmngr::ticketing::TicketingConsumer;
+use prost::Message;
+use sqlx::{types::Uuid, PgPool};
+use std::time::{Duration, SystemTime};
 
 mod common;
 
@@ -20,6 +38,22 @@ fn default_flight(plane_id: String, origin_id: String, destination_id: String) -
         expected_arrival_time: Default::default(),
         departure_gate: Default::default(),
         arrival_gate: Default::default(),
+        version: Default::default(),
+        cabin_classes: Default::default(),
+        seat_availability: Default::default(),
+        booked_seats: Default::default(),
+        load_factor: Default::default(),
+        cargo_capacity_kg: Default::default(),
+        cargo_booked_kg: Default::default(),
+        cargo_remaining_kg: Default::default(),
+        lowest_fare: None,
+        carrier_id: Default::default(),
+        flight_number: Default::default(),
+        designator: Default::default(),
+        codeshares: Default::default(),
+        schedule_id: Default::default(),
+        draft: Default::default(),
+        published_at: Default::default(),
     }
 }
 
@@ -30,6 +64,16 @@ fn default_plane() -> Plane {
         model: "Test Model".to_string(),
         cabin_capacity: 200,
         cargo_capacity_kg: 1000,
+        registration: Default::default(),
+        icao_type: Default::default(),
+        manufacturer: Default::default(),
+        in_service_date: Default::default(),
+        retirement_date: Default::default(),
+        type_id: Default::default(),
+        cabin_capacity_override: None,
+        cargo_capacity_kg_override: None,
+        aircraft_type: None,
+        cabin_configuration: None,
     }
 }
 
@@ -42,9 +86,51 @@ fn default_airport() -> Airport {
         country: "Test Country".to_string(),
         iata: "TST".to_string(),
         icao: "TSTT".to_string(),
+        timezone: "UTC".to_string(),
+        latitude: None,
+        longitude: None,
     }
 }
 
+/// Create two airports, a plane and a flight between them.
+async fn setup_flight(client: &mut common::Clients) -> Flight {
+    let airport1 = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let airport2 = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(default_flight(plane.id, airport1.id, airport2.id)),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+}
+
 #[sqlx::test]
 async fn creation(db: PgPool) {
     let mut client = common::make_test_client(db).await.unwrap();
@@ -148,6 +234,7 @@ async fn search(db: PgPool) {
             origin_id: airport1.id.clone(),
             destination_id: airport2.id.clone(),
             departure_day: Some(Default::default()),
+            include_drafts: false,
         })
         .await
         .unwrap()
@@ -163,6 +250,7 @@ async fn search(db: PgPool) {
             origin_id: airport2.id.clone(),
             destination_id: airport1.id.clone(),
             departure_day: Some(Default::default()),
+            include_drafts: false,
         })
         .await
         .unwrap()
@@ -180,6 +268,7 @@ async fn search(db: PgPool) {
                 seconds: 2 * 24 * 3600,
                 nanos: 0,
             }),
+            include_drafts: false,
         })
         .await
         .unwrap()
@@ -187,3 +276,2501 @@ async fn search(db: PgPool) {
 
     assert_eq!(r.flights.len(), 0);
 }
+
+#[sqlx::test]
+async fn retract_event(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    // set a gate
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                    gate: "A1".to_string(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: Some(flight.version),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_gate.as_deref(), Some("A1"));
+    assert_eq!(r.version, flight.version + 1);
+    assert_eq!(r.status_events.len(), 1);
+    let event_id = r.status_events[0].id.clone();
+
+    // retract it
+    let retract = UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(Event::FlightEventRetracted(FlightEventRetracted {
+                event_id,
+                reason: "wrong gate".to_string(),
+            })),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+    let r = client
+        .flights
+        .update_flight(retract.clone())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_gate, None);
+    assert_eq!(r.status_events.len(), 2);
+
+    // cannot retract twice
+    let r = client.flights.update_flight(retract).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::NotFound));
+}
+
+/// Assert the current state projection of a flight matches the fold of its status events.
+async fn assert_projection_matches(db: &PgPool, flight_id: &str) {
+    let flight_id: Uuid = flight_id.parse().unwrap();
+    let row = |table: &str| format!("select s::text from {table} s where flight_id = $1");
+
+    let projected: String = sqlx::query_scalar(&row("flight_current_state"))
+        .bind(flight_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+    let folded: String = sqlx::query_scalar(&row("flight_state_fold"))
+        .bind(flight_id)
+        .fetch_one(db)
+        .await
+        .unwrap();
+
+    assert_eq!(projected, folded);
+}
+
+#[sqlx::test]
+async fn current_state_projection(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_projection_matches(&db, &flight.id).await;
+
+    let update = |event| UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(event),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+    let retract = |event_id: &str| {
+        update(Event::FlightEventRetracted(FlightEventRetracted {
+            event_id: event_id.to_string(),
+            reason: Default::default(),
+        }))
+    };
+    let later = |t: &Option<prost_types::Timestamp>| {
+        Some(prost_types::Timestamp {
+            seconds: t.as_ref().unwrap().seconds + 3600,
+            nanos: 0,
+        })
+    };
+
+    // delay
+    let r = client
+        .flights
+        .update_flight(update(Event::FlightDelayed(FlightDelayed {
+            departure_time: later(&flight.departure_time),
+            arrival_time: later(&flight.arrival_time),
+            reason: Default::default(),
+        })))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.expected_departure_time, later(&flight.departure_time));
+    assert_projection_matches(&db, &flight.id).await;
+    let delayed_id = r.status_events.last().unwrap().id.clone();
+
+    // gate changes
+    client
+        .flights
+        .update_flight(update(Event::FlightGateDeparture(FlightGateDeparture {
+            gate: "A1".to_string(),
+        })))
+        .await
+        .unwrap();
+    let r = client
+        .flights
+        .update_flight(update(Event::FlightGateArrival(FlightGateArrival {
+            gate: "B2".to_string(),
+        })))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_gate.as_deref(), Some("A1"));
+    assert_eq!(r.arrival_gate.as_deref(), Some("B2"));
+    assert_projection_matches(&db, &flight.id).await;
+
+    // plane change
+    let r = client
+        .flights
+        .update_flight(update(Event::FlightPlaneChanged(FlightPlaneChanged {
+            plane_id: plane.id.clone(),
+        })))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.plane_id, plane.id);
+    assert_projection_matches(&db, &flight.id).await;
+    let plane_changed_id = r.status_events.last().unwrap().id.clone();
+
+    // retractions
+    let r = client
+        .flights
+        .update_flight(retract(&delayed_id))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.expected_departure_time, None);
+    assert_projection_matches(&db, &flight.id).await;
+
+    let r = client
+        .flights
+        .update_flight(retract(&plane_changed_id))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.plane_id, flight.plane_id);
+    assert_projection_matches(&db, &flight.id).await;
+
+    // cancel
+    let r = client
+        .flights
+        .update_flight(update(Event::FlightCancelled(FlightCancelled {
+            reason: Default::default(),
+        })))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.is_cancelled);
+    assert_eq!(r.version, flight.version + 7);
+    assert_projection_matches(&db, &flight.id).await;
+}
+
+#[sqlx::test]
+async fn rebuild_current_state(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+    let flight = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                    gate: "A1".to_string(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let draft = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(Flight {
+                draft: true,
+                ..default_flight(
+                    flight.plane_id.clone(),
+                    flight.origin_id.clone(),
+                    flight.destination_id.clone(),
+                )
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // flights without a projection are an error rather than silently left out
+    sqlx::query("delete from flight_current_state")
+        .execute(&db)
+        .await
+        .unwrap();
+    let r = client
+        .flights
+        .publish_flights(PublishFlightsRequest {
+            flight_ids: vec![draft.id.clone()],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::Internal));
+
+    // rebuild
+    let count = flightmngr::flights::rebuild_current_state(&Database::from_pool(db.clone()))
+        .await
+        .unwrap();
+
+    assert_eq!(count, 2);
+    assert_projection_matches(&db, &flight.id).await;
+    assert_projection_matches(&db, &draft.id).await;
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r, flight);
+}
+
+#[sqlx::test]
+async fn update_version_mismatch(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    let update = |gate: &str| UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                gate: gate.to_string(),
+            })),
+            ..Default::default()
+        }),
+        expected_version: Some(flight.version),
+    };
+
+    // first update wins
+    let r = client.flights.update_flight(update("A1")).await;
+
+    assert!(r.is_ok());
+
+    // second update based on the same version is rejected
+    let r = client.flights.update_flight(update("B2")).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::Aborted));
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_gate.as_deref(), Some("A1"));
+}
+
+#[sqlx::test]
+async fn idempotent_create(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    let request = |seconds| {
+        let time = Some(prost_types::Timestamp { seconds, nanos: 0 });
+        let mut r = tonic::Request::new(CreateFlightRequest {
+            flight: Some(Flight {
+                departure_time: time.clone(),
+                arrival_time: time,
+                ..default_flight(
+                    flight.plane_id.clone(),
+                    flight.origin_id.clone(),
+                    flight.destination_id.clone(),
+                )
+            }),
+        });
+        r.metadata_mut()
+            .insert(IDEMPOTENCY_KEY, "create-flight-1".parse().unwrap());
+        r
+    };
+
+    // create
+    let r1 = client
+        .flights
+        .create_flight(request(3600))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // retry
+    let r2 = client
+        .flights
+        .create_flight(request(3600))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r1, r2);
+
+    // same key for a different request
+    let r = client.flights.create_flight(request(7200)).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // list
+    let r = client
+        .flights
+        .list_flights(ListFlightsRequest {
+            include_cancelled: false,
+            include_drafts: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 2);
+}
+
+#[sqlx::test]
+async fn idempotent_update(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    let request = |gate: &str| {
+        let mut r = tonic::Request::new(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                    gate: gate.to_string(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        });
+        r.metadata_mut()
+            .insert(IDEMPOTENCY_KEY, "update-flight-1".parse().unwrap());
+        r
+    };
+
+    // update
+    let r1 = client
+        .flights
+        .update_flight(request("A1"))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // retry
+    let r2 = client
+        .flights
+        .update_flight(request("A1"))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r1, r2);
+
+    // same key for a different request
+    let r = client.flights.update_flight(request("B2")).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // the event was recorded once
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.version, flight.version + 1);
+    assert_eq!(r.departure_gate.as_deref(), Some("A1"));
+}
+
+#[sqlx::test]
+async fn batch_update(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight1 = setup_flight(&mut client).await;
+    let flight2 = setup_flight(&mut client).await;
+
+    let gate = |id: &str, gate: &str| UpdateFlightRequest {
+        id: id.to_string(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                gate: gate.to_string(),
+            })),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+
+    // apply all updates
+    let r = client
+        .flights
+        .batch_update_flights(BatchUpdateFlightsRequest {
+            updates: vec![
+                gate(&flight1.id, "A1"),
+                gate(&flight2.id, "B1"),
+                gate(&flight1.id, "A2"),
+            ],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 2);
+    assert_eq!(r.flights[0].id, flight1.id);
+    assert_eq!(r.flights[0].departure_gate.as_deref(), Some("A2"));
+    assert_eq!(r.flights[1].id, flight2.id);
+    assert_eq!(r.flights[1].departure_gate.as_deref(), Some("B1"));
+
+    // one failing update rolls back the whole batch
+    let r = client
+        .flights
+        .batch_update_flights(BatchUpdateFlightsRequest {
+            updates: vec![
+                gate(&flight1.id, "C1"),
+                gate(&Uuid::default().to_string(), "C2"),
+            ],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::NotFound));
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight1.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_gate.as_deref(), Some("A2"));
+}
+
+#[sqlx::test]
+async fn airport_disruption(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    let disruption = |airport_id: &str, dry_run| AirportDisruption {
+        airport_id: airport_id.to_string(),
+        window_start: Some(prost_types::Timestamp {
+            seconds: -3600,
+            nanos: 0,
+        }),
+        window_end: Some(prost_types::Timestamp {
+            seconds: 3600,
+            nanos: 0,
+        }),
+        reason: "storm".to_string(),
+        dry_run,
+    };
+
+    // dry run previews the delay
+    let r = client
+        .flights
+        .delay_airport_flights(DelayAirportFlightsRequest {
+            disruption: Some(disruption(&flight.origin_id, true)),
+            delay_minutes: 30,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+    assert_eq!(
+        r.flights[0].expected_departure_time,
+        Some(prost_types::Timestamp {
+            seconds: 30 * 60,
+            nanos: 0
+        })
+    );
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r, flight);
+
+    // cancel flights arriving at the destination
+    let r = client
+        .flights
+        .cancel_airport_flights(CancelAirportFlightsRequest {
+            disruption: Some(disruption(&flight.destination_id, false)),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+    assert!(r.flights[0].is_cancelled);
+
+    // cancelled flights are no longer affected
+    let r = client
+        .flights
+        .cancel_airport_flights(CancelAirportFlightsRequest {
+            disruption: Some(disruption(&flight.destination_id, false)),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 0);
+}
+
+#[sqlx::test]
+async fn propagate_delay(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let slot = |n: i64| {
+        Some(prost_types::Timestamp {
+            seconds: n * 1800,
+            nanos: 0,
+        })
+    };
+
+    // rotation of the same plane with half hour slots: 0-2, 3-5, 10-12
+    let flight1 = setup_flight(&mut client).await;
+    let mut flight2 = default_flight(
+        flight1.plane_id.clone(),
+        flight1.destination_id.clone(),
+        flight1.origin_id.clone(),
+    );
+    flight2.departure_time = slot(3);
+    flight2.arrival_time = slot(5);
+    let flight2 = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(flight2),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut flight3 = default_flight(
+        flight1.plane_id.clone(),
+        flight1.origin_id.clone(),
+        flight1.destination_id.clone(),
+    );
+    flight3.departure_time = slot(10);
+    flight3.arrival_time = slot(12);
+    let _ = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(flight3),
+        })
+        .await
+        .unwrap();
+
+    // delay the first flight to arrive at slot 4
+    let _ = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight1.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightDelayed(FlightDelayed {
+                    departure_time: slot(2),
+                    arrival_time: slot(4),
+                    reason: Default::default(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap();
+
+    // only the second flight is affected with a one hour turnaround
+    let request = |apply| PropagateDelayRequest {
+        flight_id: flight1.id.clone(),
+        min_turnaround_minutes: Some(60),
+        apply,
+    };
+    let r = client
+        .flights
+        .propagate_delay(request(false))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.delays.len(), 1);
+    assert_eq!(r.delays[0].flight_id, flight2.id);
+    assert_eq!(r.delays[0].departure_time, slot(6));
+    assert_eq!(r.delays[0].arrival_time, slot(8));
+
+    // apply the proposal
+    let r = client
+        .flights
+        .propagate_delay(request(true))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.delays.len(), 1);
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight2.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.expected_departure_time, slot(6));
+    assert_eq!(r.expected_arrival_time, slot(8));
+}
+
+#[sqlx::test]
+async fn propagate_delay_after_plane_change(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let slot = |n: i64| {
+        Some(prost_types::Timestamp {
+            seconds: n * 1800,
+            nanos: 0,
+        })
+    };
+
+    // the first flight moves from its plane to another one, both flying again at slot 3
+    let flight = setup_flight(&mut client).await;
+    let replacement = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let mut next_flights = Vec::new();
+    for plane_id in [&flight.plane_id, &replacement.id] {
+        let next = client
+            .flights
+            .create_flight(CreateFlightRequest {
+                flight: Some(Flight {
+                    departure_time: slot(3),
+                    arrival_time: slot(5),
+                    ..default_flight(
+                        plane_id.clone(),
+                        flight.destination_id.clone(),
+                        flight.origin_id.clone(),
+                    )
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        next_flights.push(next);
+    }
+
+    let update = |event| UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(event),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+    client
+        .flights
+        .update_flight(update(Event::FlightPlaneChanged(FlightPlaneChanged {
+            plane_id: replacement.id.clone(),
+        })))
+        .await
+        .unwrap();
+    client
+        .flights
+        .update_flight(update(Event::FlightDelayed(FlightDelayed {
+            departure_time: slot(2),
+            arrival_time: slot(4),
+            reason: Default::default(),
+        })))
+        .await
+        .unwrap();
+
+    // only the next flight of the plane operating the delayed flight is affected
+    let r = client
+        .flights
+        .propagate_delay(PropagateDelayRequest {
+            flight_id: flight.id.clone(),
+            min_turnaround_minutes: Some(30),
+            apply: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.delays.len(), 1);
+    assert_eq!(r.delays[0].flight_id, next_flights[1].id);
+    assert_eq!(r.delays[0].departure_time, slot(5));
+}
+
+#[sqlx::test]
+async fn plane_unavailable(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let hour = |n: i64| {
+        Some(prost_types::Timestamp {
+            seconds: n * 3600,
+            nanos: 0,
+        })
+    };
+
+    let flight = setup_flight(&mut client).await;
+    let replacement = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    // planes retired on the day of the flight or in service only from the next day
+    for (in_service_date, retirement_date) in [(None, Some(0)), (Some(86400), None)] {
+        let date = |seconds| prost_types::Timestamp { seconds, nanos: 0 };
+        let _ = client
+            .planes
+            .create_plane(CreatePlaneRequest {
+                plane: Some(Plane {
+                    in_service_date: in_service_date.map(date),
+                    retirement_date: retirement_date.map(date),
+                    ..default_plane()
+                }),
+            })
+            .await
+            .unwrap();
+    }
+
+    // ground the plane indefinitely
+    let unavailability = client
+        .planes
+        .set_plane_unavailable(SetPlaneUnavailableRequest {
+            unavailability: Some(PlaneUnavailability {
+                plane_id: flight.plane_id.clone(),
+                kind: UnavailabilityKind::Aog.into(),
+                start_time: hour(-1),
+                end_time: None,
+                reason: "bird strike".to_string(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // the plane can no longer be scheduled
+    let mut next = default_flight(
+        flight.plane_id.clone(),
+        flight.destination_id.clone(),
+        flight.origin_id.clone(),
+    );
+    next.departure_time = hour(2);
+    next.arrival_time = hour(4);
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest { flight: Some(next) })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    // the existing flight is impacted and can be moved to the other plane
+    let r = client
+        .flights
+        .list_impacted_flights(ListImpactedFlightsRequest {
+            unavailability_id: unavailability.id,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+    assert_eq!(r.flights[0].flight.as_ref().unwrap().id, flight.id);
+    assert_eq!(
+        r.flights[0].replacement_plane_ids,
+        vec![replacement.id.clone()]
+    );
+
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightPlaneChanged(FlightPlaneChanged {
+                    plane_id: replacement.id.clone(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.plane_id, replacement.id);
+    let plane_changed_id = r
+        .status_events
+        .iter()
+        .find(|e| matches!(e.event, Some(Event::FlightPlaneChanged(_))))
+        .unwrap()
+        .id
+        .clone();
+
+    // moving it back is rejected
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightPlaneChanged(FlightPlaneChanged {
+                    plane_id: flight.plane_id.clone(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+    // and so is retracting the plane change
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightEventRetracted(FlightEventRetracted {
+                    event_id: plane_changed_id,
+                    reason: Default::default(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+}
+
+#[sqlx::test]
+async fn maintenance_schedule(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let hour = |n: i64| {
+        Some(prost_types::Timestamp {
+            seconds: n * 3600,
+            nanos: 0,
+        })
+    };
+
+    let flight = setup_flight(&mut client).await;
+
+    // plan a check at the origin airport after the flight
+    let maintenance = client
+        .planes
+        .schedule_maintenance(ScheduleMaintenanceRequest {
+            plane_id: flight.plane_id.clone(),
+            start_time: hour(1),
+            end_time: hour(3),
+            location_id: flight.origin_id.clone(),
+            reason: "A check".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(maintenance.kind, i32::from(UnavailabilityKind::Maintenance));
+    assert_eq!(maintenance.location_id, Some(flight.origin_id.clone()));
+
+    // flights cannot overlap the check
+    let mut next = default_flight(
+        flight.plane_id.clone(),
+        flight.destination_id.clone(),
+        flight.origin_id.clone(),
+    );
+    next.departure_time = hour(2);
+    next.arrival_time = hour(4);
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest { flight: Some(next) })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    // the schedule lists both the flight and the check
+    let r = client
+        .planes
+        .get_plane_schedule(GetPlaneScheduleRequest {
+            plane_id: flight.plane_id.clone(),
+            from: hour(-1),
+            to: hour(4),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+    assert_eq!(r.flights[0].id, flight.id);
+    assert_eq!(r.unavailabilities, vec![maintenance]);
+
+    // and only the check later on
+    let r = client
+        .planes
+        .get_plane_schedule(GetPlaneScheduleRequest {
+            plane_id: flight.plane_id.clone(),
+            from: hour(2),
+            to: hour(4),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 0);
+    assert_eq!(r.unavailabilities.len(), 1);
+}
+
+#[sqlx::test]
+async fn delete_plane_with_future_flights(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let past = setup_flight(&mut client).await;
+    let tomorrow = time::OffsetDateTime::now_utc().unix_timestamp() + 86400;
+    let mut future = default_flight(
+        past.plane_id.clone(),
+        past.destination_id.clone(),
+        past.origin_id.clone(),
+    );
+    future.departure_time = Some(prost_types::Timestamp {
+        seconds: tomorrow,
+        nanos: 0,
+    });
+    future.arrival_time = Some(prost_types::Timestamp {
+        seconds: tomorrow + 3600,
+        nanos: 0,
+    });
+    let future = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(future),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // deletion is refused while the plane has future flights
+    let r = client
+        .planes
+        .delete_plane(DeletePlaneRequest {
+            id: past.plane_id.clone(),
+            ..Default::default()
+        })
+        .await;
+
+    assert!(r.is_err_and(
+        |e| e.code() == tonic::Code::FailedPrecondition && e.message().contains(&future.id)
+    ));
+
+    // unless they are cancelled
+    let _ = client
+        .planes
+        .delete_plane(DeletePlaneRequest {
+            id: past.plane_id.clone(),
+            cancel_future_flights: true,
+            cancellation_reason: "plane retired".to_string(),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: future.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.is_cancelled);
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: past.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(!r.is_cancelled);
+}
+
+#[sqlx::test]
+async fn cabin_follows_plane(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    assert_eq!(
+        flight.cabin_classes,
+        vec![CabinClassCapacity {
+            class: CabinClass::Economy.into(),
+            capacity: 200,
+        }]
+    );
+
+    // swap to a plane with a business cabin
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let classes = vec![
+        CabinClassCapacity {
+            class: CabinClass::Business.into(),
+            capacity: 20,
+        },
+        CabinClassCapacity {
+            class: CabinClass::Economy.into(),
+            capacity: 180,
+        },
+    ];
+    let _ = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: plane.id.clone(),
+            configuration: Some(CabinConfiguration {
+                classes: classes.clone(),
+                seats: Default::default(),
+            }),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightPlaneChanged(FlightPlaneChanged {
+                    plane_id: plane.id.clone(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cabin_classes, classes);
+
+    let r = client
+        .flights
+        .get_flight_seat_map(GetFlightSeatMapRequest {
+            flight_id: flight.id,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.classes, classes);
+}
+
+#[sqlx::test]
+async fn reconfigure_cabin_with_seats_sold(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let past = setup_flight(&mut client).await;
+    let tomorrow = time::OffsetDateTime::now_utc().unix_timestamp() + 86400;
+    let mut future = default_flight(
+        past.plane_id.clone(),
+        past.destination_id.clone(),
+        past.origin_id.clone(),
+    );
+    future.departure_time = Some(prost_types::Timestamp {
+        seconds: tomorrow,
+        nanos: 0,
+    });
+    future.arrival_time = Some(prost_types::Timestamp {
+        seconds: tomorrow + 3600,
+        nanos: 0,
+    });
+    let future = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(future),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    for (flight, seats) in [(&past, 190), (&future, 150)] {
+        let _ = client
+            .flights
+            .reserve_seats(ChangeSeatsRequest {
+                flight_id: flight.id.clone(),
+                class: CabinClass::Economy.into(),
+                seats,
+            })
+            .await
+            .unwrap();
+    }
+
+    let configuration = |business, economy| CabinConfiguration {
+        classes: vec![
+            CabinClassCapacity {
+                class: CabinClass::Business.into(),
+                capacity: business,
+            },
+            CabinClassCapacity {
+                class: CabinClass::Economy.into(),
+                capacity: economy,
+            },
+        ],
+        seats: Default::default(),
+    };
+
+    // the economy seats sold on the future flight would no longer fit
+    let r = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: past.plane_id.clone(),
+            configuration: Some(configuration(100, 100)),
+        })
+        .await;
+
+    assert!(r.is_err_and(
+        |e| e.code() == tonic::Code::FailedPrecondition && e.message().contains(&future.id)
+    ));
+
+    // past flights are not checked
+    let _ = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: past.plane_id.clone(),
+            configuration: Some(configuration(20, 180)),
+        })
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn seat_inventory(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+    let seats = |seats| ChangeSeatsRequest {
+        flight_id: flight.id.clone(),
+        class: CabinClass::Economy.into(),
+        seats,
+    };
+
+    // reserve
+    let r = client
+        .flights
+        .reserve_seats(seats(150))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        r,
+        SeatAvailability {
+            class: CabinClass::Economy.into(),
+            capacity: 200,
+            overbooking_allowance: 0,
+            sold: 150,
+            available: 50,
+        }
+    );
+
+    // reserve more than available
+    let r = client.flights.reserve_seats(seats(60)).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    // class missing from the plane
+    let r = client
+        .flights
+        .reserve_seats(ChangeSeatsRequest {
+            class: CabinClass::First.into(),
+            ..seats(1)
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    // overbook
+    let _ = client
+        .flights
+        .set_overbooking_allowance(seats(10))
+        .await
+        .unwrap();
+    let r = client
+        .flights
+        .reserve_seats(seats(60))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.sold, 210);
+    assert_eq!(r.available, 0);
+
+    // release
+    let r = client.flights.release_seats(seats(300)).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    let _ = client.flights.release_seats(seats(5)).await.unwrap();
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.seat_availability.len(), 1);
+    assert_eq!(r.seat_availability[0].sold, 205);
+    assert_eq!(r.seat_availability[0].available, 5);
+
+    // the seats sold do not fit on a smaller plane
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                cabin_capacity: 100,
+                ..default_plane()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let r = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightPlaneChanged(FlightPlaneChanged {
+                    plane_id: plane.id,
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+}
+
+#[sqlx::test]
+async fn ticket_bookings(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+    let _ = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: flight.plane_id.clone(),
+            configuration: Some(CabinConfiguration {
+                classes: vec![
+                    CabinClassCapacity {
+                        class: CabinClass::Business.into(),
+                        capacity: 20,
+                    },
+                    CabinClassCapacity {
+                        class: CabinClass::Economy.into(),
+                        capacity: 180,
+                    },
+                ],
+                seats: Default::default(),
+            }),
+        })
+        .await
+        .unwrap();
+
+    // a message id recorded long ago, to be forgotten
+    sqlx::query(
+        "insert into processed_messages (message_id, processed_at) \
+        values ('0', now() - interval '30 days')",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    // consume from a dedicated exchange so that tests do not see each other's tickets
+    let rabbitmq = common::connect_rabbitmq().await.unwrap();
+    let suffix = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let exchange = format!("ticketing-test-{suffix}");
+    let queue = format!("{exchange}.tickets");
+    TicketingConsumer::start(
+        Database::from_pool(db.clone()),
+        &rabbitmq,
+        &exchange,
+        &queue,
+    )
+    .await
+    .unwrap();
+
+    let channel = rabbitmq.open_channel().await.unwrap();
+    let publish = |message_id: &str, message_type: &str, content: Vec<u8>| {
+        let properties = BasicProperties::default()
+            .with_message_id(message_id)
+            .with_message_type(message_type)
+            .finish();
+        channel.basic_publish(
+            properties,
+            content,
+            BasicPublishArguments::new(&exchange, ""),
+        )
+    };
+    let created = |ticket_id: &str, flight_id: &str, class: CabinClass| {
+        TicketCreated {
+            ticket_id: ticket_id.to_string(),
+            flight_id: flight_id.to_string(),
+            class: class.into(),
+        }
+        .encode_to_vec()
+    };
+    let cancelled = |ticket_id: &str, flight_id: &str| {
+        TicketCancelled {
+            ticket_id: ticket_id.to_string(),
+            flight_id: flight_id.to_string(),
+            class: CabinClass::Economy.into(),
+        }
+        .encode_to_vec()
+    };
+    let economy = CabinClass::Economy;
+
+    publish(
+        "1",
+        "flightmngr.TicketCreated",
+        created("a", &flight.id, economy),
+    )
+    .await
+    .unwrap();
+    // redelivery
+    publish(
+        "1",
+        "flightmngr.TicketCreated",
+        created("a", &flight.id, economy),
+    )
+    .await
+    .unwrap();
+    // undecodable
+    publish("2", "flightmngr.TicketCreated", vec![0xff])
+        .await
+        .unwrap();
+    // unknown flight
+    let unknown = Uuid::default().to_string();
+    publish(
+        "3",
+        "flightmngr.TicketCreated",
+        created("b", &unknown, economy),
+    )
+    .await
+    .unwrap();
+    let business = CabinClass::Business;
+    publish(
+        "4",
+        "flightmngr.TicketCreated",
+        created("c", &flight.id, business),
+    )
+    .await
+    .unwrap();
+    publish(
+        "5",
+        "flightmngr.TicketCreated",
+        created("d", &flight.id, economy),
+    )
+    .await
+    .unwrap();
+    // class not on the plane
+    publish(
+        "8",
+        "flightmngr.TicketCreated",
+        created("e", &flight.id, CabinClass::First),
+    )
+    .await
+    .unwrap();
+    publish(
+        "6",
+        "flightmngr.TicketCancelled",
+        cancelled("d", &flight.id),
+    )
+    .await
+    .unwrap();
+    publish("7", "flightmngr.TicketCancelled", cancelled("b", &unknown))
+        .await
+        .unwrap();
+
+    // wait for the valid messages to be applied
+    let mut processed = 0;
+    for _ in 0..50 {
+        processed = sqlx::query_scalar::<_, i64>("select count(*) from processed_messages")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        if processed == 4 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(processed, 4);
+    let expired = sqlx::query_scalar::<_, i64>(
+        "select count(*) from processed_messages where message_id = '0'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(expired, 0);
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let sold = |class| {
+        r.seat_availability
+            .iter()
+            .find(|a| a.class() == class)
+            .map(|a| a.sold)
+    };
+
+    assert_eq!(sold(economy), Some(1));
+    assert_eq!(sold(business), Some(1));
+    assert_eq!(r.booked_seats, 2);
+    assert_eq!(r.load_factor, 0.01);
+
+    // tickets and reservations count the same seats
+    let r = client
+        .flights
+        .reserve_seats(ChangeSeatsRequest {
+            flight_id: flight.id.clone(),
+            class: economy.into(),
+            seats: 2,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.sold, 3);
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest { id: flight.id })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.booked_seats, 4);
+    assert_eq!(r.load_factor, 0.02);
+
+    // the invalid messages were dead-lettered
+    let _ = channel
+        .queue_delete(QueueDeleteArguments::new(&queue))
+        .await
+        .unwrap();
+    let dead_lettered = channel
+        .queue_delete(QueueDeleteArguments::new(&format!("{queue}.dead-letter")))
+        .await
+        .unwrap();
+
+    assert_eq!(dead_lettered, Some(4));
+}
+
+#[sqlx::test]
+async fn cargo_shipments(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    assert_eq!(flight.cargo_capacity_kg, 1000);
+    assert_eq!(flight.cargo_remaining_kg, 1000);
+
+    let shipment = |shipment_id: &str, weight_kg| CargoShipment {
+        flight_id: flight.id.clone(),
+        shipment_id: shipment_id.to_string(),
+        weight_kg,
+        origin_id: flight.origin_id.clone(),
+        destination_id: flight.destination_id.clone(),
+    };
+
+    // add
+    let r = client
+        .flights
+        .add_cargo_shipment(AddCargoShipmentRequest {
+            shipment: Some(shipment("S1", 600)),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cargo_booked_kg, 600);
+    assert_eq!(r.cargo_remaining_kg, 400);
+    // shipments do not change the status of the flight
+    assert_eq!(r.version, flight.version);
+
+    // duplicate
+    let r = client
+        .flights
+        .add_cargo_shipment(AddCargoShipmentRequest {
+            shipment: Some(shipment("S1", 100)),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // over capacity
+    let r = client
+        .flights
+        .add_cargo_shipment(AddCargoShipmentRequest {
+            shipment: Some(shipment("S2", 500)),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    let _ = client
+        .flights
+        .add_cargo_shipment(AddCargoShipmentRequest {
+            shipment: Some(shipment("S2", 400)),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .list_cargo_shipments(ListCargoShipmentsRequest {
+            flight_id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.shipments, vec![shipment("S1", 600), shipment("S2", 400)]);
+
+    // the cargo does not fit on a smaller plane
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                cargo_capacity_kg: 800,
+                ..default_plane()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let change_plane = UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(Event::FlightPlaneChanged(FlightPlaneChanged {
+                plane_id: plane.id,
+            })),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+
+    let r = client.flights.update_flight(change_plane.clone()).await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    // remove
+    let remove = || {
+        let mut r = tonic::Request::new(RemoveCargoShipmentRequest {
+            flight_id: flight.id.clone(),
+            shipment_id: "S1".to_string(),
+        });
+        r.metadata_mut()
+            .insert(IDEMPOTENCY_KEY, "remove-shipment-1".parse().unwrap());
+        r
+    };
+    let r = client
+        .flights
+        .remove_cargo_shipment(remove())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cargo_booked_kg, 400);
+    assert_eq!(r.version, flight.version);
+
+    // retry
+    let r2 = client
+        .flights
+        .remove_cargo_shipment(remove())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r2, r);
+
+    let r = client
+        .flights
+        .update_flight(change_plane)
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cargo_capacity_kg, 800);
+    assert_eq!(r.cargo_remaining_kg, 400);
+}
+
+#[sqlx::test]
+async fn fare_buckets(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let flight = setup_flight(&mut client).await;
+
+    let bucket = |booking_class: &str, cabin: CabinClass, base_price, seats_allotted| FareBucket {
+        booking_class: booking_class.to_string(),
+        cabin: cabin.into(),
+        currency: "EUR".to_string(),
+        base_price,
+        seats_allotted,
+        seats_available: 0,
+    };
+
+    // mixed currencies
+    let r = client
+        .flights
+        .set_fare_buckets(SetFareBucketsRequest {
+            flight_id: flight.id.clone(),
+            buckets: vec![
+                bucket("Y", CabinClass::Economy, 30000, 150),
+                FareBucket {
+                    currency: "USD".to_string(),
+                    ..bucket("Q", CabinClass::Economy, 15000, 50)
+                },
+            ],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // set
+    let r = client
+        .flights
+        .set_fare_buckets(SetFareBucketsRequest {
+            flight_id: flight.id.clone(),
+            buckets: vec![
+                bucket("J", CabinClass::Business, 90000, 20),
+                bucket("Y", CabinClass::Economy, 30000, 150),
+                bucket("Q", CabinClass::Economy, 15000, 50),
+                bucket("X", CabinClass::Economy, 9000, 0),
+            ],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // the plane has no business cabin
+    assert_eq!(
+        r.buckets
+            .iter()
+            .map(|b| (b.booking_class.as_str(), b.seats_available))
+            .collect::<Vec<_>>(),
+        vec![("X", 0), ("Q", 50), ("Y", 150), ("J", 0)]
+    );
+
+    let r = client
+        .flights
+        .search_flights(SearchFlightsRequest {
+            origin_id: flight.origin_id.clone(),
+            destination_id: flight.destination_id.clone(),
+            departure_day: Some(Default::default()),
+            include_drafts: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        r.flights[0].lowest_fare,
+        Some(FareBucket {
+            seats_available: 50,
+            ..bucket("Q", CabinClass::Economy, 15000, 50)
+        })
+    );
+
+    // sold out
+    let _ = client
+        .flights
+        .reserve_seats(ChangeSeatsRequest {
+            flight_id: flight.id.clone(),
+            class: CabinClass::Economy.into(),
+            seats: 200,
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .list_fare_buckets(ListFareBucketsRequest {
+            flight_id: flight.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.buckets.iter().all(|b| b.seats_available == 0));
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest { id: flight.id })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.lowest_fare, None);
+}
+
+#[sqlx::test]
+async fn flight_numbers(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airline = client
+        .airlines
+        .create_airline(CreateAirlineRequest {
+            airline: Some(Airline {
+                iata: "TA".to_string(),
+                icao: "TAL".to_string(),
+                name: "Test Airline".to_string(),
+                callsign: "TEST".to_string(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let origin = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(Airport {
+                timezone: "Asia/Tokyo".to_string(),
+                ..default_airport()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let destination = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut planes = vec![];
+    for _ in 0..2 {
+        let plane = client
+            .planes
+            .create_plane(CreatePlaneRequest {
+                plane: Some(default_plane()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        planes.push(plane);
+    }
+
+    // 20:00 UTC on 1970-01-01 is already 1970-01-02 in Tokyo
+    let numbered_flight = |plane_id: &str, flight_number: &str| Flight {
+        departure_time: Some(prost_types::Timestamp {
+            seconds: 20 * 3600,
+            nanos: 0,
+        }),
+        arrival_time: Some(prost_types::Timestamp {
+            seconds: 22 * 3600,
+            nanos: 0,
+        }),
+        carrier_id: airline.id.clone(),
+        flight_number: flight_number.to_string(),
+        ..default_flight(
+            plane_id.to_string(),
+            origin.id.clone(),
+            destination.id.clone(),
+        )
+    };
+
+    // invalid flight number
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(numbered_flight(&planes[0].id, "12345")),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // carrier without flight number
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(numbered_flight(&planes[0].id, "")),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let flight = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(numbered_flight(&planes[0].id, "123a")),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(flight.carrier_id, airline.id);
+    assert_eq!(flight.flight_number, "123A");
+    assert_eq!(flight.designator, "TA123A");
+
+    // same number on the same local date
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(numbered_flight(&planes[1].id, "123A")),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // lookup by the local departure date
+    let r = client
+        .flights
+        .get_flight_by_number(GetFlightByNumberRequest {
+            carrier: "tal".to_string(),
+            flight_number: "123A".to_string(),
+            date: Some(prost_types::Timestamp {
+                seconds: 24 * 3600,
+                nanos: 0,
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r, flight);
+
+    let r = client
+        .flights
+        .get_flight_by_number(GetFlightByNumberRequest {
+            carrier: "TA".to_string(),
+            flight_number: "123A".to_string(),
+            date: Some(prost_types::Timestamp {
+                seconds: 0,
+                nanos: 0,
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::NotFound));
+
+    // a cancelled flight keeps its number for the date
+    let cancelled = client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flight.id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightCancelled(FlightCancelled {
+                    reason: Default::default(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(numbered_flight(&planes[1].id, "123A")),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    let r = client
+        .flights
+        .get_flight_by_number(GetFlightByNumberRequest {
+            carrier: "TA".to_string(),
+            flight_number: "123A".to_string(),
+            date: Some(prost_types::Timestamp {
+                seconds: 24 * 3600,
+                nanos: 0,
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r, cancelled);
+}
+
+#[sqlx::test]
+async fn codeshares(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let mut airlines = vec![];
+    for (iata, icao) in [("TA", "TAL"), ("TB", "TBL"), ("TC", "TCL")] {
+        let airline = client
+            .airlines
+            .create_airline(CreateAirlineRequest {
+                airline: Some(Airline {
+                    iata: iata.to_string(),
+                    icao: icao.to_string(),
+                    name: "Test Airline".to_string(),
+                    callsign: "TEST".to_string(),
+                    ..Default::default()
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        airlines.push(airline);
+    }
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut flights = vec![];
+    for flight_number in ["100", "200"] {
+        let plane = client
+            .planes
+            .create_plane(CreatePlaneRequest {
+                plane: Some(default_plane()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let flight = client
+            .flights
+            .create_flight(CreateFlightRequest {
+                flight: Some(Flight {
+                    carrier_id: airlines[0].id.clone(),
+                    flight_number: flight_number.to_string(),
+                    ..default_flight(plane.id, airport.id.clone(), airport.id.clone())
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        flights.push(flight);
+    }
+
+    let codeshare = |airline: &Airline, flight_number: &str| Codeshare {
+        carrier_id: airline.id.clone(),
+        flight_number: flight_number.to_string(),
+        designator: Default::default(),
+    };
+
+    // the operating airline cannot market its own flight
+    let r = client
+        .flights
+        .set_codeshares(SetCodesharesRequest {
+            flight_id: flights[0].id.clone(),
+            codeshares: vec![codeshare(&airlines[0], "5000")],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let r = client
+        .flights
+        .set_codeshares(SetCodesharesRequest {
+            flight_id: flights[0].id.clone(),
+            codeshares: vec![
+                codeshare(&airlines[1], "5000"),
+                codeshare(&airlines[2], "7000"),
+            ],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        r.codeshares
+            .iter()
+            .map(|c| c.designator.as_str())
+            .collect::<Vec<_>>(),
+        vec!["TB5000", "TC7000"]
+    );
+
+    // setting the same codeshares again is allowed
+    client
+        .flights
+        .set_codeshares(SetCodesharesRequest {
+            flight_id: flights[0].id.clone(),
+            codeshares: r.codeshares.clone(),
+        })
+        .await
+        .unwrap();
+
+    // marketing number already used on the same date
+    let r = client
+        .flights
+        .set_codeshares(SetCodesharesRequest {
+            flight_id: flights[1].id.clone(),
+            codeshares: vec![codeshare(&airlines[1], "5000")],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // operating number already used as a marketing number
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let r = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(Flight {
+                carrier_id: airlines[2].id.clone(),
+                flight_number: "7000".to_string(),
+                ..default_flight(plane.id, airport.id.clone(), airport.id.clone())
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // lookup by a marketing number
+    let r = client
+        .flights
+        .get_flight_by_number(GetFlightByNumberRequest {
+            carrier: "TC".to_string(),
+            flight_number: "7000".to_string(),
+            date: Some(Default::default()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.id, flights[0].id);
+    assert_eq!(r.designator, "TA100");
+}
+
+#[sqlx::test]
+async fn concurrent_flight_numbers(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    let mut airlines = vec![];
+    for (iata, icao) in [("TA", "TAL"), ("TB", "TBL")] {
+        let airline = client
+            .airlines
+            .create_airline(CreateAirlineRequest {
+                airline: Some(Airline {
+                    iata: iata.to_string(),
+                    icao: icao.to_string(),
+                    name: "Test Airline".to_string(),
+                    callsign: "TEST".to_string(),
+                    ..Default::default()
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        airlines.push(airline);
+    }
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut planes = vec![];
+    for _ in 0..4 {
+        let plane = client
+            .planes
+            .create_plane(CreatePlaneRequest {
+                plane: Some(default_plane()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        planes.push(plane);
+    }
+    let flight = |plane: &Plane, flight_number: &str| Flight {
+        carrier_id: if flight_number.is_empty() {
+            Default::default()
+        } else {
+            airlines[0].id.clone()
+        },
+        flight_number: flight_number.to_string(),
+        ..default_flight(plane.id.clone(), airport.id.clone(), airport.id.clone())
+    };
+
+    let mut flights = vec![];
+    for (plane, flight_number) in planes.iter().zip(["100", "400", ""]) {
+        let r = client
+            .flights
+            .create_flight(CreateFlightRequest {
+                flight: Some(flight(plane, flight_number)),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        flights.push(r);
+    }
+
+    // another transaction takes the numbers while the requests are running
+    let mut t = db.begin().await.unwrap();
+    sqlx::query(
+        "update flights set carrier_id = $1::uuid, flight_number = '200' where id = $2::uuid",
+    )
+    .bind(&airlines[0].id)
+    .bind(&flights[2].id)
+    .execute(&mut *t)
+    .await
+    .unwrap();
+    sqlx::query(
+        "insert into flight_codeshares (flight_id, carrier_id, flight_number, departure_local_date) \
+        select id, $2::uuid, '300', departure_local_date from flights where id = $1::uuid",
+    )
+    .bind(&flights[0].id)
+    .bind(&airlines[1].id)
+    .execute(&mut *t)
+    .await
+    .unwrap();
+
+    let mut flights_client = client.flights.clone();
+    let request = flight(&planes[3], "200");
+    let create = tokio::spawn(async move {
+        flights_client
+            .create_flight(CreateFlightRequest {
+                flight: Some(request),
+            })
+            .await
+    });
+    let mut flights_client = client.flights.clone();
+    let request = SetCodesharesRequest {
+        flight_id: flights[1].id.clone(),
+        codeshares: vec![Codeshare {
+            carrier_id: airlines[1].id.clone(),
+            flight_number: "300".to_string(),
+            designator: Default::default(),
+        }],
+    };
+    let codeshares = tokio::spawn(async move { flights_client.set_codeshares(request).await });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    t.commit().await.unwrap();
+
+    let r = create.await.unwrap();
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    let r = codeshares.await.unwrap();
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+}
+
+#[sqlx::test]
+async fn drafts(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let flight = client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(Flight {
+                draft: true,
+                ..default_flight(plane.id, airport.id.clone(), airport.id.clone())
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(flight.draft);
+    assert_eq!(flight.published_at, None);
+
+    let search = SearchFlightsRequest {
+        origin_id: airport.id.clone(),
+        destination_id: airport.id.clone(),
+        departure_day: Some(Default::default()),
+        include_drafts: false,
+    };
+
+    // drafts are hidden unless requested
+    let r = client
+        .flights
+        .search_flights(search.clone())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.flights.is_empty());
+
+    let r = client
+        .flights
+        .list_flights(ListFlightsRequest {
+            include_cancelled: true,
+            include_drafts: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.flights.is_empty());
+
+    let r = client
+        .flights
+        .list_flights(ListFlightsRequest {
+            include_cancelled: false,
+            include_drafts: true,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights, vec![flight.clone()]);
+
+    let r = client
+        .flights
+        .publish_flights(PublishFlightsRequest {
+            flight_ids: vec![flight.id.clone()],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+    assert!(!r.flights[0].draft);
+    assert!(r.flights[0].published_at.is_some());
+
+    let r = client
+        .flights
+        .search_flights(search)
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights.len(), 1);
+
+    let r = client
+        .flights
+        .publish_flights(PublishFlightsRequest {
+            flight_ids: vec![flight.id],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+}
+
+#[sqlx::test]
+async fn diff(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let slot = |n: i64| {
+        Some(prost_types::Timestamp {
+            seconds: n * 3600,
+            nanos: 0,
+        })
+    };
+    let now = || {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        Some(prost_types::Timestamp {
+            seconds: now.as_secs() as i64,
+            nanos: now.subsec_nanos() as i32,
+        })
+    };
+    let update = |id: &str, event| UpdateFlightRequest {
+        id: id.to_string(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(event),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+
+    let delayed = setup_flight(&mut client).await;
+    let create = |n: i64| CreateFlightRequest {
+        flight: Some(Flight {
+            departure_time: slot(n),
+            arrival_time: slot(n + 1),
+            ..default_flight(
+                delayed.plane_id.clone(),
+                delayed.origin_id.clone(),
+                delayed.destination_id.clone(),
+            )
+        }),
+    };
+    let cancelled = client
+        .flights
+        .create_flight(create(2))
+        .await
+        .unwrap()
+        .into_inner();
+    let since = now();
+
+    client
+        .flights
+        .update_flight(update(
+            &delayed.id,
+            Event::FlightDelayed(FlightDelayed {
+                departure_time: slot(1),
+                arrival_time: slot(1),
+                reason: Default::default(),
+            }),
+        ))
+        .await
+        .unwrap();
+    client
+        .flights
+        .update_flight(update(
+            &cancelled.id,
+            Event::FlightCancelled(FlightCancelled {
+                reason: Default::default(),
+            }),
+        ))
+        .await
+        .unwrap();
+    let added = client
+        .flights
+        .create_flight(create(4))
+        .await
+        .unwrap()
+        .into_inner();
+    // drafts are not part of the published flights
+    client
+        .flights
+        .create_flight(CreateFlightRequest {
+            flight: Some(Flight {
+                draft: true,
+                ..create(6).flight.unwrap()
+            }),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .diff_flights(DiffFlightsRequest {
+            since: since.clone(),
+            until: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        r.added.iter().map(|f| &f.flight_id).collect::<Vec<_>>(),
+        vec![&added.id]
+    );
+    assert_eq!(
+        r.removed.iter().map(|f| &f.flight_id).collect::<Vec<_>>(),
+        vec![&cancelled.id]
+    );
+    assert_eq!(r.changed.len(), 1);
+    let change = &r.changed[0];
+    let (before, after) = (
+        change.before.as_ref().unwrap(),
+        change.after.as_ref().unwrap(),
+    );
+    assert_eq!(after.flight_id, delayed.id);
+    assert_eq!(before.departure_time, delayed.departure_time);
+    assert_eq!(after.departure_time, slot(1));
+
+    // before the changes, both flights were added since the epoch
+    let r = client
+        .flights
+        .diff_flights(DiffFlightsRequest {
+            since: slot(0),
+            until: since.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.added.len(), 2);
+    assert!(r.removed.is_empty() && r.changed.is_empty());
+
+    let r = client
+        .flights
+        .diff_flights(DiffFlightsRequest {
+            since,
+            until: slot(0),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+}
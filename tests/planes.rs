@@ -0,0 +1,299 @@
+use flightmngr::proto::flightmngr::{
+    CabinClass, CabinClassCapacity, CabinConfiguration, CreatePlaneRequest, DeletePlaneRequest,
+    GetPlaneByRegistrationRequest, Plane, RestorePlaneRequest, Seat,
+    SetPlaneCabinConfigurationRequest,
+};
+use sqlx::PgPool;
+
+mod common;
+
+fn example_plane() -> Plane {
+    Plane {
+        id: Default::default(),
+        deleted: false,
+        model: "A320neo".to_string(),
+        cabin_capacity: 180,
+        cargo_capacity_kg: 2000,
+        registration: "ei-tst".to_string(),
+        icao_type: "a20n".to_string(),
+        manufacturer: "Airbus".to_string(),
+        in_service_date: Some(prost_types::Timestamp {
+            seconds: 1_500_000_000,
+            nanos: 0,
+        }),
+        retirement_date: None,
+        type_id: Default::default(),
+        cabin_capacity_override: None,
+        cargo_capacity_kg_override: None,
+        aircraft_type: None,
+        cabin_configuration: None,
+    }
+}
+
+#[sqlx::test]
+async fn create_and_lookup(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    // create
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(example_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.registration, "EI-TST");
+    assert_eq!(r.icao_type, "A20N");
+    assert_eq!(r.manufacturer, "Airbus");
+    // dates are truncated to the day
+    assert_eq!(r.in_service_date.unwrap().seconds, 1_499_990_400);
+
+    let id = r.id;
+
+    // lookup
+    let r = client
+        .planes
+        .get_plane_by_registration(GetPlaneByRegistrationRequest {
+            registration: "ei-tst".to_string(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.id, id);
+}
+
+#[sqlx::test]
+async fn registration_unique(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    // create
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(example_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let id = r.id;
+
+    // create duplicate
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(example_plane()),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    // the registration is reusable once the plane is deleted
+    let _ = client
+        .planes
+        .delete_plane(DeletePlaneRequest {
+            id: id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let _ = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(example_plane()),
+        })
+        .await
+        .unwrap();
+
+    // but the deleted plane can no longer be restored
+    let r = client
+        .planes
+        .restore_plane(RestorePlaneRequest { id })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+}
+
+#[sqlx::test]
+async fn concurrent_registration(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    // another transaction registers the plane while the request is running
+    let mut t = db.begin().await.unwrap();
+    sqlx::query(
+        "insert into planes (id, model, cabin_capacity, cargo_capacity_kg, registration) \
+        values (gen_random_uuid(), 'A320neo', 180, 2000, 'EI-TST')",
+    )
+    .execute(&mut *t)
+    .await
+    .unwrap();
+
+    let request = tokio::spawn(async move {
+        client
+            .planes
+            .create_plane(CreatePlaneRequest {
+                plane: Some(example_plane()),
+            })
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    t.commit().await.unwrap();
+
+    let r = request.await.unwrap();
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+}
+
+#[sqlx::test]
+async fn concurrent_restore(db: PgPool) {
+    let mut client = common::make_test_client(db.clone()).await.unwrap();
+
+    let id = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(example_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+    let _ = client
+        .planes
+        .delete_plane(DeletePlaneRequest {
+            id: id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // another transaction registers a plane while the restore is running
+    let mut t = db.begin().await.unwrap();
+    sqlx::query(
+        "insert into planes (id, model, cabin_capacity, cargo_capacity_kg, registration) \
+        values (gen_random_uuid(), 'A320neo', 180, 2000, 'EI-TST')",
+    )
+    .execute(&mut *t)
+    .await
+    .unwrap();
+
+    let request = tokio::spawn(async move {
+        client
+            .planes
+            .restore_plane(RestorePlaneRequest { id })
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    t.commit().await.unwrap();
+
+    let r = request.await.unwrap();
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+}
+
+#[sqlx::test]
+async fn cabin_configuration(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    // a small plane with a row of business and two rows of economy
+    let r = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                cabin_capacity: 10,
+                ..example_plane()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // economy only by default
+    assert_eq!(
+        r.cabin_configuration.unwrap().classes,
+        vec![CabinClassCapacity {
+            class: CabinClass::Economy.into(),
+            capacity: 10,
+        }]
+    );
+
+    let id = r.id;
+    let seat = |row, letter: &str, class: CabinClass, blocked| Seat {
+        row,
+        letter: letter.to_string(),
+        class: class.into(),
+        exit_row: row == 3,
+        blocked,
+    };
+    let mut seats = vec![
+        seat(1, "A", CabinClass::Business, false),
+        seat(1, "B", CabinClass::Business, false),
+    ];
+    for row in 2..=3 {
+        for letter in ["A", "B", "C", "D"] {
+            seats.push(seat(row, letter, CabinClass::Economy, false));
+        }
+    }
+    let classes = |business, economy| {
+        vec![
+            CabinClassCapacity {
+                class: CabinClass::Business.into(),
+                capacity: business,
+            },
+            CabinClassCapacity {
+                class: CabinClass::Economy.into(),
+                capacity: economy,
+            },
+        ]
+    };
+
+    // totals must match the cabin capacity
+    let r = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: id.clone(),
+            configuration: Some(CabinConfiguration {
+                classes: classes(2, 6),
+                seats: Default::default(),
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // and the seat map must match the class capacities
+    let mut blocked = seats.clone();
+    blocked[2].blocked = true;
+    let r = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: id.clone(),
+            configuration: Some(CabinConfiguration {
+                classes: classes(2, 8),
+                seats: blocked,
+            }),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let r = client
+        .planes
+        .set_plane_cabin_configuration(SetPlaneCabinConfigurationRequest {
+            plane_id: id.clone(),
+            configuration: Some(CabinConfiguration {
+                classes: classes(2, 8),
+                seats: seats.clone(),
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let configuration = r.cabin_configuration.unwrap();
+    assert_eq!(configuration.classes, classes(2, 8));
+    assert_eq!(configuration.seats, seats);
+}
@@ -0,0 +1,296 @@
+use flightmngr::proto::flightmngr::{
+    flight_status_event::Event, AircraftType, Airport, BlockTime, CreateAircraftTypeRequest,
+    CreateAirportRequest, CreateFlightRequest, CreatePlaneRequest, CreateRouteRequest,
+    DeleteRouteRequest, Flight, FlightCancelled, FlightDelayed, FlightStatusEvent, GetRouteRequest,
+    Plane, Route, RouteFlightsRequest, SetRouteBlockTimesRequest, UpdateFlightRequest,
+};
+use sqlx::PgPool;
+
+mod common;
+
+fn hour(n: i64) -> Option<prost_types::Timestamp> {
+    Some(prost_types::Timestamp {
+        seconds: n * 3600,
+        nanos: 0,
+    })
+}
+
+fn airport(iata: &str, coordinates: Option<(f64, f64)>) -> Airport {
+    Airport {
+        name: format!("Test Airport {iata}"),
+        city: "Test City".to_string(),
+        country: "Test Country".to_string(),
+        iata: iata.to_string(),
+        icao: format!("T{iata}"),
+        timezone: "UTC".to_string(),
+        latitude: coordinates.map(|c| c.0),
+        longitude: coordinates.map(|c| c.1),
+        ..Default::default()
+    }
+}
+
+fn route(origin_id: &str, destination_id: &str) -> CreateRouteRequest {
+    CreateRouteRequest {
+        route: Some(Route {
+            origin_id: origin_id.to_string(),
+            destination_id: destination_id.to_string(),
+            ..Default::default()
+        }),
+    }
+}
+
+#[sqlx::test]
+async fn routes(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let mut airports = Vec::new();
+    for airport in [
+        airport("FCO", Some((41.8, 12.25))),
+        airport("LHR", Some((51.47, -0.45))),
+        airport("XXX", None),
+    ] {
+        let airport = client
+            .airports
+            .create_airport(CreateAirportRequest {
+                airport: Some(airport),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        airports.push(airport.id);
+    }
+    let (fco, lhr, unknown) = (&airports[0], &airports[1], &airports[2]);
+
+    let route = client
+        .routes
+        .create_route(route(fco, lhr))
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(route.distance_km, 1444);
+    assert!(route.block_times.is_empty());
+
+    let r = client.routes.create_route(self::route(fco, lhr)).await;
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+
+    let r = client.routes.create_route(self::route(fco, unknown)).await;
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    let r = client.routes.create_route(self::route(fco, fco)).await;
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let r = client
+        .routes
+        .get_route(GetRouteRequest {
+            id: route.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r, route);
+
+    client
+        .routes
+        .delete_route(DeleteRouteRequest {
+            id: route.id.clone(),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .routes
+        .get_route(GetRouteRequest { id: route.id })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.deleted);
+
+    // the airports can be connected again
+    client
+        .routes
+        .create_route(self::route(fco, lhr))
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn block_times_and_performance(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let mut airports = Vec::new();
+    for airport in [
+        airport("FCO", Some((41.8, 12.25))),
+        airport("LHR", Some((51.47, -0.45))),
+    ] {
+        let airport = client
+            .airports
+            .create_airport(CreateAirportRequest {
+                airport: Some(airport),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        airports.push(airport.id);
+    }
+    let (fco, lhr) = (&airports[0], &airports[1]);
+
+    let aircraft_type = client
+        .aircraft_types
+        .create_aircraft_type(CreateAircraftTypeRequest {
+            aircraft_type: Some(AircraftType {
+                icao_designator: "A20N".to_string(),
+                name: "Airbus A320neo".to_string(),
+                typical_seats: 180,
+                cargo_capacity_kg: 3000,
+                max_range_km: 6300,
+                cruise_speed_kmh: 833,
+                min_turnaround_minutes: 35,
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                model: "Test Model".to_string(),
+                type_id: aircraft_type.id.clone(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let route = client
+        .routes
+        .create_route(route(fco, lhr))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let block_times = vec![BlockTime {
+        aircraft_type_id: aircraft_type.id.clone(),
+        minutes: 150,
+    }];
+    let r = client
+        .routes
+        .set_route_block_times(SetRouteBlockTimesRequest {
+            route_id: route.id.clone(),
+            block_times: block_times.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.block_times, block_times);
+
+    let r = client
+        .routes
+        .set_route_block_times(SetRouteBlockTimesRequest {
+            route_id: route.id.clone(),
+            block_times: vec![BlockTime {
+                minutes: 0,
+                ..block_times[0].clone()
+            }],
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    // the arrival defaults to the block time of the route
+    let flight = |origin_id: &str, destination_id: &str, departure: i64| CreateFlightRequest {
+        flight: Some(Flight {
+            plane_id: plane.id.clone(),
+            origin_id: origin_id.to_string(),
+            destination_id: destination_id.to_string(),
+            departure_time: hour(departure),
+            ..Default::default()
+        }),
+    };
+    let mut flights = Vec::new();
+    for departure in [0, 6, 12] {
+        let r = client
+            .flights
+            .create_flight(flight(fco, lhr, departure))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            r.arrival_time.as_ref().unwrap().seconds,
+            departure * 3600 + 150 * 60
+        );
+        flights.push(r);
+    }
+
+    // no route in the other direction
+    let r = client.flights.create_flight(flight(lhr, fco, 3)).await;
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let update = |flight: &Flight, event| UpdateFlightRequest {
+        id: flight.id.clone(),
+        status_event: Some(FlightStatusEvent {
+            event: Some(event),
+            ..Default::default()
+        }),
+        expected_version: None,
+    };
+    client
+        .flights
+        .update_flight(update(
+            &flights[1],
+            Event::FlightDelayed(FlightDelayed {
+                departure_time: hour(7),
+                arrival_time: hour(9),
+                reason: Default::default(),
+            }),
+        ))
+        .await
+        .unwrap();
+    client
+        .flights
+        .update_flight(update(
+            &flights[2],
+            Event::FlightCancelled(FlightCancelled {
+                reason: Default::default(),
+            }),
+        ))
+        .await
+        .unwrap();
+
+    let request = RouteFlightsRequest {
+        route_id: route.id.clone(),
+        from: hour(0),
+        to: hour(24),
+    };
+    let r = client
+        .routes
+        .list_route_flights(request.clone())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(
+        r.flights.iter().map(|f| &f.id).collect::<Vec<_>>(),
+        flights.iter().map(|f| &f.id).collect::<Vec<_>>()
+    );
+
+    let r = client
+        .routes
+        .get_route_performance(request)
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.flights, 3);
+    assert_eq!(r.cancelled, 1);
+    assert_eq!(r.on_time, 1);
+    assert_eq!(r.average_delay_minutes, 30.0);
+}
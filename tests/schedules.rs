@@ -0,0 +1,676 @@
+use flightmngr::proto::flightmngr::{
+    flight_status_event::Event, get_season_request, AircraftType, Airport, AirportClosure,
+    CopySeasonRequest, CreateAircraftTypeRequest, CreateAirportRequest, CreatePlaneRequest,
+    CreateScheduleRequest, DeleteScheduleRequest, DiffFlightsRequest, FlightGateDeparture,
+    FlightStatusEvent, GenerateScheduleFlightsRequest, GetFlightRequest, GetSeasonRequest,
+    ListSchedulesRequest, Plane, Schedule, ScheduleAirportClosureRequest, SeasonConflictKind,
+    UpdateFlightRequest, UpdateScheduleRequest,
+};
+use sqlx::PgPool;
+use std::time::SystemTime;
+
+mod common;
+
+/// Midnight UTC, `days` after today.
+fn day(days: i64) -> prost_types::Timestamp {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    prost_types::Timestamp {
+        seconds: (now / 86400 + days) * 86400,
+        nanos: 0,
+    }
+}
+
+/// Midnight UTC on a date.
+fn date(year: i32, month: u8, day: u8) -> prost_types::Timestamp {
+    let date = time::Date::from_calendar_date(year, month.try_into().unwrap(), day).unwrap();
+
+    prost_types::Timestamp {
+        seconds: date.midnight().assume_utc().unix_timestamp(),
+        nanos: 0,
+    }
+}
+
+fn default_airport() -> Airport {
+    Airport {
+        id: Default::default(),
+        deleted: false,
+        name: "Test Airport".to_string(),
+        city: "Test City".to_string(),
+        country: "Test Country".to_string(),
+        iata: "TST".to_string(),
+        icao: "TSTT".to_string(),
+        timezone: "UTC".to_string(),
+        latitude: None,
+        longitude: None,
+    }
+}
+
+fn default_plane() -> Plane {
+    Plane {
+        model: "Test Model".to_string(),
+        cabin_capacity: 200,
+        cargo_capacity_kg: 1000,
+        ..Default::default()
+    }
+}
+
+fn default_schedule(origin_id: String, destination_id: String) -> Schedule {
+    Schedule {
+        origin_id,
+        destination_id,
+        departure_local_minutes: 10 * 60,
+        arrival_local_minutes: 12 * 60,
+        days_of_week: (1..=7).collect(),
+        valid_from: Some(day(1)),
+        valid_to: Some(day(30)),
+        ..Default::default()
+    }
+}
+
+#[sqlx::test]
+async fn generate_update_delete(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // neither a plane nor an aircraft type
+    let r = client
+        .schedules
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(default_schedule(airport.id.clone(), airport.id.clone())),
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::InvalidArgument));
+
+    let schedule = client
+        .schedules
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(Schedule {
+                plane_id: plane.id.clone(),
+                exceptions: vec![day(3)],
+                ..default_schedule(airport.id.clone(), airport.id.clone())
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // generate a week
+    let r = client
+        .schedules
+        .generate_schedule_flights(GenerateScheduleFlightsRequest {
+            schedule_id: schedule.id.clone(),
+            until: Some(day(7)),
+            draft: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.created.len(), 6);
+    assert!(r.skipped.is_empty());
+    assert!(r.created.iter().all(|f| f.schedule_id == schedule.id));
+    assert_eq!(
+        r.created[0].departure_time.as_ref().unwrap().seconds,
+        day(1).seconds + 10 * 3600
+    );
+    let flights = r.created;
+
+    // generating again is a no-op
+    let r = client
+        .schedules
+        .generate_schedule_flights(GenerateScheduleFlightsRequest {
+            schedule_id: schedule.id.clone(),
+            until: Some(day(7)),
+            draft: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.created.is_empty());
+
+    // modify the first flight individually
+    client
+        .flights
+        .update_flight(UpdateFlightRequest {
+            id: flights[0].id.clone(),
+            status_event: Some(FlightStatusEvent {
+                event: Some(Event::FlightGateDeparture(FlightGateDeparture {
+                    gate: "A1".to_string(),
+                })),
+                ..Default::default()
+            }),
+            expected_version: None,
+        })
+        .await
+        .unwrap();
+
+    // depart an hour later and stop after five days
+    let r = client
+        .schedules
+        .update_schedule(UpdateScheduleRequest {
+            schedule: Some(Schedule {
+                departure_local_minutes: 11 * 60,
+                valid_to: Some(day(5)),
+                ..schedule.clone()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.created.is_empty());
+    assert_eq!(r.updated.len(), 3);
+    assert!(r
+        .updated
+        .iter()
+        .all(|f| f.departure_time.as_ref().unwrap().seconds % 86400 == 11 * 3600));
+    assert_eq!(r.cancelled.len(), 2);
+    assert!(r.cancelled.iter().all(|f| f.is_cancelled));
+
+    let r = client
+        .flights
+        .get_flight(GetFlightRequest {
+            id: flights[0].id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.departure_time, flights[0].departure_time);
+
+    // deleting cancels the remaining unmodified flights
+    let r = client
+        .schedules
+        .delete_schedule(DeleteScheduleRequest {
+            id: schedule.id.clone(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cancelled.len(), 3);
+    assert!(r.schedule.unwrap().deleted);
+}
+
+#[sqlx::test]
+async fn exception_removed(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let schedule = client
+        .schedules
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(Schedule {
+                plane_id: plane.id.clone(),
+                ..default_schedule(airport.id.clone(), airport.id.clone())
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let r = client
+        .schedules
+        .generate_schedule_flights(GenerateScheduleFlightsRequest {
+            schedule_id: schedule.id.clone(),
+            until: Some(day(5)),
+            draft: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.created.len(), 5);
+    let flight = r.created[2].clone();
+
+    // an exception cancels the flight of its date
+    let r = client
+        .schedules
+        .update_schedule(UpdateScheduleRequest {
+            schedule: Some(Schedule {
+                exceptions: vec![day(3)],
+                ..schedule.clone()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.cancelled.len(), 1);
+    assert_eq!(r.cancelled[0].id, flight.id);
+
+    // and only once
+    let r = client
+        .schedules
+        .generate_schedule_flights(GenerateScheduleFlightsRequest {
+            schedule_id: schedule.id.clone(),
+            until: Some(day(5)),
+            draft: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.created.is_empty());
+    assert!(r.updated.is_empty());
+    assert!(r.cancelled.is_empty());
+
+    // removing the exception brings the flight back
+    let r = client
+        .schedules
+        .update_schedule(UpdateScheduleRequest {
+            schedule: Some(schedule.clone()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.created.is_empty());
+    assert_eq!(r.updated.len(), 1);
+    assert_eq!(r.updated[0].id, flight.id);
+    assert!(!r.updated[0].is_cancelled);
+
+    // and the schedule still manages it
+    let r = client
+        .schedules
+        .update_schedule(UpdateScheduleRequest {
+            schedule: Some(Schedule {
+                departure_local_minutes: 11 * 60,
+                ..schedule.clone()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.updated.len(), 5);
+}
+
+#[sqlx::test]
+async fn schedule_changes_in_diff(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let schedule = client
+        .schedules
+        .create_schedule(CreateScheduleRequest {
+            schedule: Some(Schedule {
+                plane_id: plane.id.clone(),
+                valid_to: Some(day(2)),
+                ..default_schedule(airport.id.clone(), airport.id.clone())
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    let flights = client
+        .schedules
+        .generate_schedule_flights(GenerateScheduleFlightsRequest {
+            schedule_id: schedule.id.clone(),
+            until: Some(day(3)),
+            draft: false,
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .created;
+
+    assert_eq!(flights.len(), 2);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let since = prost_types::Timestamp {
+        seconds: now.as_secs() as i64,
+        nanos: now.subsec_nanos() as i32,
+    };
+
+    // depart an hour later
+    client
+        .schedules
+        .update_schedule(UpdateScheduleRequest {
+            schedule: Some(Schedule {
+                departure_local_minutes: 11 * 60,
+                ..schedule.clone()
+            }),
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .flights
+        .diff_flights(DiffFlightsRequest {
+            since: Some(since),
+            until: None,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(r.added.is_empty() && r.removed.is_empty());
+    assert_eq!(r.changed.len(), 2);
+    for (change, flight) in r.changed.iter().zip(&flights) {
+        let (before, after) = (
+            change.before.as_ref().unwrap(),
+            change.after.as_ref().unwrap(),
+        );
+        assert_eq!(after.flight_id, flight.id);
+        assert_eq!(before.departure_time, flight.departure_time);
+        assert_eq!(
+            after.departure_time.as_ref().unwrap().seconds,
+            flight.departure_time.as_ref().unwrap().seconds + 3600
+        );
+    }
+}
+
+#[sqlx::test]
+async fn aircraft_type_planes(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let aircraft_type = client
+        .aircraft_types
+        .create_aircraft_type(CreateAircraftTypeRequest {
+            aircraft_type: Some(AircraftType {
+                icao_designator: "A20N".to_string(),
+                name: "Airbus A320neo".to_string(),
+                typical_seats: 180,
+                cargo_capacity_kg: 3000,
+                max_range_km: 6300,
+                cruise_speed_kmh: 833,
+                min_turnaround_minutes: 35,
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(Plane {
+                type_id: aircraft_type.id.clone(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let schedule = Schedule {
+        aircraft_type_id: aircraft_type.id.clone(),
+        ..default_schedule(airport.id.clone(), airport.id.clone())
+    };
+
+    let mut responses = vec![];
+    for _ in 0..2 {
+        let schedule = client
+            .schedules
+            .create_schedule(CreateScheduleRequest {
+                schedule: Some(schedule.clone()),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let r = client
+            .schedules
+            .generate_schedule_flights(GenerateScheduleFlightsRequest {
+                schedule_id: schedule.id,
+                until: Some(day(3)),
+                draft: false,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        responses.push(r);
+    }
+
+    assert_eq!(responses[0].created.len(), 3);
+    assert!(responses[0].created.iter().all(|f| f.plane_id == plane.id));
+
+    // the only plane of the type is already flying
+    assert!(responses[1].created.is_empty());
+    assert_eq!(responses[1].skipped.len(), 3);
+}
+
+#[sqlx::test]
+async fn seasons(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let r = client
+        .schedules
+        .get_season(GetSeasonRequest {
+            season: Some(get_season_request::Season::Code("s26".to_string())),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.code, "S26");
+    assert_eq!(r.start, Some(date(2026, 3, 29)));
+    assert_eq!(r.end, Some(date(2026, 10, 24)));
+
+    let r = client
+        .schedules
+        .get_season(GetSeasonRequest {
+            season: Some(get_season_request::Season::Date(date(2027, 1, 15))),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.code, "W26");
+    assert_eq!(r.start, Some(date(2026, 10, 25)));
+    assert_eq!(r.end, Some(date(2027, 3, 27)));
+}
+
+#[sqlx::test]
+async fn copy_season(db: PgPool) {
+    let mut client = common::make_test_client(db).await.unwrap();
+
+    let airport = client
+        .airports
+        .create_airport(CreateAirportRequest {
+            airport: Some(default_airport()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let plane = client
+        .planes
+        .create_plane(CreatePlaneRequest {
+            plane: Some(default_plane()),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    // daily, and on Wednesdays an hour later with the same plane
+    let daily = Schedule {
+        plane_id: plane.id.clone(),
+        valid_from: Some(date(2027, 4, 1)),
+        valid_to: Some(date(2027, 4, 30)),
+        ..default_schedule(airport.id.clone(), airport.id.clone())
+    };
+    let wednesdays = Schedule {
+        departure_local_minutes: 11 * 60,
+        arrival_local_minutes: 13 * 60,
+        days_of_week: vec![3],
+        ..daily.clone()
+    };
+    for schedule in [daily, wednesdays] {
+        client
+            .schedules
+            .create_schedule(CreateScheduleRequest {
+                schedule: Some(schedule),
+            })
+            .await
+            .unwrap();
+    }
+
+    // 2027-04-07 is shifted 31 weeks to 2027-11-10
+    client
+        .airports
+        .schedule_airport_closure(ScheduleAirportClosureRequest {
+            closure: Some(AirportClosure {
+                airport_id: airport.id.clone(),
+                start_time: Some(date(2027, 11, 10)),
+                end_time: Some(date(2027, 11, 11)),
+                reason: "runway works".to_string(),
+                ..Default::default()
+            }),
+        })
+        .await
+        .unwrap();
+
+    let copy = CopySeasonRequest {
+        season: "S27".to_string(),
+        target_season: Default::default(),
+        dry_run: true,
+        ignore_conflicts: false,
+    };
+
+    let r = client
+        .schedules
+        .copy_season(copy.clone())
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.target_season.unwrap().code, "W27");
+    assert_eq!(r.schedules.len(), 2);
+    assert!(r
+        .schedules
+        .iter()
+        .all(|s| s.valid_from == Some(date(2027, 11, 4)) && s.valid_to == Some(date(2027, 12, 3))));
+    let count = |kind: SeasonConflictKind| {
+        r.conflicts
+            .iter()
+            .filter(|c| c.kind == i32::from(kind))
+            .count()
+    };
+    // four Wednesdays, departure and arrival on the closed day for both schedules
+    assert_eq!(count(SeasonConflictKind::PlaneOverlap), 4);
+    assert_eq!(count(SeasonConflictKind::AirportClosed), 4);
+
+    // the dry run saved nothing
+    let r = client
+        .schedules
+        .list_schedules(ListSchedulesRequest {
+            show_deleted: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.schedules.len(), 2);
+
+    let r = client
+        .schedules
+        .copy_season(CopySeasonRequest {
+            dry_run: false,
+            ..copy.clone()
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::FailedPrecondition));
+
+    client
+        .schedules
+        .copy_season(CopySeasonRequest {
+            dry_run: false,
+            ignore_conflicts: true,
+            ..copy.clone()
+        })
+        .await
+        .unwrap();
+
+    let r = client
+        .schedules
+        .list_schedules(ListSchedulesRequest {
+            show_deleted: false,
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert_eq!(r.schedules.len(), 4);
+
+    // copying the season again would duplicate the schedules
+    let r = client
+        .schedules
+        .copy_season(CopySeasonRequest {
+            dry_run: false,
+            ignore_conflicts: true,
+            ..copy
+        })
+        .await;
+
+    assert!(r.is_err_and(|e| e.code() == tonic::Code::AlreadyExists));
+}